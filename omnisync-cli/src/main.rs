@@ -1,7 +1,15 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use omnisync_core::{providers::gdrive::GoogleDriveProvider, SyncEngine};
+use omnisync_core::credential_store::{KeyringCredentialStore, KEYRING_SERVICE};
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::models::{RemoteTrashItem, SyncMode, TrashItem};
+use omnisync_core::reconcile::SyncPlan;
+use omnisync_core::registry::provider_type;
+use omnisync_core::{SyncEngine, SyncPair};
+use serde::Serialize;
+use serde_json::json;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -11,6 +19,16 @@ struct Args {
     #[arg(short, long, default_value = "./omnisync.db")]
     db_path: String,
 
+    /// Control socket of the daemon (default: the database path with ".sock" appended).
+    /// While a daemon is running, every command but `daemon`, `login` and `accounts`
+    /// goes through it.
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,28 +37,396 @@ struct Args {
 enum Commands {
     /// Start the sync daemon
     Daemon,
-    /// Login to a cloud provider
+    /// Show what a running daemon is doing
+    Status,
+    /// Manage sync pairs
+    #[command(subcommand)]
+    Pairs(PairCommands),
+    /// Manage connected accounts
+    #[command(subcommand)]
+    Accounts(AccountCommands),
+    /// Connect a gdrive, onedrive or dropbox account. Works without a local browser:
+    /// open the printed link anywhere and paste back the address it ends on.
     Login {
-        #[arg(long)]
         provider: String,
+    },
+    /// Sync a pair now and wait for it to finish
+    Sync {
+        pair_id: i64,
+    },
+    /// Show what the next sync of a pair would do, without doing it
+    Plan {
+        pair_id: i64,
+    },
+    /// List deletions held back by the mass-deletion safeguard, or settle them
+    Deletions {
+        pair_id: i64,
+        /// Delete them on the cloud and resume the pair
+        #[arg(long, conflicts_with = "restore")]
+        confirm: bool,
+        /// Download the files again and resume the pair
+        #[arg(long)]
+        restore: bool,
+    },
+    /// List the local files sync deleted or overwrote in a pair, or restore one
+    Trash {
+        pair_id: i64,
+        /// The cloud trash instead: entries the pair deleted on the cloud
         #[arg(long)]
-        token: String,
+        remote: bool,
+        /// Put this item back where it was
+        #[arg(long, value_name = "ITEM_ID")]
+        restore: Option<i64>,
+        /// Keep local items for this many days from now on (0: forever)
+        #[arg(long, value_name = "DAYS", conflicts_with = "remote")]
+        retention: Option<i64>,
+        /// Delete on the cloud for good instead of using the cloud trash
+        #[arg(long, value_name = "BOOL")]
+        permanent_delete: Option<bool>,
     },
+}
+
+#[derive(Subcommand, Debug)]
+enum PairCommands {
+    /// List sync pairs
+    List,
     /// Add a sync pair
     Add {
         #[arg(long)]
         local: String,
+        /// Remote folder id ("root" for the top level)
         #[arg(long)]
         remote: String,
+        /// Display name of the remote folder (defaults to the id)
+        #[arg(long)]
+        remote_name: Option<String>,
+        /// Account to sync with, e.g. "gdrive:user@gmail.com" (see `accounts list`)
+        #[arg(long)]
+        account: String,
+        /// two-way, upload-only, download-only or mirror
+        #[arg(long, default_value = "two-way")]
+        mode: SyncMode,
+        /// Add it paused, e.g. to check `plan` first
+        #[arg(long)]
+        paused: bool,
+    },
+    /// Remove a sync pair. Files are left alone on both sides.
+    Remove {
+        pair_id: i64,
+    },
+    /// Stop watching and polling a pair
+    Pause {
+        pair_id: i64,
+    },
+    /// Watch and poll a paused pair again
+    Resume {
+        pair_id: i64,
+    },
+    /// Change which way a sync pair syncs
+    Mode {
+        pair_id: i64,
+        /// two-way, upload-only, download-only or mirror
+        mode: SyncMode,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AccountCommands {
+    /// List connected accounts
+    List,
+    /// Connect an account configured with connection settings (local, s3, webdav, sftp).
+    /// Cloud drives sign in with `login` instead.
+    Add {
         #[arg(long)]
         provider: String,
+        /// Connection settings as JSON, e.g. '{"path": "/mnt/backup"}' for local
+        #[arg(long)]
+        config: String,
+        /// Password or secret key, where the provider needs one
+        #[arg(long, default_value = "")]
+        secret: String,
+    },
+    /// Disconnect an account. Its sync pairs stay and resume once it is connected again.
+    Remove {
+        account_id: String,
     },
 }
 
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// One line per status event, as text or as a JSON object
+fn status_printer(json: bool) -> Arc<impl Fn(SyncStatus) + Send + Sync + 'static> {
+    Arc::new(move |status: SyncStatus| {
+        if json {
+            if let Ok(line) = serde_json::to_string(&status) {
+                println!("{}", line);
+            }
+            return;
+        }
+        match status {
+            SyncStatus::Idle { .. } | SyncStatus::Syncing { .. } | SyncStatus::Progress { .. } => {}
+            SyncStatus::Downloading { path, .. } => println!("Downloading {}", path),
+            SyncStatus::Uploaded { path, .. } => println!("Synced {}", path),
+            SyncStatus::Deleted { path, .. } => println!("Deleted {}", path),
+            SyncStatus::Moved { from, path, .. } => println!("Moved {} -> {}", from, path),
+            SyncStatus::Conflict { path, conflict_path, .. } => println!("Conflict on {}, local copy kept as {}", path, conflict_path),
+            SyncStatus::Error { path, message, .. } => eprintln!("Error on {}: {}", path, message),
+            SyncStatus::AuthExpired { account_id } => eprintln!("Session for {} expired; connect the account again", account_id),
+            SyncStatus::DeletionsPending { pair_id, count } => {
+                eprintln!("Sync pair {} paused: {} deletion(s) need confirming (see `deletions {}`)", pair_id, count, pair_id)
+            }
+        }
+    })
+}
+
+fn print_pairs(pairs: &[SyncPair], json: bool) -> Result<()> {
+    if json {
+        return print_json(&pairs);
+    }
+    if pairs.is_empty() {
+        println!("No sync pairs; add one with `pairs add`");
+    }
+    for pair in pairs {
+        let last_sync = pair.last_sync_at.map(|t| format!("synced {}d ago", days_ago(t))).unwrap_or_else(|| "never synced".to_string());
+        println!(
+            "{}\t{}\t{}\t{} -> {} ({})\t{}",
+            pair.id, pair.status, pair.sync_mode().as_str(), pair.local_path, pair.remote_name, pair.account_id, last_sync
+        );
+    }
+    Ok(())
+}
+
+fn print_plan(plan: &SyncPlan, json: bool) -> Result<()> {
+    if json {
+        return print_json(plan);
+    }
+    if plan.actions.is_empty() {
+        println!("Sync pair {} is up to date", plan.pair_id);
+    }
+    for action in &plan.actions {
+        println!("{}", action);
+    }
+    Ok(())
+}
+
+fn print_pending_deletions(pending: &[String], json: bool) -> Result<()> {
+    if json {
+        return print_json(&pending);
+    }
+    for path in pending {
+        println!("{}", path);
+    }
+    println!("{} pending deletion(s); settle with --confirm or --restore", pending.len());
+    Ok(())
+}
+
+fn print_trash(items: &[TrashItem], json: bool) -> Result<()> {
+    if json {
+        return print_json(&items);
+    }
+    for item in items {
+        println!("{}\t{}\t{}d ago\t{}{}", item.id, item.reason, days_ago(item.trashed_at), item.path, if item.is_dir { "/" } else { "" });
+    }
+    println!("{} item(s) in the trash; put one back with --restore <ITEM_ID>", items.len());
+    Ok(())
+}
+
+fn print_remote_trash(items: &[RemoteTrashItem], json: bool) -> Result<()> {
+    if json {
+        return print_json(&items);
+    }
+    for item in items {
+        println!("{}\t{}d ago\t{}{}", item.id, days_ago(item.trashed_at), item.path, if item.is_dir { "/" } else { "" });
+    }
+    println!("{} item(s) in the cloud trash; put one back with --remote --restore <ITEM_ID>", items.len());
+    Ok(())
+}
+
+/// What a pair is doing, going by its latest event
+fn describe_activity(status: Option<SyncStatus>) -> String {
+    match status {
+        None | Some(SyncStatus::Idle { .. }) => "idle".to_string(),
+        Some(SyncStatus::Syncing { path, .. }) => format!("syncing {}", path),
+        Some(SyncStatus::Downloading { path, .. }) => format!("downloading {}", path),
+        Some(SyncStatus::Progress { path, bytes, total, .. }) => format!("uploading {} ({}%)", path, bytes * 100 / total.max(1)),
+        Some(SyncStatus::Uploaded { path, .. }) => format!("synced {}", path),
+        Some(SyncStatus::Deleted { path, .. }) => format!("deleted {}", path),
+        Some(SyncStatus::Moved { from, path, .. }) => format!("moved {} -> {}", from, path),
+        Some(SyncStatus::Conflict { path, .. }) => format!("conflict on {}", path),
+        Some(SyncStatus::Error { path, message, .. }) => format!("error on {}: {}", path, message),
+        Some(SyncStatus::DeletionsPending { count, .. }) => format!("{} deletion(s) need confirming", count),
+        Some(SyncStatus::AuthExpired { .. }) => "session expired".to_string(),
+    }
+}
+
+/// Run the command through the daemon on `socket`, if one is running and the command
+/// is one it serves. Returns false when the command should use the database instead.
+/// Pairs are only changed through a running daemon, so that it watches and syncs along.
+#[cfg(unix)]
+async fn via_daemon(socket: &Path, command: &Commands, json: bool) -> Result<bool> {
+    use omnisync_core::control::ControlClient;
+
+    let served = !matches!(command, Commands::Daemon | Commands::Login { .. } | Commands::Accounts(_));
+    if !served || !socket.exists() {
+        return Ok(false);
+    }
+    let Ok(mut client) = ControlClient::connect(socket).await else {
+        return Ok(false);
+    };
+
+    match command {
+        Commands::Status => {
+            let status = client.call("status", serde_json::Value::Null).await?;
+            if json {
+                print_json(&status)?;
+            } else {
+                println!("Daemon running (pid {})", status["pid"]);
+                for pair in status["pairs"].as_array().into_iter().flatten() {
+                    let activity = serde_json::from_value(pair["activity"].clone()).ok();
+                    println!("{}\t{}\t{}", pair["pair_id"], pair["status"].as_str().unwrap_or_default(), describe_activity(activity));
+                }
+            }
+        }
+        Commands::Sync { pair_id } => {
+            call_printing_events(&mut client, "sync", *pair_id, json).await?;
+            if !json {
+                println!("Sync pair {} is done syncing", pair_id);
+            }
+        }
+        Commands::Plan { pair_id } => {
+            let plan: SyncPlan = serde_json::from_value(client.call("plan", json!({ "pair_id": pair_id })).await?)?;
+            print_plan(&plan, json)?;
+        }
+        Commands::Deletions { pair_id, confirm, restore } => {
+            if *confirm {
+                call_printing_events(&mut client, "confirm_deletions", *pair_id, json).await?;
+            } else if *restore {
+                call_printing_events(&mut client, "restore_deletions", *pair_id, json).await?;
+            } else {
+                let pending: Vec<String> = serde_json::from_value(client.call("deletions", json!({ "pair_id": pair_id })).await?)?;
+                print_pending_deletions(&pending, json)?;
+            }
+        }
+        Commands::Trash { pair_id, remote, restore, retention, permanent_delete } => {
+            if let Some(days) = retention {
+                client.call("set_trash_retention", json!({ "pair_id": pair_id, "days": days })).await?;
+                if !json {
+                    println!("Trash of sync pair {} is kept for {} day(s) (0: forever)", pair_id, (*days).max(0));
+                }
+            }
+            if let Some(permanent) = permanent_delete {
+                client.call("set_permanent_delete", json!({ "pair_id": pair_id, "permanent": permanent })).await?;
+                if !json {
+                    println!("Sync pair {} {} on the cloud", pair_id, if *permanent { "deletes for good" } else { "moves deletions to the trash" });
+                }
+            }
+            match restore {
+                Some(id) => {
+                    client.call("restore_trash", json!({ "item_id": id, "remote": remote })).await?;
+                    if !json {
+                        println!("Restored trash item {}{}", id, if *remote { " on the cloud" } else { "" });
+                    }
+                }
+                None if retention.is_some() || permanent_delete.is_some() => {}
+                None => {
+                    let items = client.call("trash", json!({ "pair_id": pair_id, "remote": remote })).await?;
+                    if *remote {
+                        print_remote_trash(&serde_json::from_value::<Vec<RemoteTrashItem>>(items)?, json)?;
+                    } else {
+                        print_trash(&serde_json::from_value::<Vec<TrashItem>>(items)?, json)?;
+                    }
+                }
+            }
+        }
+        Commands::Pairs(PairCommands::Add { local, remote, remote_name, account, mode, paused }) => {
+            // The daemon runs elsewhere, so relative paths are resolved here
+            let local = Path::new(local).canonicalize()?;
+            let params = json!({
+                "local": local,
+                "remote": remote,
+                "remote_name": remote_name,
+                "account": account,
+                "mode": mode,
+                "paused": paused,
+            });
+            let pair: SyncPair = serde_json::from_value(client.call("add_pair", params).await?)?;
+            if json {
+                print_json(&pair)?;
+            } else {
+                println!("Added sync pair with ID: {}", pair.id);
+            }
+        }
+        Commands::Pairs(PairCommands::Remove { pair_id }) => {
+            client.call("remove_pair", json!({ "pair_id": pair_id })).await?;
+            if !json {
+                println!("Removed sync pair {}", pair_id);
+            }
+        }
+        Commands::Pairs(PairCommands::Mode { pair_id, mode }) => {
+            client.call("set_mode", json!({ "pair_id": pair_id, "mode": mode })).await?;
+            if !json {
+                println!("Sync pair {} is now {}", pair_id, mode.as_str());
+            }
+        }
+        Commands::Pairs(PairCommands::List) => {
+            let pairs: Vec<SyncPair> = serde_json::from_value(client.call("list_pairs", serde_json::Value::Null).await?)?;
+            print_pairs(&pairs, json)?;
+        }
+        Commands::Pairs(PairCommands::Pause { pair_id }) => {
+            client.call("pause", json!({ "pair_id": pair_id })).await?;
+            if !json {
+                println!("Paused sync pair {}", pair_id);
+            }
+        }
+        Commands::Pairs(PairCommands::Resume { pair_id }) => {
+            client.call("resume", json!({ "pair_id": pair_id })).await?;
+            if !json {
+                println!("Resumed sync pair {}", pair_id);
+            }
+        }
+        Commands::Daemon | Commands::Login { .. } | Commands::Accounts(_) => unreachable!("checked above"),
+    }
+    Ok(true)
+}
+
+/// Call a method that syncs `pair_id`, printing the pair's events until it returns
+#[cfg(unix)]
+async fn call_printing_events(client: &mut omnisync_core::control::ControlClient, method: &str, pair_id: i64, json: bool) -> Result<()> {
+    let on_status = status_printer(json);
+    client.subscribe().await?;
+    client.call_with_events(method, json!({ "pair_id": pair_id }), |status| {
+        if status.pair_id() == Some(pair_id) {
+            on_status(status);
+        }
+    }).await?;
+    Ok(())
+}
+
+/// The control socket needs Unix domain sockets; elsewhere every command uses the database
+#[cfg(not(unix))]
+async fn via_daemon(_socket: &Path, _command: &Commands, _json: bool) -> Result<bool> {
+    Ok(false)
+}
+
+fn days_ago(timestamp: i64) -> i64 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    (now - timestamp).max(0) / 86400
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
-    
+    let json = args.json;
+    let socket = args.socket.clone().unwrap_or_else(|| PathBuf::from(format!("{}.sock", args.db_path)));
+
+    // A running daemon owns the pairs; ask it instead of the database where it can answer
+    if via_daemon(&socket, &args.command, json).await? {
+        return Ok(());
+    }
+
     let connection_options = SqliteConnectOptions::from_str(&format!("sqlite://{}", args.db_path))?
         .create_if_missing(true);
 
@@ -52,45 +438,216 @@ async fn main() -> Result<()> {
         .run(&pool)
         .await?;
 
-    let mut engine = SyncEngine::new(pool.clone());
-
-    // Load credentials and initialize providers
-    // In a real app, we'd check which providers are configured
-    // For MVP, if we have a gdrive token in DB, we init the provider
-    let gdrive_token: Option<String> = sqlx::query_scalar(
-        "SELECT access_token FROM credentials WHERE provider_id = 'gdrive'"
-    )
-    .fetch_optional(&pool)
-    .await?;
-
-    if let Some(token) = gdrive_token {
-        println!("Initializing Google Drive provider...");
-        let provider = GoogleDriveProvider::new(token);
-        engine.register_provider(Arc::new(provider));
+    let engine = SyncEngine::new(pool.clone());
+    // Tokens go to the OS keyring; servers without one keep them in the database
+    match KeyringCredentialStore::open(KEYRING_SERVICE) {
+        Ok(store) => {
+            engine.use_credential_store(Arc::new(store)).await?;
+        }
+        Err(e) if matches!(args.command, Commands::Daemon | Commands::Login { .. } | Commands::Accounts(AccountCommands::Add { .. })) => {
+            eprintln!("OS keyring unavailable, keeping tokens in {}: {}", args.db_path, e);
+        }
+        Err(_) => {}
     }
 
     match args.command {
         Commands::Daemon => {
-            println!("Starting OmniSync Daemon...");
-            // TODO: In real app, we should only start if there are providers
-            engine.start().await?;
-        }
-        Commands::Login { provider, token } => {
-            if provider != "gdrive" {
-                return Err(anyhow::anyhow!("Only 'gdrive' is supported for now"));
-            }
-            
-            let mut query = sqlx::query("INSERT INTO credentials (provider_id, access_token) VALUES (?, ?) ON CONFLICT(provider_id) DO UPDATE SET access_token = excluded.access_token");
-            query = query.bind(&provider).bind(token);
-            query.execute(&pool).await?;
-            
-            println!("Successfully logged in to {}", provider);
-        }
-        Commands::Add { local, remote, provider } => {
-            let id = engine.add_sync_pair(&local, &remote, &provider).await?;
+            if !json {
+                println!("Starting OmniSync Daemon...");
+            }
+            let on_status = status_printer(json);
+            #[cfg(unix)]
+            let on_status = {
+                let server = omnisync_core::control::ControlServer::bind(engine.clone(), &socket).await?;
+                let sink = server.sink();
+                tokio::spawn(async move {
+                    if let Err(e) = server.run().await {
+                        eprintln!("Control socket stopped: {}", e);
+                    }
+                });
+                if !json {
+                    println!("Listening for commands on {}", socket.display());
+                }
+                Arc::new(move |status: SyncStatus| {
+                    sink.send(status.clone());
+                    on_status(status);
+                })
+            };
+            engine.start(move |status| on_status(status)).await?;
+        }
+        Commands::Status => anyhow::bail!("No daemon is running on {}", socket.display()),
+        Commands::Pairs(command) => pairs(&engine, command, json).await?,
+        Commands::Accounts(command) => accounts(&engine, command, json).await?,
+        Commands::Login { provider } => {
+            let (code_verifier, code_challenge) = SyncEngine::generate_pkce();
+            let url = engine.oauth_authorize_url(&provider, &code_challenge)?;
+            // Prompts go to stderr so --json output stays parseable
+            eprintln!("Open this link in a browser, on this machine or any other, and allow access:\n\n  {}\n", url);
+            eprintln!("The browser then fails to load a page on 127.0.0.1. Paste the address it shows here:");
+            let mut redirect = String::new();
+            std::io::stdin().read_line(&mut redirect)?;
+            let account_id = engine.complete_oauth(&provider, &redirect, &code_verifier).await?;
+            if json {
+                print_json(&json!({ "account_id": account_id }))?;
+            } else {
+                println!("Connected {}", account_id);
+            }
+        }
+        Commands::Sync { pair_id } => {
+            let pair = engine.get_sync_pair(pair_id).await?
+                .ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+            engine.perform_initial_sync(&pair, status_printer(json)).await?;
+            if !json {
+                println!("Sync pair {} is done syncing", pair_id);
+            }
+        }
+        Commands::Plan { pair_id } => {
+            print_plan(&engine.plan_sync(pair_id).await?, json)?;
+        }
+        Commands::Deletions { pair_id, confirm, restore } => {
+            if confirm {
+                engine.confirm_deletions(pair_id, status_printer(json)).await?;
+            } else if restore {
+                engine.restore_deletions(pair_id, status_printer(json)).await?;
+            } else {
+                print_pending_deletions(&engine.pending_deletions(pair_id).await?, json)?;
+            }
+        }
+        Commands::Trash { pair_id, remote, restore, retention, permanent_delete } => {
+            if let Some(days) = retention {
+                engine.set_trash_retention(pair_id, days).await?;
+                if !json {
+                    println!("Trash of sync pair {} is kept for {} day(s) (0: forever)", pair_id, days.max(0));
+                }
+            }
+            if let Some(permanent) = permanent_delete {
+                engine.set_permanent_delete(pair_id, permanent).await?;
+                if !json {
+                    println!("Sync pair {} {} on the cloud", pair_id, if permanent { "deletes for good" } else { "moves deletions to the trash" });
+                }
+            }
+            match (restore, remote) {
+                (Some(id), false) => {
+                    engine.restore_trash_item(id).await?;
+                    if !json {
+                        println!("Restored trash item {}", id);
+                    }
+                }
+                (Some(id), true) => {
+                    engine.restore_remote_trash_item(id).await?;
+                    if !json {
+                        println!("Restored trash item {} on the cloud", id);
+                    }
+                }
+                (None, _) if retention.is_some() || permanent_delete.is_some() => {}
+                (None, false) => print_trash(&engine.list_trash(Some(pair_id)).await?, json)?,
+                (None, true) => print_remote_trash(&engine.list_remote_trash(pair_id).await?, json)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn pairs(engine: &SyncEngine, command: PairCommands, json: bool) -> Result<()> {
+    match command {
+        PairCommands::List => print_pairs(&engine.get_sync_pairs().await?, json)?,
+        PairCommands::Add { local, remote, remote_name, account, mode, paused } => {
+            if engine.get_credentials(&account).await?.is_none() {
+                anyhow::bail!("No account {}; see `accounts list`", account);
+            }
+            let remote_name = remote_name.unwrap_or_else(|| remote.clone());
+            let id = engine.add_sync_pair(&local, &remote, &remote_name, provider_type(&account), &account).await?;
+            engine.set_sync_mode(id, mode).await?;
+            if paused {
+                engine.set_pair_active(id, false).await?;
+            }
+            if json {
+                return print_json(&engine.get_sync_pair(id).await?);
+            }
             println!("Added sync pair with ID: {}", id);
         }
+        PairCommands::Remove { pair_id } => {
+            if engine.get_sync_pair(pair_id).await?.is_none() {
+                anyhow::bail!("No sync pair with id {}", pair_id);
+            }
+            engine.remove_sync_pair(pair_id).await?;
+            if !json {
+                println!("Removed sync pair {}", pair_id);
+            }
+        }
+        PairCommands::Pause { pair_id } => {
+            engine.set_pair_active(pair_id, false).await?;
+            if !json {
+                println!("Paused sync pair {}", pair_id);
+            }
+        }
+        PairCommands::Resume { pair_id } => {
+            engine.set_pair_active(pair_id, true).await?;
+            if !json {
+                println!("Resumed sync pair {}", pair_id);
+            }
+        }
+        PairCommands::Mode { pair_id, mode } => {
+            engine.set_sync_mode(pair_id, mode).await?;
+            if !json {
+                println!("Sync pair {} is now {}", pair_id, mode.as_str());
+            }
+        }
     }
+    Ok(())
+}
 
+async fn accounts(engine: &SyncEngine, command: AccountCommands, json: bool) -> Result<()> {
+    match command {
+        AccountCommands::List => {
+            let accounts = engine.get_all_accounts().await?;
+            if json {
+                // Never print tokens or secrets
+                let listed: Vec<_> = accounts.iter()
+                    .map(|a| json!({
+                        "account_id": a.account_id,
+                        "provider_id": a.provider_id,
+                        "user_name": a.user_name,
+                        "user_email": a.user_email,
+                    }))
+                    .collect();
+                return print_json(&listed);
+            }
+            if accounts.is_empty() {
+                println!("No accounts; connect one with `accounts add`");
+            }
+            for account in &accounts {
+                println!(
+                    "{}\t{}\t{}",
+                    account.account_id,
+                    account.user_name.as_deref().unwrap_or_default(),
+                    account.user_email.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        AccountCommands::Add { provider, config, secret } => {
+            let config: serde_json::Value = serde_json::from_str(&config)
+                .map_err(|e| anyhow::anyhow!("--config is not valid JSON: {}", e))?;
+            let account_id = engine.connect_account(&provider, config, &secret).await?;
+            if json {
+                return print_json(&json!({ "account_id": account_id }));
+            }
+            println!("Connected {}", account_id);
+        }
+        AccountCommands::Remove { account_id } => {
+            if engine.get_credentials(&account_id).await?.is_none() {
+                anyhow::bail!("No account {}", account_id);
+            }
+            engine.sign_out(&account_id).await?;
+            let left = engine.get_sync_pairs().await?.into_iter().filter(|p| p.account_id == account_id).count();
+            if !json {
+                println!("Disconnected {}", account_id);
+                if left > 0 {
+                    println!("{} sync pair(s) still use it; connect it again or remove them with `pairs remove`", left);
+                }
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,24 @@
+-- Rebuild the files table as a per-pair sync state index.
+-- The original table was keyed by absolute path and never written to, so it is
+-- safe to drop. Each row now records the last-synced (base) version of an entry,
+-- keyed by pair and path relative to the pair root.
+DROP INDEX IF EXISTS idx_files_path;
+DROP TABLE IF EXISTS files;
+
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair_id INTEGER NOT NULL,
+    path TEXT NOT NULL,                  -- relative to the pair root, '/' separated
+    is_dir INTEGER NOT NULL DEFAULT 0,
+    hash TEXT,                           -- local content hash at last sync
+    size INTEGER,
+    modified_at INTEGER,                 -- local mtime at last sync
+    remote_id TEXT,
+    remote_hash TEXT,
+    remote_modified_at INTEGER,
+    status TEXT NOT NULL DEFAULT 'synced', -- pending, synced, conflict
+    synced_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    UNIQUE (pair_id, path)
+);
+
+CREATE INDEX idx_files_pair ON files(pair_id);
@@ -0,0 +1,13 @@
+-- Conflicts detected when both sides changed a file since the last sync.
+-- The local version is kept as a "conflicted copy" next to the original.
+CREATE TABLE IF NOT EXISTS conflicts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair_id INTEGER NOT NULL,
+    path TEXT NOT NULL,                  -- original file, relative to the pair root
+    conflict_path TEXT NOT NULL,         -- conflicted copy, relative to the pair root
+    detected_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    resolved_at INTEGER,
+    resolution TEXT                      -- keep_local, keep_remote, keep_both
+);
+
+CREATE INDEX idx_conflicts_pair ON conflicts(pair_id);
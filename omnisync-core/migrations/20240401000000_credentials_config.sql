@@ -0,0 +1,4 @@
+-- Connection settings for providers that are not configured through OAuth
+-- (e.g. S3 endpoint, region and bucket), stored as a JSON object.
+-- Secrets stay in access_token.
+ALTER TABLE credentials ADD COLUMN config TEXT;
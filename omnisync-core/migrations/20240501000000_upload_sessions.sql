@@ -0,0 +1,11 @@
+-- Resumable upload sessions, so an interrupted upload continues after a restart.
+-- A session is only reused while the local file keeps the recorded size and mtime.
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    local_path TEXT NOT NULL,
+    remote_parent TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    modified_at INTEGER NOT NULL,
+    session_uri TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    PRIMARY KEY (local_path, remote_parent)
+);
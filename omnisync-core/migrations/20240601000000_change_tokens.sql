@@ -0,0 +1,7 @@
+-- Change feed position per account (Drive startPageToken), so a poll only
+-- fetches what changed since the last one.
+CREATE TABLE IF NOT EXISTS change_tokens (
+    account_id TEXT PRIMARY KEY,
+    cursor TEXT NOT NULL,
+    updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
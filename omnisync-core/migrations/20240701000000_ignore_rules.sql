@@ -0,0 +1,10 @@
+-- Per-pair ignore rules in gitignore syntax, one pattern per line.
+-- The default keeps what used to be hard-coded (hidden entries, node_modules,
+-- target) minus dist, and adds temp files and Office lock files.
+ALTER TABLE sync_pairs ADD COLUMN ignore_rules TEXT NOT NULL DEFAULT '.*
+!.omnisyncignore
+node_modules/
+target/
+*.tmp
+~$*
+';
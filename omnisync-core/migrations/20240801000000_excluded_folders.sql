@@ -0,0 +1,3 @@
+-- Selective sync: remote folders of a pair that are not mirrored locally,
+-- as keys relative to the pair root, one per line.
+ALTER TABLE sync_pairs ADD COLUMN excluded_folders TEXT NOT NULL DEFAULT '';
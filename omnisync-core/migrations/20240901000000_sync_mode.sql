@@ -0,0 +1,2 @@
+-- Sync direction per pair: two_way, upload_only, download_only, mirror
+ALTER TABLE sync_pairs ADD COLUMN mode TEXT NOT NULL DEFAULT 'two_way';
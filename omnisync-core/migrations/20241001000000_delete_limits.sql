@@ -0,0 +1,5 @@
+-- Mass-deletion safeguard: a pass that would delete more than delete_limit files
+-- remotely, or more than delete_limit_percent of the pair's files, pauses the pair
+-- until the deletions are confirmed. 0 turns a limit off.
+ALTER TABLE sync_pairs ADD COLUMN delete_limit INTEGER NOT NULL DEFAULT 50;
+ALTER TABLE sync_pairs ADD COLUMN delete_limit_percent INTEGER NOT NULL DEFAULT 20;
@@ -0,0 +1,16 @@
+-- Local trash: files the engine deletes or overwrites locally are moved to
+-- <pair root>/.omnisync/trash/<id>/<name> instead of being lost.
+CREATE TABLE IF NOT EXISTS trash_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair_id INTEGER NOT NULL,
+    path TEXT NOT NULL,                  -- original location, relative to the pair root
+    is_dir BOOLEAN NOT NULL DEFAULT 0,
+    size INTEGER,
+    reason TEXT NOT NULL,                -- deleted, overwritten, replaced
+    trashed_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX idx_trash_items_pair ON trash_items(pair_id);
+
+-- Days trashed items are kept before they are purged. 0 keeps them forever.
+ALTER TABLE sync_pairs ADD COLUMN trash_retention_days INTEGER NOT NULL DEFAULT 30;
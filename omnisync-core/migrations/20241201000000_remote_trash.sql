@@ -0,0 +1,15 @@
+-- Remote deletions go to the provider's trash where it has one. Pairs can opt into
+-- deleting for good instead.
+ALTER TABLE sync_pairs ADD COLUMN permanent_delete BOOLEAN NOT NULL DEFAULT 0;
+
+-- Entries this engine moved to a provider's trash, so they can be listed per pair
+CREATE TABLE IF NOT EXISTS remote_trash_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair_id INTEGER NOT NULL,
+    path TEXT NOT NULL,                  -- original location, relative to the pair root
+    trash_id TEXT NOT NULL,              -- provider id to restore it by
+    is_dir BOOLEAN NOT NULL DEFAULT 0,
+    trashed_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX idx_remote_trash_items_pair ON remote_trash_items(pair_id);
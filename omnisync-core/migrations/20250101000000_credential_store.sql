@@ -0,0 +1,3 @@
+-- Tokens of accounts whose row has this set are kept in the credential store (the OS
+-- keyring), and access_token/refresh_token are left empty here
+ALTER TABLE credentials ADD COLUMN secret_in_store BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Version token of the remote copy at the last sync (a WebDAV ETag), compared when the
+-- provider reports no content hash
+ALTER TABLE files ADD COLUMN remote_revision TEXT;
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Tokens from a code exchange or a refresh
+#[derive(Debug, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    /// Set when one is issued or rotated; otherwise the previous one stays valid
+    pub refresh_token: Option<String>,
+    /// Seconds the access token is valid for
+    pub expires_in: Option<i64>,
+}
+
+/// The signed-in user an account is shown as
+#[derive(Debug, Clone, Default)]
+pub struct UserInfo {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// OAuth sign-in and token upkeep of a provider type, registered next to its
+/// provider factory. Accounts of types with one get their tokens refreshed before
+/// they expire.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Where to send the user to grant access in the PKCE code flow
+    fn authorize_url(&self, redirect_uri: &str, code_challenge: &str) -> Result<String>;
+
+    /// Redeem the code the authorization redirect carried
+    async fn exchange_code(&self, code: &str, redirect_uri: &str, code_verifier: &str) -> Result<TokenResponse>;
+
+    async fn refresh(&self, refresh_token: &str) -> Result<TokenResponse>;
+
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo>;
+
+    /// Revoke the grant so the tokens stop working; providers that cannot do nothing
+    async fn revoke(&self, _access_token: &str, _refresh_token: Option<&str>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Errors when the app was built without a client id for the provider
+pub(crate) fn check_client_id(client_id: &str, provider: &str) -> Result<()> {
+    if client_id.is_empty() || client_id == "NOT_CONFIGURED" {
+        anyhow::bail!("{} OAuth client id not configured in .env file", provider);
+    }
+    Ok(())
+}
+
+/// POST a form to a token endpoint and read the tokens it answers with.
+/// `what` names the request in errors, e.g. "Token refresh".
+pub(crate) async fn request_tokens(url: &str, params: &[(&str, &str)], what: &str) -> Result<TokenResponse> {
+    let response = reqwest::Client::new().post(url)
+        .form(params)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let err = response.text().await?;
+        return Err(anyhow::anyhow!("{} failed: {}", what, err));
+    }
+
+    let tokens: serde_json::Value = response.json().await?;
+    let access_token = tokens["access_token"].as_str()
+        .ok_or_else(|| anyhow::anyhow!("No access token returned"))?.to_string();
+    Ok(TokenResponse {
+        access_token,
+        refresh_token: tokens["refresh_token"].as_str().map(|s| s.to_string()),
+        expires_in: tokens["expires_in"].as_i64(),
+    })
+}
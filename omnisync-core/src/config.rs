@@ -22,3 +22,25 @@ pub fn get_google_client_secret() -> String {
     std::env::var("GOOGLE_CLIENT_SECRET")
         .unwrap_or_else(|_| "NOT_CONFIGURED".to_string())
 }
+
+pub fn get_onedrive_client_id() -> String {
+    // OneDrive uses a public client with PKCE, so only the client ID is needed
+    if let Some(id) = option_env!("ONEDRIVE_CLIENT_ID") {
+        return id.to_string();
+    }
+
+    dotenvy::dotenv().ok();
+    std::env::var("ONEDRIVE_CLIENT_ID")
+        .unwrap_or_else(|_| "NOT_CONFIGURED".to_string())
+}
+
+pub fn get_dropbox_client_id() -> String {
+    // Dropbox app key; the app uses PKCE, so no secret is shipped
+    if let Some(id) = option_env!("DROPBOX_CLIENT_ID") {
+        return id.to_string();
+    }
+
+    dotenvy::dotenv().ok();
+    std::env::var("DROPBOX_CLIENT_ID")
+        .unwrap_or_else(|_| "NOT_CONFIGURED".to_string())
+}
@@ -0,0 +1,427 @@
+//! Control socket of a running daemon: JSON-RPC 2.0 over a Unix domain socket, one
+//! message per line.
+//!
+//! Methods, most taking `{"pair_id": <id>}`:
+//! - `list_pairs`, `status`, `add_pair` (`local`, `remote`, `account` and optionally
+//!   `remote_name`, `mode`, `paused`; returns the new pair) and `remove_pair`
+//! - `sync`, `pause`, `resume`, `set_mode` (`mode`) and `plan`
+//! - `deletions`, `confirm_deletions` and `restore_deletions`
+//! - `trash` (`remote` for the cloud trash), `restore_trash` (`item_id`, `remote`),
+//!   `set_trash_retention` (`days`) and `set_permanent_delete` (`permanent`)
+//! - `subscribe`, after which every `SyncStatus` the daemon reports arrives as a
+//!   `status` notification, always ahead of the reply to the call that caused it
+//!
+//! Everything that changes pairs goes through here while a daemon runs, so its watcher
+//! and running passes follow along.
+
+use crate::engine::{SyncEngine, SyncStatus};
+use crate::models::{SyncMode, SyncPair};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Any error the engine returned
+const ENGINE_ERROR: i64 = -32000;
+
+/// Events a subscriber may fall behind by before it misses some
+const EVENT_BUFFER: usize = 1024;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        RpcError { code: ENGINE_ERROR, message: e.to_string() }
+    }
+}
+
+struct Shared {
+    engine: SyncEngine,
+    events: broadcast::Sender<SyncStatus>,
+    /// Latest event of each pair, for `status`
+    activity: std::sync::Mutex<HashMap<i64, SyncStatus>>,
+}
+
+/// Feeds engine events to subscribers and to `status`
+#[derive(Clone)]
+pub struct StatusSink(Arc<Shared>);
+
+impl StatusSink {
+    pub fn send(&self, status: SyncStatus) {
+        if let Some(pair_id) = status.pair_id() {
+            self.0.activity.lock().unwrap().insert(pair_id, status.clone());
+        }
+        // Nobody subscribed is fine
+        let _ = self.0.events.send(status);
+    }
+}
+
+pub struct ControlServer {
+    listener: UnixListener,
+    shared: Arc<Shared>,
+}
+
+impl ControlServer {
+    /// Listen on `path`. A socket left behind by a daemon that is gone is replaced; one
+    /// that still answers means another daemon is running.
+    pub async fn bind(engine: SyncEngine, path: &Path) -> Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(path).await.is_ok() {
+                anyhow::bail!("A daemon is already running on {}", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let shared = Arc::new(Shared { engine, events, activity: Default::default() });
+        Ok(Self { listener, shared })
+    }
+
+    /// Where the daemon's status events go; pass everything the engine reports
+    pub fn sink(&self) -> StatusSink {
+        StatusSink(self.shared.clone())
+    }
+
+    /// Answer clients until the listener fails
+    pub async fn run(self) -> Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            tokio::spawn(handle_connection(self.shared.clone(), stream));
+        }
+    }
+}
+
+async fn handle_connection(shared: Arc<Shared>, stream: UnixStream) {
+    let (read, mut write) = stream.into_split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let line = format!("{}\n", message);
+            if write.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Replies go straight to the writer until the connection subscribes, then through
+    // the event forwarder so they queue up behind the events sent before them
+    let mut replies = tx.clone();
+    let mut subscription = None;
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = replies.send(response(Value::Null, Err(RpcError { code: PARSE_ERROR, message: e.to_string() })));
+                continue;
+            }
+        };
+        // Requests without an id are notifications and get no answer
+        let id = request.get("id").cloned();
+        let Some(method) = request["method"].as_str().map(|m| m.to_string()) else {
+            let _ = replies.send(response(id.unwrap_or_default(), Err(RpcError { code: INVALID_REQUEST, message: "Missing method".to_string() })));
+            continue;
+        };
+
+        if method == "subscribe" {
+            if subscription.is_none() {
+                let (reply_tx, reply_rx) = mpsc::unbounded_channel();
+                subscription = Some(tokio::spawn(forward_events(shared.events.subscribe(), reply_rx, tx.clone())));
+                replies = reply_tx;
+            }
+            if let Some(id) = id {
+                let _ = replies.send(response(id, Ok(Value::Null)));
+            }
+            continue;
+        }
+
+        // Each call runs on its own, so a long sync does not hold up the connection
+        let (shared, replies) = (shared.clone(), replies.clone());
+        tokio::spawn(async move {
+            let result = dispatch(&shared, &method, &request["params"]).await;
+            if let Some(id) = id {
+                let _ = replies.send(response(id, result));
+            }
+        });
+    }
+
+    if let Some(subscription) = subscription {
+        subscription.abort();
+    }
+}
+
+/// Pass events and replies of a subscribed connection to its writer. Events broadcast
+/// before a reply was ready are written first, so a client never sees the reply to a
+/// `sync` ahead of the events of that sync.
+async fn forward_events(
+    mut events: broadcast::Receiver<SyncStatus>,
+    mut replies: mpsc::UnboundedReceiver<Value>,
+    tx: mpsc::UnboundedSender<Value>,
+) {
+    let notification = |status: SyncStatus| json!({ "jsonrpc": "2.0", "method": "status", "params": status });
+    loop {
+        tokio::select! {
+            reply = replies.recv() => {
+                let Some(reply) = reply else { return };
+                loop {
+                    match events.try_recv() {
+                        Ok(status) => {
+                            if tx.send(notification(status)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    }
+                }
+                if tx.send(reply).is_err() {
+                    return;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(status) => {
+                    if tx.send(notification(status)).is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                // No more events; keep passing replies on
+                Err(broadcast::error::RecvError::Closed) => {
+                    while let Some(reply) = replies.recv().await {
+                        if tx.send(reply).is_err() {
+                            return;
+                        }
+                    }
+                    return;
+                }
+            },
+        }
+    }
+}
+
+async fn dispatch(shared: &Arc<Shared>, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let engine = &shared.engine;
+    match method {
+        "list_pairs" => Ok(json!(engine.get_sync_pairs().await?)),
+        "status" => {
+            let activity = shared.activity.lock().unwrap().clone();
+            let pairs: Vec<Value> = engine.get_sync_pairs().await?.into_iter()
+                .map(|p| json!({
+                    "pair_id": p.id,
+                    "status": p.status,
+                    "last_sync_at": p.last_sync_at,
+                    "activity": activity.get(&p.id),
+                }))
+                .collect();
+            Ok(json!({ "pid": std::process::id(), "pairs": pairs }))
+        }
+        "add_pair" => {
+            let (local, remote, account) = (string(params, "local")?, string(params, "remote")?, string(params, "account")?);
+            let remote_name = params["remote_name"].as_str().unwrap_or(remote);
+            let mode = match params["mode"].as_str() {
+                Some(mode) => SyncMode::from_str(mode).map_err(|e| RpcError { code: INVALID_PARAMS, message: e.to_string() })?,
+                None => SyncMode::TwoWay,
+            };
+            if engine.get_credentials(account).await?.is_none() {
+                return Err(anyhow::anyhow!("No account {}; see `accounts list`", account).into());
+            }
+            let id = engine.add_sync_pair(local, remote, remote_name, crate::registry::provider_type(account), account).await?;
+            engine.set_sync_mode(id, mode).await?;
+            if params["paused"].as_bool().unwrap_or(false) {
+                engine.set_pair_active(id, false).await?;
+            }
+            Ok(json!(engine.get_sync_pair(id).await?))
+        }
+        "remove_pair" => {
+            let pair_id = pair_id(params)?;
+            existing_pair(engine, pair_id).await?;
+            engine.remove_sync_pair(pair_id).await?;
+            Ok(Value::Null)
+        }
+        "sync" => {
+            let pair = existing_pair(engine, pair_id(params)?).await?;
+            engine.perform_initial_sync(&pair, sink(shared)).await?;
+            Ok(Value::Null)
+        }
+        "pause" | "resume" => {
+            engine.set_pair_active(pair_id(params)?, method == "resume").await?;
+            Ok(Value::Null)
+        }
+        "set_mode" => {
+            let mode = SyncMode::from_str(string(params, "mode")?)
+                .map_err(|e| RpcError { code: INVALID_PARAMS, message: e.to_string() })?;
+            engine.set_sync_mode(pair_id(params)?, mode).await?;
+            Ok(Value::Null)
+        }
+        "plan" => Ok(json!(engine.plan_sync(pair_id(params)?).await?)),
+        "deletions" => Ok(json!(engine.pending_deletions(pair_id(params)?).await?)),
+        "confirm_deletions" => {
+            engine.confirm_deletions(pair_id(params)?, sink(shared)).await?;
+            Ok(Value::Null)
+        }
+        "restore_deletions" => {
+            engine.restore_deletions(pair_id(params)?, sink(shared)).await?;
+            Ok(Value::Null)
+        }
+        "trash" => {
+            let pair_id = pair_id(params)?;
+            if params["remote"].as_bool().unwrap_or(false) {
+                Ok(json!(engine.list_remote_trash(pair_id).await?))
+            } else {
+                Ok(json!(engine.list_trash(Some(pair_id)).await?))
+            }
+        }
+        "restore_trash" => {
+            let item_id = params["item_id"].as_i64().ok_or_else(|| RpcError {
+                code: INVALID_PARAMS,
+                message: "Expected {\"item_id\": <id>}".to_string(),
+            })?;
+            if params["remote"].as_bool().unwrap_or(false) {
+                engine.restore_remote_trash_item(item_id).await?;
+            } else {
+                engine.restore_trash_item(item_id).await?;
+            }
+            Ok(Value::Null)
+        }
+        "set_trash_retention" => {
+            let days = params["days"].as_i64().ok_or_else(|| RpcError {
+                code: INVALID_PARAMS,
+                message: "Expected {\"pair_id\": <id>, \"days\": <days>}".to_string(),
+            })?;
+            engine.set_trash_retention(pair_id(params)?, days).await?;
+            Ok(Value::Null)
+        }
+        "set_permanent_delete" => {
+            let permanent = params["permanent"].as_bool().ok_or_else(|| RpcError {
+                code: INVALID_PARAMS,
+                message: "Expected {\"pair_id\": <id>, \"permanent\": <bool>}".to_string(),
+            })?;
+            engine.set_permanent_delete(pair_id(params)?, permanent).await?;
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method {}", method) }),
+    }
+}
+
+fn pair_id(params: &Value) -> Result<i64, RpcError> {
+    params["pair_id"].as_i64().ok_or_else(|| RpcError {
+        code: INVALID_PARAMS,
+        message: "Expected {\"pair_id\": <id>}".to_string(),
+    })
+}
+
+fn string<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params[name].as_str().ok_or_else(|| RpcError {
+        code: INVALID_PARAMS,
+        message: format!("Missing \"{}\"", name),
+    })
+}
+
+async fn existing_pair(engine: &SyncEngine, pair_id: i64) -> Result<SyncPair, RpcError> {
+    Ok(engine.get_sync_pair(pair_id).await?
+        .ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?)
+}
+
+/// Status callback publishing to the daemon's subscribers
+fn sink(shared: &Arc<Shared>) -> Arc<impl Fn(SyncStatus) + Send + Sync + 'static> {
+    let sink = StatusSink(shared.clone());
+    Arc::new(move |status| sink.send(status))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": e.code, "message": e.message } }),
+    }
+}
+
+/// Connection to a running daemon's control socket
+pub struct ControlClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    write: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl ControlClient {
+    pub async fn connect(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path).await
+            .with_context(|| format!("No daemon is running on {}", path.display()))?;
+        let (read, write) = stream.into_split();
+        Ok(Self { lines: BufReader::new(read).lines(), write, next_id: 0 })
+    }
+
+    /// Call a method and wait for its result
+    pub async fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.call_with_events(method, params, |_| {}).await
+    }
+
+    /// Call a method, handing the events that arrive in the meantime to `on_event`.
+    /// Only subscribed connections get events.
+    pub async fn call_with_events(&mut self, method: &str, params: Value, mut on_event: impl FnMut(SyncStatus)) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let line = format!("{}\n", json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        self.write.write_all(line.as_bytes()).await?;
+
+        loop {
+            let message = self.read().await?
+                .ok_or_else(|| anyhow::anyhow!("The daemon closed the connection"))?;
+            if message["id"].as_u64() == Some(id) {
+                if let Some(error) = message.get("error") {
+                    anyhow::bail!("{}", error["message"].as_str().unwrap_or("Daemon error"));
+                }
+                return Ok(message["result"].clone());
+            }
+            if let Some(status) = as_event(&message) {
+                on_event(status);
+            }
+        }
+    }
+
+    /// Receive `SyncStatus` events from now on, through `next_event` or `call_with_events`
+    pub async fn subscribe(&mut self) -> Result<()> {
+        self.call("subscribe", Value::Null).await.map(|_| ())
+    }
+
+    /// The next event after `subscribe`; None once the daemon is gone
+    pub async fn next_event(&mut self) -> Result<Option<SyncStatus>> {
+        while let Some(message) = self.read().await? {
+            if let Some(status) = as_event(&message) {
+                return Ok(Some(status));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn read(&mut self) -> Result<Option<Value>> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn as_event(message: &Value) -> Option<SyncStatus> {
+    if message["method"] != "status" {
+        return None;
+    }
+    serde_json::from_value(message["params"].clone()).ok()
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Service name the apps keep their keyring entries under
+pub const KEYRING_SERVICE: &str = "omnisync";
+
+/// Keyring entry looked up to tell whether the platform keyring works
+const PROBE_USER: &str = "omnisync-probe";
+
+/// Where the tokens and secrets of accounts are kept instead of the database,
+/// one opaque string per account_id
+pub trait CredentialStore: Send + Sync {
+    fn get(&self, account_id: &str) -> Result<Option<String>>;
+    fn set(&self, account_id: &str, secret: &str) -> Result<()>;
+    /// Deleting an account that has no entry is not an error
+    fn delete(&self, account_id: &str) -> Result<()>;
+}
+
+/// The OS keyring: Secret Service on Linux, the Keychain on macOS and Credential
+/// Manager on Windows, with one entry per account under `service`
+pub struct KeyringCredentialStore {
+    service: String,
+}
+
+impl KeyringCredentialStore {
+    /// Errors when the platform has no usable keyring, e.g. a server without a Secret Service
+    pub fn open(service: &str) -> Result<Self> {
+        match keyring::Entry::new(service, PROBE_USER)?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(Self { service: service.to_string() }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn entry(&self, account_id: &str) -> Result<keyring::Entry> {
+        Ok(keyring::Entry::new(&self.service, account_id)?)
+    }
+}
+
+impl CredentialStore for KeyringCredentialStore {
+    fn get(&self, account_id: &str) -> Result<Option<String>> {
+        match self.entry(account_id)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, account_id: &str, secret: &str) -> Result<()> {
+        Ok(self.entry(account_id)?.set_password(secret)?)
+    }
+
+    fn delete(&self, account_id: &str) -> Result<()> {
+        match self.entry(account_id)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Kept in memory only, e.g. for tests
+#[derive(Default)]
+pub struct MemoryCredentialStore {
+    secrets: std::sync::Mutex<HashMap<String, String>>,
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn get(&self, account_id: &str) -> Result<Option<String>> {
+        Ok(self.secrets.lock().unwrap().get(account_id).cloned())
+    }
+
+    fn set(&self, account_id: &str, secret: &str) -> Result<()> {
+        self.secrets.lock().unwrap().insert(account_id.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, account_id: &str) -> Result<()> {
+        self.secrets.lock().unwrap().remove(account_id);
+        Ok(())
+    }
+}
+
+/// What the engine keeps in a store entry
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredSecrets {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+}
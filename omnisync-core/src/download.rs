@@ -0,0 +1,111 @@
+use crate::hashing::{hash_file, ContentHasher};
+use crate::provider::{CloudError, CloudResult, HashKind, RemoteFile};
+use anyhow::anyhow;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Where the engine stages downloads before they replace a local file, relative to the
+/// pair root (ignored by every matcher)
+pub const STAGING_DIR: &str = ".omnisync/incoming";
+
+/// What a finished download has to match before it replaces the local file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Expected<'a> {
+    pub size: Option<u64>,
+    pub hash: Option<(HashKind, &'a str)>,
+}
+
+impl<'a> Expected<'a> {
+    /// The listed size and hash of `remote`, whose hash is in `kind`
+    pub fn listed(remote: &'a RemoteFile, kind: HashKind) -> Self {
+        Self { size: remote.size, hash: remote.hash.as_deref().map(|hash| (kind, hash)) }
+    }
+
+    /// Only the listed size, for providers whose hash is not always a content hash
+    pub fn size_of(remote: &RemoteFile) -> Self {
+        Self { size: remote.size, hash: None }
+    }
+}
+
+/// Temporary sibling a download is written to, ".<name>.omnisync-part" (skipped by every ignore matcher)
+pub fn part_path(local_path: &Path) -> CloudResult<PathBuf> {
+    let filename = local_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+    Ok(local_path.with_file_name(format!(".{}.omnisync-part", filename)))
+}
+
+/// Stream a response body into a temporary sibling of `local_path`, check it against
+/// `expected`, fsync it and only then rename it over the real path. A failed or
+/// interrupted download never leaves a truncated file where the sync would see it.
+pub async fn save_response(mut response: reqwest::Response, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+    let temp = part_path(local_path)?;
+
+    let written: CloudResult<()> = async {
+        let mut file = tokio::fs::File::create(&temp).await?;
+        let mut hasher = expected.hash.map(|(kind, _)| ContentHasher::new(kind));
+        let mut size = 0u64;
+        while let Some(chunk) = response.chunk().await? {
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&chunk);
+            }
+            size += chunk.len() as u64;
+            file.write_all(&chunk).await?;
+        }
+        file.sync_all().await?;
+        check(local_path, expected, size, hasher.map(ContentHasher::finalize))
+    }.await;
+
+    finish(&temp, local_path, written).await
+}
+
+/// Move a part file the provider filled itself (`part_path(local_path)`) into place,
+/// after the same checks and fsync as `save_response`
+pub async fn commit_part(local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+    let temp = part_path(local_path)?;
+
+    let checked: CloudResult<()> = async {
+        let file = tokio::fs::OpenOptions::new().write(true).open(&temp).await?;
+        file.sync_all().await?;
+        let size = file.metadata().await?.len();
+        let hash = match expected.hash {
+            Some((kind, _)) => Some(hash_file(&temp, kind).await?),
+            None => None,
+        };
+        check(local_path, expected, size, hash)
+    }.await;
+
+    finish(&temp, local_path, checked).await
+}
+
+fn check(local_path: &Path, expected: Expected<'_>, size: u64, hash: Option<String>) -> CloudResult<()> {
+    let name = local_path.file_name().unwrap_or_default().to_string_lossy();
+    if let Some(expected_size) = expected.size.filter(|s| *s != size) {
+        return Err(CloudError::ApiError(format!(
+            "Size mismatch for {}: expected {} bytes, got {}", name, expected_size, size
+        )));
+    }
+    if let (Some((kind, expected_hash)), Some(actual)) = (expected.hash, hash) {
+        // Hex digests may come in either case; base64 ones are case sensitive
+        let matches = match kind {
+            HashKind::QuickXor => expected_hash == actual,
+            _ => expected_hash.eq_ignore_ascii_case(&actual),
+        };
+        if !matches {
+            return Err(CloudError::ApiError(format!(
+                "Checksum mismatch for {}: expected {}, got {}", name, expected_hash, actual
+            )));
+        }
+    }
+    Ok(())
+}
+
+async fn finish(temp: &Path, local_path: &Path, result: CloudResult<()>) -> CloudResult<()> {
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(temp).await;
+        return Err(e);
+    }
+    tokio::fs::rename(temp, local_path).await?;
+    Ok(())
+}
@@ -1,13 +1,19 @@
-use crate::models::SyncPair;
-use crate::provider::{CloudProvider, CloudError};
+use crate::auth::{AuthProvider, TokenResponse};
+use crate::credential_store::{CredentialStore, StoredSecrets};
+use crate::ignore_rules::IgnoreMatcher;
+use crate::models::{Conflict, ConflictResolution, FileState, FolderCheckState, FolderNode, RemoteTrashItem, SyncMode, SyncPair, TrashItem, TrashReason};
+use crate::provider::{ChangeSet, CloudProvider, CloudError, RemoteFile, Transfer, UploadKey, UploadSessionStore};
+use crate::registry::{provider_type, ConnectedAccount, ConnectionForm, ProviderRegistry};
+use crate::reconcile::{apply_mode, conflicted_copy_name, decide_dir, decide_file, exceeds_delete_limit, plan_action, remote_changed, Decision, LocalState, SyncAction, SyncPlan};
 use crate::watcher::FilesystemWatcher;
 use anyhow::{Context, Result};
-use sqlx::SqlitePool;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row, SqlitePool};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
-use std::collections::HashMap;
+use tokio::net::{TcpListener, TcpStream};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::{Instant, Duration};
 use rand::{Rng, thread_rng};
@@ -20,52 +26,241 @@ use futures::stream::StreamExt;
 #[derive(Clone)]
 pub struct SyncEngine {
     pool: SqlitePool,
-    providers: Arc<Mutex<Vec<Arc<dyn CloudProvider>>>>,
+    registry: Arc<std::sync::RwLock<ProviderRegistry>>,
     watcher: Arc<Mutex<FilesystemWatcher>>,
     sync_cache: Arc<Mutex<HashMap<PathBuf, Instant>>>,
     cancel_tokens: Arc<Mutex<HashMap<i64, Arc<std::sync::atomic::AtomicBool>>>>,
+    /// Pairs whose next pass may go over their deletion limit
+    deletions_approved: Arc<Mutex<HashSet<i64>>>,
+    /// When the watcher deleted files remotely, per pair, within `DELETION_WINDOW`
+    recent_deletions: Arc<Mutex<HashMap<i64, Vec<Instant>>>>,
+    /// Where account tokens go instead of the database, once set
+    credential_store: Arc<std::sync::RwLock<Option<Arc<dyn CredentialStore>>>>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum SyncStatus {
     Idle { pair_id: i64 },
     Syncing { pair_id: i64, path: String },
     Downloading { pair_id: i64, path: String },
     Uploaded { pair_id: i64, path: String },
+    /// Bytes sent so far of a large upload
+    Progress { pair_id: i64, path: String, bytes: u64, total: u64 },
     Deleted { pair_id: i64, path: String },
+    /// Renamed or moved on the other side instead of re-transferred
+    Moved { pair_id: i64, from: String, path: String },
+    Conflict { pair_id: i64, path: String, conflict_path: String },
     Error { pair_id: i64, path: String, message: String },
     AuthExpired { account_id: String },
+    /// A pass would delete more remotely than the pair allows. The pair is paused
+    /// until the deletions are confirmed or the files restored.
+    DeletionsPending { pair_id: i64, count: usize },
 }
 
-/// Extract provider type from account_id (e.g., "gdrive:user@gmail.com" -> "gdrive")
-fn provider_type(account_id: &str) -> &str {
-    account_id.split(':').next().unwrap_or(account_id)
+impl SyncStatus {
+    /// The pair the event is about; None for account-wide events
+    pub fn pair_id(&self) -> Option<i64> {
+        match self {
+            SyncStatus::Idle { pair_id }
+            | SyncStatus::Syncing { pair_id, .. }
+            | SyncStatus::Downloading { pair_id, .. }
+            | SyncStatus::Uploaded { pair_id, .. }
+            | SyncStatus::Progress { pair_id, .. }
+            | SyncStatus::Deleted { pair_id, .. }
+            | SyncStatus::Moved { pair_id, .. }
+            | SyncStatus::Conflict { pair_id, .. }
+            | SyncStatus::Error { pair_id, .. }
+            | SyncStatus::DeletionsPending { pair_id, .. } => Some(*pair_id),
+            SyncStatus::AuthExpired { .. } => None,
+        }
+    }
+}
+
+/// Loopback redirect used by the browser OAuth flows
+const OAUTH_REDIRECT_URI: &str = "http://127.0.0.1:4420";
+
+const PAIR_COLUMNS: &str = "id, local_path, remote_path, remote_name, provider_id, account_id, status, created_at, last_sync_at, ignore_rules, excluded_folders, mode, delete_limit, delete_limit_percent, trash_retention_days, permanent_delete";
+
+const TRASH_COLUMNS: &str = "id, pair_id, path, is_dir, size, reason, trashed_at";
+
+const REMOTE_TRASH_COLUMNS: &str = "id, pair_id, path, trash_id, is_dir, trashed_at";
+
+const CREDENTIAL_COLUMNS: &str = "account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar, config";
+
+/// Remote deletions the watcher made within this long count together against a
+/// pair's deletion limits, so a folder emptied over several batches still trips them
+const DELETION_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Resumable sessions older than this are started over; Google Drive expires them after a week
+const UPLOAD_SESSION_MAX_AGE_SECS: i64 = 6 * 24 * 60 * 60;
+
+/// Upload sessions kept in the `upload_sessions` table
+struct SqliteUploadSessions {
+    pool: SqlitePool,
+}
+
+#[async_trait::async_trait]
+impl UploadSessionStore for SqliteUploadSessions {
+    async fn load(&self, key: &UploadKey) -> Option<String> {
+        sqlx::query_scalar::<_, String>(
+            "SELECT session_uri FROM upload_sessions
+             WHERE local_path = ? AND remote_parent = ? AND size = ? AND modified_at = ?
+               AND created_at > unixepoch() - ?"
+        )
+        .bind(&key.local_path)
+        .bind(&key.remote_parent)
+        .bind(key.size as i64)
+        .bind(key.modified_at)
+        .bind(UPLOAD_SESSION_MAX_AGE_SECS)
+        .fetch_optional(&self.pool)
+        .await
+        .unwrap_or(None)
+    }
+
+    async fn save(&self, key: &UploadKey, session_uri: &str) {
+        let result = sqlx::query(
+            "INSERT OR REPLACE INTO upload_sessions (local_path, remote_parent, size, modified_at, session_uri)
+             VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&key.local_path)
+        .bind(&key.remote_parent)
+        .bind(key.size as i64)
+        .bind(key.modified_at)
+        .bind(session_uri)
+        .execute(&self.pool)
+        .await;
+        if let Err(e) = result {
+            eprintln!("Failed to save upload session for {}: {:?}", key.local_path, e);
+        }
+    }
+
+    async fn clear(&self, key: &UploadKey) {
+        let _ = sqlx::query("DELETE FROM upload_sessions WHERE local_path = ? AND remote_parent = ?")
+            .bind(&key.local_path)
+            .bind(&key.remote_parent)
+            .execute(&self.pool)
+            .await;
+    }
+}
+
+/// State shared by every directory level of a single sync pass
+struct SyncRun<F> {
+    pair: SyncPair,
+    provider: Arc<dyn CloudProvider>,
+    /// Base index grouped by parent key, then by entry name
+    base: HashMap<String, HashMap<String, FileState>>,
+    on_status: Arc<F>,
+    cancel: Arc<std::sync::atomic::AtomicBool>,
+    /// Only reconcile the listed directory levels, not folders that are in sync below them
+    shallow: bool,
+    ignore: IgnoreMatcher,
+    mode: SyncMode,
+    /// Dry run: actions are collected here instead of being carried out
+    plan: Option<std::sync::Mutex<Vec<SyncAction>>>,
+}
+
+/// An entry of a directory level with the decision reached for it
+struct PlannedEntry {
+    name: String,
+    key: String,
+    path: PathBuf,
+    is_dir: bool,
+    /// Only filled in for files present locally
+    local: Option<LocalState>,
+    remote: Option<RemoteFile>,
+    base: Option<FileState>,
+    decision: Decision,
+}
+
+impl PlannedEntry {
+    fn action(&self) -> Option<SyncAction> {
+        plan_action(self.decision, self.is_dir, &self.key, self.local.as_ref().map(|l| l.size), self.remote.as_ref().and_then(|r| r.size))
+    }
 }
 
-/// Build a provider instance from credentials
-fn make_provider(account_id: &str, access_token: String) -> Option<Box<dyn CloudProvider>> {
-    match provider_type(account_id) {
-        "gdrive" => Some(Box::new(crate::providers::gdrive::GoogleDriveProvider::new(access_token))),
-        _ => None,
+impl<F> SyncRun<F> {
+    fn cancelled(&self) -> bool {
+        self.cancel.load(std::sync::atomic::Ordering::Relaxed)
     }
 }
 
+/// Index key of `path` relative to the pair root, '/' separated (e.g. "docs/a.txt")
+fn relative_key(pair: &SyncPair, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(&pair.local_path).ok()?;
+    let parts: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    Some(parts.join("/"))
+}
+
+fn join_key(dir_key: &str, name: &str) -> String {
+    if dir_key.is_empty() { name.to_string() } else { format!("{}/{}", dir_key, name) }
+}
+
+/// Whether `key` is `dir` itself or inside it
+fn key_within(key: &str, dir: &str) -> bool {
+    dir.is_empty() || key.strip_prefix(dir).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Split an index key into (parent key, name)
+fn split_key(key: &str) -> (&str, &str) {
+    key.rsplit_once('/').unwrap_or(("", key))
+}
+
+/// What a pair leaves out: its ignore rules and the folders excluded by selective sync
+fn pair_matcher(pair: &SyncPair) -> IgnoreMatcher {
+    IgnoreMatcher::new(Path::new(&pair.local_path), &pair.ignore_rules).excluding(pair.excluded_keys())
+}
+
+fn mtime_secs(meta: &std::fs::Metadata) -> i64 {
+    meta.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
 impl SyncEngine {
     pub fn new(pool: SqlitePool) -> Self {
         let watcher = FilesystemWatcher::new().expect("Failed to initialize watcher");
 
         Self {
             pool,
-            providers: Arc::new(Mutex::new(Vec::new())),
+            registry: Arc::new(std::sync::RwLock::new(ProviderRegistry::default())),
             watcher: Arc::new(Mutex::new(watcher)),
             sync_cache: Arc::new(Mutex::new(HashMap::new())),
             cancel_tokens: Arc::new(Mutex::new(HashMap::new())),
+            deletions_approved: Arc::new(Mutex::new(HashSet::new())),
+            recent_deletions: Arc::new(Mutex::new(HashMap::new())),
+            credential_store: Arc::new(std::sync::RwLock::new(None)),
         }
     }
 
-    pub async fn register_provider(&self, provider: Arc<dyn CloudProvider>) {
-        self.providers.lock().await.push(provider);
+    /// Register (or replace) the factory used to build providers for accounts of
+    /// `provider_type`, e.g. a backend from another crate or a test double
+    pub fn register_provider<F>(&self, provider_type: &str, factory: F)
+    where
+        F: Fn(&crate::models::Credentials) -> crate::provider::CloudResult<Box<dyn CloudProvider>> + Send + Sync + 'static,
+    {
+        self.registry.write().unwrap_or_else(|e| e.into_inner()).register(provider_type, factory);
+    }
+
+    /// Register (or replace) how accounts of `provider_type` sign in and refresh their tokens
+    pub fn register_auth<A: AuthProvider + 'static>(&self, provider_type: &str, auth: A) {
+        self.registry.write().unwrap_or_else(|e| e.into_inner()).register_auth(provider_type, auth);
+    }
+
+    /// Register (or replace) how accounts of `provider_type` are set up from a connection form
+    pub fn register_form<C: ConnectionForm + 'static>(&self, provider_type: &str, form: C) {
+        self.registry.write().unwrap_or_else(|e| e.into_inner()).register_form(provider_type, form);
+    }
+
+    fn auth_provider(&self, provider_type: &str) -> Option<Arc<dyn AuthProvider>> {
+        self.registry.read().unwrap_or_else(|e| e.into_inner()).auth(provider_type)
+    }
+
+    /// Build a provider instance from credentials with the registered factory
+    pub fn make_provider(&self, creds: &crate::models::Credentials) -> Result<Box<dyn CloudProvider>> {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        Ok(registry.build(creds)?)
     }
 
     pub async fn start<F>(&self, on_status: F) -> Result<()>
@@ -94,10 +289,14 @@ impl SyncEngine {
         // 3. Start the event loop with periodic background poll
         let mut last_poll = Instant::now();
         let mut last_token_refresh = Instant::now();
+        let mut last_trash_purge: Option<Instant> = None;
+        // One poll per account at a time; a slow one is not stacked on
+        let mut polls: HashMap<String, tokio::task::JoinHandle<()>> = HashMap::new();
         
         loop {
             // Collect events for a short period to group them
             let mut pending_paths: HashMap<PathBuf, notify::EventKind> = HashMap::new();
+            let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
             
             // Drain current channel
             while let Some(event_result) = {
@@ -105,21 +304,76 @@ impl SyncEngine {
                 watcher_guard.try_recv()
             } {
                 if let Ok(event) = event_result {
-                    for path in event.paths {
-                        pending_paths.insert(path, event.kind);
+                    use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+                    match event.kind {
+                        notify::EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                            renames.push((event.paths[0].clone(), event.paths[1].clone()));
+                        }
+                        // Halves of a rename the backend could not pair (e.g. moved in or out of the tree)
+                        notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                            for path in event.paths {
+                                pending_paths.insert(path, notify::EventKind::Remove(RemoveKind::Any));
+                            }
+                        }
+                        notify::EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                            for path in event.paths {
+                                pending_paths.insert(path, notify::EventKind::Create(CreateKind::Any));
+                            }
+                        }
+                        kind => {
+                            for path in event.paths {
+                                pending_paths.insert(path, kind);
+                            }
+                        }
                     }
                 }
             }
 
-            if !pending_paths.is_empty() {
+            if !pending_paths.is_empty() || !renames.is_empty() {
                 let pairs = self.get_sync_pairs().await.unwrap_or_default();
-                for (path, kind) in pending_paths {
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') || name == "node_modules" || name == "target" || name == "dist" { continue; }
+                // inotify reports the From and To halves alongside the paired event
+                for (from, to) in &renames {
+                    pending_paths.remove(from);
+                    pending_paths.remove(to);
+                }
+                renames.extend(self.match_moves(&mut pending_paths, &pairs).await);
+
+                // One matcher per pair and batch, so edited rule files take effect right away
+                let matchers: HashMap<i64, IgnoreMatcher> = pairs.iter().map(|p| (p.id, pair_matcher(p))).collect();
+                let ignored = |pair: &SyncPair, path: &Path| matchers.get(&pair.id).map(|m| m.is_ignored_path(path)).unwrap_or(false);
+
+                // Pairs that lost too much at once are paused before anything is deleted remotely
+                let mut held: HashSet<i64> = HashSet::new();
+                for pair in pairs.iter().filter(|p| p.status == "active") {
+                    let removals = pending_paths.iter().any(|(path, kind)| matches!(kind, notify::EventKind::Remove(_)) && path.starts_with(&pair.local_path));
+                    if removals && self.hold_mass_deletion(pair, None, &on_status).await.unwrap_or(false) {
+                        held.insert(pair.id);
+                    }
+                }
+
+                for (from, to) in renames {
+                    let pair = pairs.iter().find(|p| p.status == "active" && !held.contains(&p.id) && from.starts_with(&p.local_path) && to.starts_with(&p.local_path));
+                    let movable = pair.is_some_and(|p| {
+                        let mode = p.sync_mode();
+                        mode.uploads() && mode.deletes_remote() && !ignored(p, &from) && !ignored(p, &to)
+                    });
+                    let (Some(pair), true) = (pair, movable) else {
+                        // Moved across pairs, out of the tree, to or from an ignored name (editors
+                        // saving through a temp file), or in a pair that must not remove the
+                        // remote original: a removal plus a creation, each subject to the mode
+                        pending_paths.insert(from, notify::EventKind::Remove(notify::event::RemoveKind::Any));
+                        pending_paths.insert(to, notify::EventKind::Create(notify::event::CreateKind::Any));
+                        continue;
+                    };
+                    let cancel = self.cancel_tokens.lock().await.get(&pair.id).cloned();
+                    if let Err(e) = self.move_remote_entry(&from, &to, pair, on_status.clone(), cancel).await {
+                        eprintln!("Failed to sync move {:?} -> {:?}: {:?}", from, to, e);
                     }
+                }
 
+                for (path, kind) in pending_paths {
                     for pair in &pairs {
-                        if pair.status == "active" && path.starts_with(&pair.local_path) {
+                        if pair.status == "active" && !held.contains(&pair.id) && path.starts_with(&pair.local_path) && !ignored(pair, &path) {
                             let cancel = {
                                 let tokens = self.cancel_tokens.lock().await;
                                 tokens.get(&pair.id).cloned()
@@ -129,7 +383,13 @@ impl SyncEngine {
                                 if c.load(std::sync::atomic::Ordering::Relaxed) { continue; }
                             }
 
+                            let mode = pair.sync_mode();
+                            if !mode.uploads() {
+                                continue;
+                            }
+
                             match kind {
+                                notify::EventKind::Remove(_) if !mode.deletes_remote() => {}
                                 notify::EventKind::Remove(_) => {
                                     println!("Watcher: Detected removal of {:?}", path);
                                     if let Err(e) = self.delete_remote_file(&path, pair, on_status.clone(), cancel).await {
@@ -146,7 +406,7 @@ impl SyncEngine {
                                         if path.is_dir() {
                                             let creds = self.get_valid_credentials(&pair.account_id).await.unwrap_or(None);
                                             if let Some(creds) = creds {
-                                                if let Some(provider) = make_provider(&pair.account_id, creds.access_token) {
+                                                if let Ok(provider) = self.make_provider(&creds) {
                                                     let _ = self.ensure_remote_path_exists(provider.as_ref(), pair, &path).await;
                                                 }
                                             }
@@ -155,18 +415,16 @@ impl SyncEngine {
                                         }
                                     }
                                 }
-                                notify::EventKind::Create(_) => {
-                                    if path.exists() {
-                                        if path.is_dir() {
-                                            let creds = self.get_valid_credentials(&pair.account_id).await.unwrap_or(None);
-                                            if let Some(creds) = creds {
-                                                if let Some(provider) = make_provider(&pair.account_id, creds.access_token) {
-                                                    let _ = self.ensure_remote_path_exists(provider.as_ref(), pair, &path).await;
-                                                }
+                                notify::EventKind::Create(_) if path.exists() => {
+                                    if path.is_dir() {
+                                        let creds = self.get_valid_credentials(&pair.account_id).await.unwrap_or(None);
+                                        if let Some(creds) = creds {
+                                            if let Ok(provider) = self.make_provider(&creds) {
+                                                let _ = self.ensure_remote_path_exists(provider.as_ref(), pair, &path).await;
                                             }
-                                        } else {
-                                            let _ = self.sync_file(&path, pair, on_status.clone(), cancel).await;
                                         }
+                                    } else {
+                                        let _ = self.sync_file(&path, pair, on_status.clone(), cancel).await;
                                     }
                                 }
                                 _ => {}
@@ -176,11 +434,11 @@ impl SyncEngine {
                 }
             }
 
-            // Periodic token refresh (every 30 min) — keeps all Google OAuth sessions alive
+            // Periodic token refresh (every 30 min) — keeps all OAuth sessions alive
             if last_token_refresh.elapsed() > Duration::from_secs(30 * 60) {
                 println!("Proactive token refresh check...");
-                if let Ok(accounts) = self.get_accounts_for_provider("gdrive").await {
-                    for account in &accounts {
+                if let Ok(accounts) = self.get_all_accounts().await {
+                    for account in accounts.iter().filter(|a| self.auth_provider(&a.provider_id).is_some()) {
                         match self.get_valid_credentials(&account.account_id).await {
                             Ok(Some(_)) => {
                                 println!("Token refreshed successfully for {}", account.account_id);
@@ -195,19 +453,38 @@ impl SyncEngine {
                 last_token_refresh = Instant::now();
             }
 
+            // Periodic trash cleanup (at startup, then hourly)
+            if last_trash_purge.is_none_or(|t| t.elapsed() > Duration::from_secs(60 * 60)) {
+                match self.purge_trash().await {
+                    Ok(0) => {}
+                    Ok(n) => println!("Purged {} expired trash item(s)", n),
+                    Err(e) => eprintln!("Trash cleanup failed: {:?}", e),
+                }
+                last_trash_purge = Some(Instant::now());
+            }
+
             // Periodic cloud poll (every 60s)
             if last_poll.elapsed() > Duration::from_secs(60) {
                 let pairs = self.get_sync_pairs().await.unwrap_or_default();
-                for pair in &pairs {
-                    if pair.status == "active" {
-                        // Spawn background sync to avoid blocking the event loop
-                        let engine = self.clone();
-                        let pair_c = pair.clone();
-                        let on_status_c = on_status.clone();
-                        tokio::spawn(async move {
-                            let _ = engine.perform_initial_sync(&pair_c, on_status_c).await;
-                        });
+                let mut by_account: HashMap<String, Vec<SyncPair>> = HashMap::new();
+                for pair in pairs.into_iter().filter(|p| p.status == "active") {
+                    by_account.entry(pair.account_id.clone()).or_default().push(pair);
+                }
+                polls.retain(|_, poll| !poll.is_finished());
+                for (account_id, pairs) in by_account {
+                    if polls.contains_key(&account_id) {
+                        println!("Previous poll of {} still running, skipping", account_id);
+                        continue;
                     }
+                    // Spawn background sync to avoid blocking the event loop
+                    let engine = self.clone();
+                    let on_status_c = on_status.clone();
+                    let account = account_id.clone();
+                    polls.insert(account_id, tokio::spawn(async move {
+                        if let Err(e) = engine.poll_remote_changes(&account, &pairs, on_status_c).await {
+                            eprintln!("Remote poll failed for {}: {:?}", account, e);
+                        }
+                    }));
                 }
                 last_poll = Instant::now();
             }
@@ -236,10 +513,7 @@ impl SyncEngine {
 
         let creds = self.get_valid_credentials(&pair.account_id).await.unwrap_or(None);
         if let Some(creds) = creds {
-            let provider = match make_provider(&pair.account_id, creds.access_token) {
-                Some(p) => p,
-                None => return Ok(()),
-            };
+            let provider = self.make_provider(&creds)?;
 
             let filename = path.file_name()
                 .and_then(|n| n.to_str())
@@ -256,21 +530,29 @@ impl SyncEngine {
 
             // Pre-check: Does it actually need syncing?
             let local_meta = tokio::fs::metadata(path).await?;
-            let local_size = local_meta.len();
-            let local_hash = self.compute_local_hash(path).await?;
-            
+            // Hashed with the provider's own hash kind, so it is comparable with remote hashes
+            let local_hash = self.compute_local_hash(path, provider.as_ref()).await?;
+            let local = LocalState { size: local_meta.len(), modified_at: mtime_secs(&local_meta), hash: local_hash.clone() };
+
+            let key = relative_key(pair, path).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+
             let existing_info = provider.list_files(&remote_parent_id).await?;
             if let Some(remote) = existing_info.iter().find(|r| r.name == filename) {
-                let matches = if let Some(r_hash) = &remote.hash {
-                    *r_hash == local_hash
-                } else if let Some(r_size) = remote.size {
-                    r_size == local_size
-                } else {
-                    false
-                };
-
-                if matches {
-                    return Ok(());
+                // The same three-way comparison as a full pass, so a same-size edit is not
+                // mistaken for a match when the provider reports no hash
+                let base = self.get_file_state(pair.id, &key).await?;
+                match apply_mode(decide_file(Some(&local), Some(remote), base.as_ref()), pair.sync_mode(), true) {
+                    Decision::InSync => {
+                        self.record_file_state(pair.id, &key, path, Some(&local_hash), remote).await?;
+                        return Ok(());
+                    }
+                    // Remote also changed since the last sync: keep both versions
+                    Decision::BothChanged => {
+                        return self.handle_conflict(provider.as_ref(), path, &remote_parent_id, remote, pair, on_status).await;
+                    }
+                    Decision::Upload => {}
+                    // Only the remote copy changed; the next pass downloads it
+                    _ => return Ok(()),
                 }
             }
 
@@ -278,40 +560,202 @@ impl SyncEngine {
             let pair_id = pair.id;
             on_status(SyncStatus::Syncing { pair_id, path: path_str.clone() });
 
-            if let Err(e) = provider.upload_file(path, &remote_parent_id).await {
-                eprintln!("Upload error for {:?}: {:?}", path, e);
-                on_status(SyncStatus::Error { pair_id, path: path_str.clone(), message: e.to_string() });
-                if matches!(e, CloudError::Unauthenticated) {
-                    eprintln!("Token expired during upload, disconnecting account: {}", pair.account_id);
-                    let _ = self.disconnect_account(&pair.account_id).await;
-                    on_status(SyncStatus::AuthExpired { account_id: pair.account_id.clone() });
-                }
-                return Err(e.into());
-            } else {
-                println!("Successfully synced {:?} -> folder ID {}", path, pair.remote_path);
-                on_status(SyncStatus::Uploaded { pair_id, path: path_str.clone() });
+            let progress_status = on_status.clone();
+            let progress_path = path_str.clone();
+            let transfer = Transfer {
+                sessions: Some(Arc::new(SqliteUploadSessions { pool: self.pool.clone() })),
+                progress: Some(Arc::new(move |bytes, total| {
+                    progress_status(SyncStatus::Progress { pair_id, path: progress_path.clone(), bytes, total });
+                })),
+            };
 
-                {
-                    let mut cache = self.sync_cache.lock().await;
-                    cache.insert(path.to_path_buf(), Instant::now());
+            match provider.upload_file_with(path, &remote_parent_id, &transfer).await {
+                Err(e) => {
+                    eprintln!("Upload error for {:?}: {:?}", path, e);
+                    on_status(SyncStatus::Error { pair_id, path: path_str.clone(), message: e.to_string() });
+                    if matches!(e, CloudError::Unauthenticated) {
+                        eprintln!("Token expired during upload, disconnecting account: {}", pair.account_id);
+                        let _ = self.disconnect_account(&pair.account_id).await;
+                        on_status(SyncStatus::AuthExpired { account_id: pair.account_id.clone() });
+                    }
+                    return Err(e.into());
                 }
+                Ok(remote) => {
+                    println!("Successfully synced {:?} -> folder ID {}", path, pair.remote_path);
+                    self.record_file_state(pair.id, &key, path, Some(&local_hash), &remote).await?;
+                    on_status(SyncStatus::Uploaded { pair_id, path: path_str.clone() });
+
+                    {
+                        let mut cache = self.sync_cache.lock().await;
+                        cache.insert(path.to_path_buf(), Instant::now());
+                    }
 
-                let on_status_clone = on_status.clone();
-                tokio::spawn(async move {
-                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                    on_status_clone(SyncStatus::Idle { pair_id });
-                });
+                    let on_status_clone = on_status.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        on_status_clone(SyncStatus::Idle { pair_id });
+                    });
+                }
             }
         }
         Ok(())
     }
 
     pub async fn perform_initial_sync<F>(&self, pair: &SyncPair, on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        self.run_sync(pair, on_status, None).await
+    }
+
+    /// Bring the pairs of one account up to date with the remote side. Providers with a
+    /// change feed only have the directories touched since the stored cursor reconciled;
+    /// the rest, and any account whose cursor is missing or expired, get a full walk.
+    pub async fn poll_remote_changes<F>(&self, account_id: &str, pairs: &[SyncPair], on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let creds = self.get_valid_credentials(account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+
+        let cursor = self.get_change_cursor(account_id).await?;
+        let delta = match cursor {
+            Some(cursor) => match provider.changes_since(Some(&cursor)).await {
+                Ok(delta) => delta,
+                Err(CloudError::InvalidCursor) => {
+                    println!("Change cursor for {} expired, walking the full tree", account_id);
+                    None
+                }
+                Err(e) => return Err(e.into()),
+            },
+            None => None,
+        };
+
+        let Some(ChangeSet { changes, cursor }) = delta else {
+            // Take the cursor before walking, so changes made during the walk are seen next time
+            let start = provider.changes_since(None).await?;
+            let mut failed = 0;
+            for pair in pairs {
+                if let Err(e) = self.perform_initial_sync(pair, on_status.clone()).await {
+                    eprintln!("Full sync of {} failed: {:?}", pair.local_path, e);
+                    failed += 1;
+                }
+            }
+            // Without a new cursor the next poll walks again
+            if failed > 0 {
+                anyhow::bail!("{} of {} pair(s) failed to sync, not storing a change cursor for {}", failed, pairs.len(), account_id);
+            }
+            if let Some(start) = start {
+                self.set_change_cursor(account_id, &start.cursor).await?;
+            }
+            return Ok(());
+        };
+
+        let mut failed = 0;
+
+        for pair in pairs {
+            let states = self.get_file_states(pair.id).await?;
+            // Directory keys by remote id, and the parent directory of every known entry
+            let mut dir_keys: HashMap<&str, &str> = HashMap::from([(pair.remote_path.as_str(), "")]);
+            let mut entry_parents: HashMap<&str, &str> = HashMap::new();
+            for state in &states {
+                if let Some(id) = state.remote_id.as_deref() {
+                    if state.is_dir {
+                        dir_keys.insert(id, &state.path);
+                    }
+                    entry_parents.insert(id, split_key(&state.path).0);
+                }
+            }
+
+            let matcher = pair_matcher(pair);
+            // Entries that only got a new name or parent are renamed locally, not re-downloaded
+            let mut moves: Vec<(String, String, String)> = Vec::new();
+            for change in changes.iter().filter(|c| !c.removed) {
+                let (Some(old_key), Some(name)) = (states.iter().find(|s| s.remote_id.as_deref() == Some(change.file_id.as_str())).map(|s| s.path.as_str()), &change.name) else { continue };
+                let Some(parent_key) = change.parents.iter().find_map(|p| dir_keys.get(p.as_str()).copied()) else { continue };
+                let new_key = join_key(parent_key, name);
+                // Moved into an excluded folder: dropped by the sync of its old folder instead
+                if new_key != old_key && !matcher.is_ignored(&new_key, states.iter().any(|s| s.path == old_key && s.is_dir)) {
+                    moves.push((old_key.to_string(), new_key, change.file_id.clone()));
+                }
+            }
+
+            let mut dirs: Vec<String> = Vec::new();
+            for change in &changes {
+                // Where it was at the last sync, and where it is now
+                let old = entry_parents.get(change.file_id.as_str()).copied();
+                let new = change.parents.iter().filter_map(|p| dir_keys.get(p.as_str()).copied());
+                for key in old.into_iter().chain(new) {
+                    if !dirs.iter().any(|d| d == key) {
+                        dirs.push(key.to_string());
+                    }
+                }
+            }
+
+            // One-way pairs put the entry back where it was instead
+            if !pair.sync_mode().downloads() {
+                moves.clear();
+            }
+            for (old_key, new_key, remote_id) in moves {
+                if let Err(e) = self.move_local_entry(pair, &old_key, &new_key, &remote_id, on_status.clone()).await {
+                    eprintln!("Failed to apply remote move {} -> {}: {:?}", old_key, new_key, e);
+                }
+            }
+
+            if !dirs.is_empty() {
+                println!("Remote changes in {} folder(s) of {}", dirs.len(), pair.local_path);
+                if let Err(e) = self.run_sync(pair, on_status.clone(), Some(dirs)).await {
+                    eprintln!("Applying remote changes to {} failed: {:?}", pair.local_path, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        // The feed has already handed these changes over; keep the old cursor so the
+        // next poll is given them again instead of losing them
+        if failed > 0 {
+            anyhow::bail!("{} of {} pair(s) failed to sync, keeping the change cursor for {}", failed, pairs.len(), account_id);
+        }
+        self.set_change_cursor(account_id, &cursor).await?;
+        Ok(())
+    }
+
+    /// Provider, base index and settings for one pass over `pair`
+    async fn prepare_run<F>(&self, pair: &SyncPair, on_status: Arc<F>, cancel: Arc<std::sync::atomic::AtomicBool>, shallow: bool, dry_run: bool) -> Result<SyncRun<F>> {
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider: Arc<dyn CloudProvider> = Arc::from(self.make_provider(&creds)?);
+
+        // Group the base index by parent directory so each level can find its entries
+        let mut base: HashMap<String, HashMap<String, FileState>> = HashMap::new();
+        for state in self.get_file_states(pair.id).await? {
+            let (parent, name) = split_key(&state.path);
+            base.entry(parent.to_string()).or_default().insert(name.to_string(), state);
+        }
+
+        Ok(SyncRun {
+            pair: pair.clone(),
+            provider,
+            base,
+            on_status,
+            cancel,
+            shallow,
+            ignore: pair_matcher(pair),
+            mode: pair.sync_mode(),
+            plan: dry_run.then(|| std::sync::Mutex::new(Vec::new())),
+        })
+    }
+
+    /// One sync pass over a pair: the whole tree, or just the directory levels in `dirs`
+    /// (keys relative to the pair root, "" for the root itself)
+    async fn run_sync<F>(&self, pair: &SyncPair, on_status: Arc<F>, dirs: Option<Vec<String>>) -> Result<()>
     where
         F: Fn(SyncStatus) + Send + Sync + 'static,
     {
         let local_path = Path::new(&pair.local_path);
         if !local_path.exists() { return Ok(()); }
+        if self.hold_mass_deletion(pair, dirs.as_deref(), &on_status).await? {
+            return Ok(());
+        }
 
         let token = {
             let mut tokens = self.cancel_tokens.lock().await;
@@ -323,15 +767,30 @@ impl SyncEngine {
             t
         };
 
-        let _token_c = token.clone();
-
-        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-        let provider: Arc<dyn CloudProvider> = match provider_type(&pair.account_id) {
-            "gdrive" => Arc::new(crate::providers::gdrive::GoogleDriveProvider::new(creds.access_token)),
-            _ => return Ok(()),
-        };
+        let run = self.prepare_run(pair, on_status, token, dirs.is_some(), false).await?;
+        match dirs {
+            None => self.sync_directory_recursive(local_path, Some(&pair.remote_path), "", &run).await?,
+            Some(dirs) => {
+                for key in dirs.into_iter().filter(|k| !run.ignore.is_ignored(k, true)) {
+                    let remote_id = if key.is_empty() {
+                        Some(pair.remote_path.clone())
+                    } else {
+                        let (parent, name) = split_key(&key);
+                        run.base.get(parent).and_then(|entries| entries.get(name)).and_then(|s| s.remote_id.clone())
+                    };
+                    let local_dir = local_path.join(&key);
+                    // A folder removed on either side is handled by the level above it
+                    if let (Some(remote_id), true) = (remote_id, local_dir.is_dir()) {
+                        self.sync_directory_recursive(&local_dir, Some(&remote_id), &key, &run).await?;
+                    }
+                }
+            }
+        }
 
-        self.sync_directory_recursive(local_path, &pair.remote_path, pair, &provider, &on_status, token).await?;
+        // A newer run took over part way, so this was not a complete pass
+        if run.cancelled() {
+            anyhow::bail!("Sync of {} was cancelled by a newer run", pair.local_path);
+        }
 
         // Update last sync time
         let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
@@ -344,183 +803,149 @@ impl SyncEngine {
         Ok(())
     }
 
+    /// Entries synced before that are gone locally, so the next pass would delete them
+    /// remotely. Limited to what lies under the directory levels in `scope` when given.
+    fn missing_locally<'a>(pair: &SyncPair, states: &'a [FileState], scope: Option<&[String]>) -> Vec<&'a FileState> {
+        if !pair.sync_mode().deletes_remote() {
+            return Vec::new();
+        }
+        let matcher = pair_matcher(pair);
+        let root = Path::new(&pair.local_path);
+        states.iter()
+            .filter(|s| scope.is_none_or(|dirs| dirs.iter().any(|d| key_within(split_key(&s.path).0, d))))
+            .filter(|s| !matcher.is_ignored(&s.path, s.is_dir))
+            .filter(|s| !root.join(&s.path).exists())
+            .collect()
+    }
+
+    /// Mass-deletion safeguard: pause the pair and ask for confirmation instead of
+    /// syncing when the next pass would delete more remotely than the pair allows
+    /// (an unmounted drive, an emptied folder). Returns whether it tripped.
+    async fn hold_mass_deletion<F>(&self, pair: &SyncPair, scope: Option<&[String]>, on_status: &Arc<F>) -> Result<bool>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        if self.deletions_approved.lock().await.remove(&pair.id) {
+            self.recent_deletions.lock().await.remove(&pair.id);
+            return Ok(false);
+        }
+        // Files the watcher already deleted are gone from the index, but still count
+        let recent = {
+            let mut recent = self.recent_deletions.lock().await;
+            let times = recent.entry(pair.id).or_default();
+            times.retain(|t| t.elapsed() < DELETION_WINDOW);
+            times.len()
+        };
+        let states = self.get_file_states(pair.id).await?;
+        let missing = Self::missing_locally(pair, &states, scope);
+        let deletions = missing.iter().filter(|s| !s.is_dir).count() + recent;
+        let total = states.iter().filter(|s| !s.is_dir).count() + recent;
+        if !exceeds_delete_limit(deletions, total, pair.delete_limit, pair.delete_limit_percent) {
+            return Ok(false);
+        }
+
+        println!("Sync: {} of {} files would be deleted on cloud for {}, pausing until confirmed", deletions, total, pair.local_path);
+        self.set_pair_active(pair.id, false).await?;
+        on_status(SyncStatus::DeletionsPending { pair_id: pair.id, count: deletions });
+        Ok(true)
+    }
+
+    /// Keys of the entries the next pass would delete remotely, for review after the
+    /// mass-deletion safeguard paused the pair
+    pub async fn pending_deletions(&self, pair_id: i64) -> Result<Vec<String>> {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let states = self.get_file_states(pair_id).await?;
+        Ok(Self::missing_locally(&pair, &states, None).into_iter().map(|s| s.path.clone()).collect())
+    }
+
+    /// Go ahead with the deletions held back by the safeguard: resume the pair and
+    /// sync it once without the limit
+    pub async fn confirm_deletions<F>(&self, pair_id: i64, on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        self.deletions_approved.lock().await.insert(pair_id);
+        self.set_pair_active(pair_id, true).await?;
+        self.perform_initial_sync(&pair, on_status).await
+    }
+
+    /// Undo the deletions held back by the safeguard: the entries are downloaded again
+    /// instead of being deleted remotely, and the pair is resumed
+    pub async fn restore_deletions<F>(&self, pair_id: i64, on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let states = self.get_file_states(pair_id).await?;
+        // Without a base record, an entry only found remotely is downloaded
+        for state in Self::missing_locally(&pair, &states, None) {
+            self.forget_file_state(pair_id, &state.path).await?;
+        }
+        self.recent_deletions.lock().await.remove(&pair_id);
+        self.set_pair_active(pair_id, true).await?;
+        self.perform_initial_sync(&pair, on_status).await
+    }
+
+    /// What a full sync pass over the pair would do right now. Nothing is changed on
+    /// either side or in the state index, and a sync already running is left alone.
+    pub async fn plan_sync(&self, pair_id: i64) -> Result<SyncPlan> {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let run = self.prepare_run(&pair, Arc::new(|_: SyncStatus| {}), cancel, false, true).await?;
+        self.sync_directory_recursive(Path::new(&pair.local_path), Some(&pair.remote_path), "", &run).await?;
+
+        let actions = run.plan.map(|p| p.into_inner().unwrap_or_else(|e| e.into_inner())).unwrap_or_default();
+        Ok(SyncPlan { pair_id, actions })
+    }
+
+    /// Reconcile one directory level in two phases: every entry gets a decision first,
+    /// then the decisions are carried out, or written to the plan on a dry run.
+    /// `remote_dir_id` is only `None` on a dry run, for a folder not created remotely yet.
     #[async_recursion]
     async fn sync_directory_recursive<F>(
         &self,
         local_dir: &Path,
-        remote_dir_id: &str,
-        pair: &SyncPair,
-        provider: &Arc<dyn CloudProvider>,
-        on_status: &Arc<F>,
-        cancel: Arc<std::sync::atomic::AtomicBool>
+        remote_dir_id: Option<&str>,
+        dir_key: &str,
+        run: &SyncRun<F>,
     ) -> Result<()>
     where
         F: Fn(SyncStatus) + Send + Sync + 'static,
     {
-        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+        if run.cancelled() {
              return Ok(());
         }
 
+        let entries = self.plan_level(local_dir, remote_dir_id, dir_key, run).await?;
 
-        // 1. Get local files & dirs
-        let mut local_entries = HashMap::new();
-        let mut entries = tokio::fs::read_dir(local_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let name = entry.file_name().to_string_lossy().to_string();
-            local_entries.insert(name, entry.path());
-        }
-
-        // 2. Get remote files & dirs
-        let remote_entries = provider.list_files(remote_dir_id).await?;
-
-        let mut tasks: Vec<BoxFuture<'_, Result<()>>> = Vec::new();
-
-        // 3. Reconcile: Local to Cloud
-        for (name, path) in &local_entries {
-            if name.starts_with('.') || name == "node_modules" || name == "target" || name == "dist" { continue; }
-
-            if let Some(remote) = remote_entries.iter().find(|r| &r.name == name) {
-                if path.is_dir() {
-                    if remote.is_dir {
-                        let path = path.clone();
-                        let remote_id = remote.id.clone();
-                        let cancel_c = cancel.clone();
-                        tasks.push(Box::pin(async move {
-                            if cancel_c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
-                            self.sync_directory_recursive(&path, &remote_id, pair, provider, on_status, cancel_c).await
-                        }));
-                    }
-                } else {
-                    if !remote.is_dir {
-                        let path = path.clone();
-                        let remote_id = remote.id.clone();
-                        let remote_size = remote.size;
-                        let remote_mtime = remote.modified_at;
-                        let remote_hash = remote.hash.clone();
-                        let cancel_c = cancel.clone();
-                        let on_status_c = on_status.clone();
-                        tasks.push(Box::pin(async move {
-                            if cancel_c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
-                            let local_meta = tokio::fs::metadata(&path).await?;
-                            let local_size = local_meta.len();
-                            let local_mtime = local_meta.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
-
-                            match (remote_size, remote_mtime, remote_hash) {
-                                (Some(_r_size), Some(r_mtime), Some(r_hash)) => {
-                                    let local_hash = self.compute_local_hash(&path).await?;
-                                    if local_hash != r_hash {
-                                        if local_mtime > r_mtime + 2 {
-                                            println!("Sync: Local file {:?} is newer and content differs. Uploading.", path);
-                                            let _ = self.sync_file(&path, pair, on_status_c, Some(cancel_c)).await;
-                                        } else if r_mtime > local_mtime + 2 {
-                                            println!("Sync: Cloud file {:?} is newer and content differs. Downloading.", path);
-                                            let _ = self.sync_remote_to_local(&remote_id, &path, pair, on_status_c, Some(cancel_c)).await;
-                                        }
-                                    }
-                                }
-                                (Some(r_size), Some(r_mtime), None) => {
-                                    if local_mtime > r_mtime + 2 {
-                                        println!("Sync: Local file {:?} is newer (no cloud hash). Uploading.", path);
-                                        let _ = self.sync_file(&path, pair, on_status_c, Some(cancel_c)).await;
-                                    } else if r_mtime > local_mtime + 2 || local_size != r_size {
-                                        println!("Sync: Cloud file {:?} is newer or size differs (no cloud hash). Downloading.", path);
-                                        let _ = self.sync_remote_to_local(&remote_id, &path, pair, on_status_c, Some(cancel_c)).await;
-                                    }
-                                }
-                                _ => {
-                                    println!("Sync: Missing metadata for {:?}, checking via sync_file.", path);
-                                    let _ = self.sync_file(&path, pair, on_status_c, Some(cancel_c)).await;
-                                }
-                            };
-                            Ok(())
-                        }));
-                    }
+        if let Some(plan) = &run.plan {
+            for entry in entries {
+                if let Some(action) = entry.action() {
+                    plan.lock().unwrap_or_else(|e| e.into_inner()).push(action);
                 }
-            } else {
-                // Missing in cloud
-                let path = path.clone();
-                let name = name.clone();
-                let remote_dir_id = remote_dir_id.to_string();
-                let on_status_c = on_status.clone();
-                let cancel_c = cancel.clone();
-                tasks.push(Box::pin(async move {
-                    if cancel_c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
-                    let local_meta = tokio::fs::metadata(&path).await?;
-                    let local_mtime = local_meta.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
-                    
-                    let mut was_there_before = false;
-                    if let Some(last_sync) = pair.last_sync_at {
-                        if local_mtime < last_sync {
-                            was_there_before = true;
-                        }
-                    }
-
-                    if path.is_dir() {
-                        if was_there_before {
-                            println!("Directory {:?} missing on cloud (was there before), deleting locally", path);
-                            let _ = tokio::fs::remove_dir_all(&path).await;
-                        } else {
-                            let new_folder_id = provider.create_folder(&name, &remote_dir_id).await?;
-                            self.sync_directory_recursive(&path, &new_folder_id, pair, provider, &on_status_c, cancel_c).await?;
-                        }
-                    } else {
-                        if was_there_before {
-                            println!("File {:?} missing on cloud (was there before), deleting locally", path);
-                            let path_str = path.to_string_lossy().to_string();
-                            let pair_id = pair.id;
-                            if let Err(e) = tokio::fs::remove_file(&path).await {
-                                eprintln!("Failed to delete local file {:?}: {:?}", path, e);
-                            } else {
-                                on_status_c(SyncStatus::Deleted { pair_id, path: path_str });
-                            }
-                        } else {
-                            let _ = self.sync_file(&path, pair, on_status_c, Some(cancel_c)).await;
-                        }
-                    }
-                    Ok(())
-                }));
-            }
-        }
-
-        // 4. Reconcile: Cloud to Local
-        for remote in &remote_entries {
-            if remote.name.starts_with('.') || remote.name == "node_modules" || remote.name == "target" || remote.name == "dist" { continue; }
-            if !local_entries.contains_key(&remote.name) {
-                let dest = local_dir.join(&remote.name);
-                let remote_name = remote.name.clone();
-                let remote_id = remote.id.clone();
-                let remote_is_dir = remote.is_dir;
-                let remote_modified_at = remote.modified_at;
-                let remote_dir_id = remote_dir_id.to_string();
-                let on_status_c = on_status.clone();
-                let cancel_c = cancel.clone();
-                tasks.push(Box::pin(async move {
-                    if cancel_c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
-                    let mut was_deleted_locally = false;
-                    if let (Some(last_sync), Some(r_mtime)) = (pair.last_sync_at, remote_modified_at) {
-                        if r_mtime <= last_sync {
-                            was_deleted_locally = true;
-                        }
-                    }
+                // Folders that are kept or created are walked as if both sides had them
+                if entry.is_dir && matches!(entry.decision, Decision::InSync | Decision::Upload | Decision::Download) {
+                    let remote_id = entry.remote.as_ref().map(|r| r.id.clone());
+                    self.sync_directory_recursive(&entry.path, remote_id.as_deref(), &entry.key, run).await?;
+                }
+            }
+            return Ok(());
+        }
 
-                    if was_deleted_locally {
-                        println!("Sync: File {:?} missing locally (was there before), deleting on cloud", remote_name);
-                        if let Err(e) = provider.delete_file(&remote_name, &remote_dir_id).await {
-                            eprintln!("Failed to sync local deletion to cloud for {}: {:?}", remote_name, e);
-                        }
+        let remote_dir_id = remote_dir_id.ok_or_else(|| anyhow::anyhow!("Missing remote folder"))?;
+        let tasks: Vec<BoxFuture<'_, Result<()>>> = entries.into_iter()
+            .map(|entry| -> BoxFuture<'_, Result<()>> {
+                Box::pin(async move {
+                    if run.cancelled() { return Ok(()); }
+                    if entry.is_dir {
+                        self.apply_dir(entry, remote_dir_id, run).await
                     } else {
-                        if remote_is_dir {
-                            tokio::fs::create_dir_all(&dest).await?;
-                            self.sync_directory_recursive(&dest, &remote_id, pair, provider, &on_status_c, cancel_c).await?;
-                        } else {
-                            println!("Sync: File {:?} is new on cloud. Downloading.", remote_name);
-                            let _ = self.sync_remote_to_local(&remote_id, &dest, pair, on_status_c, Some(cancel_c)).await;
-                        }
+                        self.apply_file(entry, remote_dir_id, run).await
                     }
-                    Ok(())
-                }));
-            }
-        }
+                })
+            })
+            .collect();
 
         // Run concurrently (up to 3 parallel file transfers / directory syncs)
         let mut stream = futures::stream::iter(tasks).buffer_unordered(3);
@@ -533,49 +958,522 @@ impl SyncEngine {
         Ok(())
     }
 
-    async fn ensure_remote_path_exists(&self, provider: &dyn CloudProvider, pair: &SyncPair, local_path: &Path) -> Result<String> {
-        let relative = local_path.strip_prefix(&pair.local_path)?;
-        let mut current_id = pair.remote_path.clone();
-        
-        for component in relative.components() {
-            let name = component.as_os_str().to_string_lossy();
-            if name == "." || name == "/" { continue; }
-
-            let entries = provider.list_files(&current_id).await?;
-            if let Some(folder) = entries.iter().find(|e| e.name == name && e.is_dir) {
-                current_id = folder.id.clone();
-            } else {
-                current_id = provider.create_folder(&name, &current_id).await?;
-            }
-        }
-        
-        Ok(current_id)
-    }
-
-    pub async fn sync_remote_to_local<F>(&self, file_id: &str, dest: &Path, pair: &SyncPair, on_status: Arc<F>, cancel: Option<Arc<std::sync::atomic::AtomicBool>>) -> Result<()>
+    /// First phase for a directory level: compare every entry's local, remote and base state
+    async fn plan_level<F>(&self, local_dir: &Path, remote_dir_id: Option<&str>, dir_key: &str, run: &SyncRun<F>) -> Result<Vec<PlannedEntry>>
     where
         F: Fn(SyncStatus) + Send + Sync + 'static,
     {
-        if let Some(c) = &cancel {
-            if c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
+        // 1. Get local files & dirs (none for a folder that only exists remotely)
+        let mut local_entries = HashMap::new();
+        if local_dir.is_dir() {
+            let mut entries = tokio::fs::read_dir(local_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                local_entries.insert(name, entry.path());
+            }
         }
-        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-        let provider = match make_provider(&pair.account_id, creds.access_token) {
-            Some(p) => p,
-            None => return Ok(()),
-        };
 
-        let path_str = dest.to_string_lossy().to_string();
-        let pair_id = pair.id;
-        on_status(SyncStatus::Downloading { pair_id, path: path_str.clone() });
+        // 2. Get remote files & dirs, and what both sides looked like at the last sync
+        let remote_entries = match remote_dir_id {
+            Some(id) => run.provider.list_files(id).await?,
+            None => Vec::new(),
+        };
+        let empty = HashMap::new();
+        let base_entries = run.base.get(dir_key).unwrap_or(&empty);
 
-        if let Err(e) = provider.download_file(file_id, dest).await {
-            eprintln!("Download error: {:?}", e);
+        let mut names: Vec<&String> = local_entries.keys()
+            .chain(remote_entries.iter().map(|r| &r.name))
+            .chain(base_entries.keys())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        // 3. Decide each entry against its base state
+        let mut pending: Vec<BoxFuture<'_, Result<PlannedEntry>>> = Vec::new();
+        for name in names {
+            let path = local_dir.join(name);
+            let local = local_entries.get(name);
+            let remote = remote_entries.iter().find(|r| &r.name == name).cloned();
+            let base = base_entries.get(name).cloned();
+            let key = join_key(dir_key, name);
+
+            let local_is_dir = local.map(|p| p.is_dir());
+            let is_dir = match (local_is_dir, &remote) {
+                (Some(l), Some(r)) if l != r.is_dir => {
+                    eprintln!("Sync: {:?} is a file on one side and a folder on the other, skipping", path);
+                    continue;
+                }
+                (Some(l), _) => l,
+                (None, Some(r)) => r.is_dir,
+                (None, None) => base.as_ref().map(|b| b.is_dir).unwrap_or(false),
+            };
+            let mut entry = PlannedEntry { name: name.clone(), key, path, is_dir, local: None, remote, base, decision: Decision::Forget };
+
+            if run.ignore.is_ignored(&entry.key, is_dir) {
+                // No longer tracked: should it come back, both sides are merged afresh
+                // rather than a missing side being taken for a deletion
+                if entry.base.is_some() {
+                    pending.push(Box::pin(async move { Ok(entry) }));
+                }
+                continue;
+            }
+
+            let local_exists = local.is_some();
+            pending.push(Box::pin(async move {
+                let decision = if entry.is_dir {
+                    decide_dir(local_exists, entry.remote.is_some(), entry.base.as_ref())
+                } else {
+                    if local_exists {
+                        entry.local = Some(self.local_state(&entry.path, entry.base.as_ref(), run.provider.as_ref()).await?);
+                    }
+                    decide_file(entry.local.as_ref(), entry.remote.as_ref(), entry.base.as_ref())
+                };
+                entry.decision = apply_mode(decision, run.mode, local_exists);
+                Ok(entry)
+            }));
+        }
+
+        // Hash changed files a few at a time, keeping the entries in name order
+        let mut planned = Vec::new();
+        let mut stream = futures::stream::iter(pending).buffered(3);
+        while let Some(res) = stream.next().await {
+            match res {
+                Ok(entry) => planned.push(entry),
+                Err(e) => eprintln!("Directory sync error: {:?}", e),
+            }
+        }
+        Ok(planned)
+    }
+
+    /// Size, mtime and content hash of a local file, reusing the recorded hash
+    /// when size and mtime are unchanged since the last sync
+    async fn local_state(&self, path: &Path, base: Option<&FileState>, provider: &dyn CloudProvider) -> Result<LocalState> {
+        let meta = tokio::fs::metadata(path).await?;
+        let size = meta.len();
+        let modified_at = mtime_secs(&meta);
+        let cached = base
+            .filter(|b| b.size == Some(size as i64) && b.modified_at == Some(modified_at))
+            .and_then(|b| b.hash.clone());
+        let hash = match cached {
+            Some(h) => h,
+            None => self.compute_local_hash(path, provider).await?,
+        };
+        Ok(LocalState { size, modified_at, hash })
+    }
+
+    /// Second phase for a folder: carry out its decision, then recurse into it
+    async fn apply_dir<F>(&self, entry: PlannedEntry, remote_dir_id: &str, run: &SyncRun<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let pair = &run.pair;
+        let PlannedEntry { name, key, path, remote, base, decision, .. } = entry;
+        let (path, key) = (path.as_path(), key.as_str());
+        match decision {
+            Decision::InSync => {
+                let remote = remote.ok_or_else(|| anyhow::anyhow!("Missing remote folder"))?;
+                if base.as_ref().and_then(|b| b.remote_id.as_deref()) != Some(remote.id.as_str()) {
+                    self.record_file_state(pair.id, key, path, None, &remote).await?;
+                } else if run.shallow {
+                    // Known on both sides; changes inside it are reported on their own
+                    return Ok(());
+                }
+                self.sync_directory_recursive(path, Some(&remote.id), key, run).await?;
+            }
+            Decision::Upload => {
+                let new_folder_id = run.provider.create_folder(&name, remote_dir_id).await?;
+                let remote = RemoteFile { id: new_folder_id, name: name.clone(), is_dir: true, size: None, modified_at: None, hash: None, revision: None };
+                self.record_file_state(pair.id, key, path, None, &remote).await?;
+                self.sync_directory_recursive(path, Some(&remote.id), key, run).await?;
+            }
+            Decision::Download => {
+                let remote = remote.ok_or_else(|| anyhow::anyhow!("Missing remote folder"))?;
+                tokio::fs::create_dir_all(path).await?;
+                self.record_file_state(pair.id, key, path, None, &remote).await?;
+                self.sync_directory_recursive(path, Some(&remote.id), key, run).await?;
+            }
+            Decision::DeleteLocal => {
+                println!("Directory {:?} missing on cloud (was there before), moving it to the trash", path);
+                self.trash_local_entry(pair, path, TrashReason::Deleted).await?;
+                self.forget_file_state(pair.id, key).await?;
+            }
+            Decision::DeleteRemote => {
+                println!("Sync: Folder {:?} missing locally (was there before), deleting on cloud", name);
+                self.remove_remote_entry(run.provider.as_ref(), pair, key, &name, remote_dir_id).await?;
+                self.forget_file_state(pair.id, key).await?;
+            }
+            Decision::Forget | Decision::BothChanged => {
+                self.forget_file_state(pair.id, key).await?;
+            }
+            Decision::Skip => {}
+        }
+        Ok(())
+    }
+
+    /// Second phase for a file: carry out its decision
+    async fn apply_file<F>(&self, entry: PlannedEntry, remote_dir_id: &str, run: &SyncRun<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let pair = &run.pair;
+        let PlannedEntry { name, key, path, local, remote, base, decision, .. } = entry;
+        let (path, key) = (path.as_path(), key.as_str());
+        let on_status = run.on_status.clone();
+        let cancel = Some(run.cancel.clone());
+        match decision {
+            Decision::InSync => {
+                if let (Some(local), Some(remote)) = (&local, &remote) {
+                    let stale = base.as_ref().map(|b| {
+                        b.hash.as_deref() != Some(local.hash.as_str())
+                            || b.remote_id.as_deref() != Some(remote.id.as_str())
+                            || b.remote_hash != remote.hash
+                            || b.remote_revision != remote.revision
+                            || b.remote_modified_at != remote.modified_at
+                    }).unwrap_or(true);
+                    if stale {
+                        self.record_file_state(pair.id, key, path, Some(&local.hash), remote).await?;
+                    }
+                }
+            }
+            Decision::Upload => {
+                println!("Sync: Local file {:?} is new or changed. Uploading.", path);
+                let _ = self.sync_file(path, pair, on_status, cancel).await;
+            }
+            Decision::Download => {
+                if let Some(remote) = &remote {
+                    println!("Sync: Cloud file {:?} is new or changed. Downloading.", path);
+                    let _ = self.sync_remote_to_local(remote, path, pair, on_status, cancel).await;
+                }
+            }
+            Decision::DeleteLocal => {
+                println!("File {:?} missing on cloud (was there before), moving it to the trash", path);
+                let path_str = path.to_string_lossy().to_string();
+                if let Err(e) = self.trash_local_entry(pair, path, TrashReason::Deleted).await {
+                    eprintln!("Failed to delete local file {:?}: {:?}", path, e);
+                } else {
+                    self.forget_file_state(pair.id, key).await?;
+                    on_status(SyncStatus::Deleted { pair_id: pair.id, path: path_str });
+                }
+            }
+            Decision::DeleteRemote => {
+                println!("Sync: File {:?} missing locally (was there before), deleting on cloud", name);
+                if let Err(e) = self.remove_remote_entry(run.provider.as_ref(), pair, key, &name, remote_dir_id).await {
+                    eprintln!("Failed to sync local deletion to cloud for {}: {:?}", name, e);
+                } else {
+                    self.forget_file_state(pair.id, key).await?;
+                }
+            }
+            Decision::BothChanged => {
+                if let Some(remote) = &remote {
+                    self.handle_conflict(run.provider.as_ref(), path, remote_dir_id, remote, pair, on_status).await?;
+                }
+            }
+            Decision::Forget => {
+                if base.is_some() {
+                    self.forget_file_state(pair.id, key).await?;
+                }
+            }
+            Decision::Skip => {}
+        }
+        Ok(())
+    }
+
+    /// Keep both versions of a file that changed on both sides since the last sync.
+    /// The local version is renamed to a conflicted copy and uploaded under that name,
+    /// then the remote version is downloaded to the original path.
+    async fn handle_conflict<F>(&self, provider: &dyn CloudProvider, path: &Path, remote_dir_id: &str, remote: &RemoteFile, pair: &SyncPair, on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let name = path.file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid filename"))?;
+        let key = relative_key(pair, path).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+
+        let host = gethostname::gethostname().to_string_lossy().to_string();
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut n = 1;
+        let copy_path = loop {
+            let candidate = path.with_file_name(conflicted_copy_name(name, &host, &date, n));
+            if !candidate.exists() { break candidate; }
+            n += 1;
+        };
+        let copy_key = relative_key(pair, &copy_path).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+
+        // Without a remote hash, equal sizes may still be equal contents
+        if remote.hash.is_none() && remote.size == Some(tokio::fs::metadata(path).await?.len()) {
+            if let Some(local_hash) = self.same_content(provider, pair, path, remote).await? {
+                return self.record_file_state(pair.id, &key, path, Some(&local_hash), remote).await;
+            }
+        }
+
+        println!("Conflict: {:?} changed on both sides, keeping local version as {:?}", path, copy_path);
+        {
+            // Keep the watcher from re-uploading either file while we shuffle them around
+            let mut cache = self.sync_cache.lock().await;
+            cache.insert(path.to_path_buf(), Instant::now());
+            cache.insert(copy_path.clone(), Instant::now());
+        }
+        tokio::fs::rename(path, &copy_path).await?;
+
+        let copy_hash = self.compute_local_hash(&copy_path, provider).await?;
+        let uploaded = provider.upload_file(&copy_path, remote_dir_id).await?;
+        self.record_file_state(pair.id, &copy_key, &copy_path, Some(&copy_hash), &uploaded).await?;
+
+        self.sync_remote_to_local(remote, path, pair, on_status.clone(), None).await?;
+
+        sqlx::query("INSERT INTO conflicts (pair_id, path, conflict_path) VALUES (?, ?, ?)")
+            .bind(pair.id)
+            .bind(&key)
+            .bind(&copy_key)
+            .execute(&self.pool)
+            .await?;
+
+        on_status(SyncStatus::Conflict {
+            pair_id: pair.id,
+            path: path.to_string_lossy().to_string(),
+            conflict_path: copy_path.to_string_lossy().to_string(),
+        });
+        Ok(())
+    }
+
+    /// Download `remote` next to the pair and compare it with the local file at `path`.
+    /// Returns the local hash when both have the same content.
+    async fn same_content(&self, provider: &dyn CloudProvider, pair: &SyncPair, path: &Path, remote: &RemoteFile) -> Result<Option<String>> {
+        let staging = Path::new(&pair.local_path)
+            .join(crate::download::STAGING_DIR)
+            .join(format!("{:016x}", thread_rng().gen::<u64>()));
+        let compared: Result<Option<String>> = async {
+            let staged = staging.join("compare");
+            tokio::fs::create_dir_all(&staging).await?;
+            provider.download_remote(remote, &staged).await?;
+            let local_hash = self.compute_local_hash(path, provider).await?;
+            let same = self.compute_local_hash(&staged, provider).await? == local_hash;
+            Ok(same.then_some(local_hash))
+        }.await;
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        compared
+    }
+
+    /// Pair up removals and creations from one batch of watcher events that are really
+    /// moves: a file that vanished and a new, untracked file with the same size and hash.
+    /// Matched paths are taken out of `pending`.
+    async fn match_moves(&self, pending: &mut HashMap<PathBuf, notify::EventKind>, pairs: &[SyncPair]) -> Vec<(PathBuf, PathBuf)> {
+        let mut moves = Vec::new();
+        for pair in pairs.iter().filter(|p| p.status == "active") {
+            let in_pair: Vec<PathBuf> = pending.keys().filter(|p| p.starts_with(&pair.local_path)).cloned().collect();
+            let mut gone = Vec::new();
+            let mut created = Vec::new();
+            for path in in_pair {
+                let Some(key) = relative_key(pair, &path) else { continue };
+                let state = self.get_file_state(pair.id, &key).await.ok().flatten();
+                match (path.is_file(), state) {
+                    (false, Some(state)) if !path.exists() && !state.is_dir && state.hash.is_some() => gone.push((path, state)),
+                    (true, None) => created.push(path),
+                    _ => {}
+                }
+            }
+            if gone.is_empty() || created.is_empty() {
+                continue;
+            }
+
+            let Ok(Some(creds)) = self.get_valid_credentials(&pair.account_id).await else { continue };
+            let Ok(provider) = self.make_provider(&creds) else { continue };
+            for path in created {
+                let Ok(meta) = tokio::fs::metadata(&path).await else { continue };
+                let candidates: Vec<usize> = gone.iter().enumerate()
+                    .filter(|(_, (_, s))| s.size == Some(meta.len() as i64))
+                    .map(|(i, _)| i)
+                    .collect();
+                if candidates.is_empty() {
+                    continue;
+                }
+                let Ok(hash) = self.compute_local_hash(&path, provider.as_ref()).await else { continue };
+                if let Some(i) = candidates.into_iter().find(|&i| gone[i].1.hash.as_deref() == Some(hash.as_str())) {
+                    let (from, _) = gone.swap_remove(i);
+                    pending.remove(&from);
+                    pending.remove(&path);
+                    moves.push((from, path));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Apply a remote rename or move to the local copy. Left to the regular reconcile
+    /// when the source is gone locally or the destination is taken.
+    async fn move_local_entry<F>(&self, pair: &SyncPair, old_key: &str, new_key: &str, remote_id: &str, on_status: Arc<F>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        let root = Path::new(&pair.local_path);
+        let (from, to) = (root.join(old_key), root.join(new_key));
+        if !from.exists() || to.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(&from, &to).await?;
+        self.rekey_file_states(pair.id, old_key, new_key, remote_id, remote_id).await?;
+        {
+            let mut cache = self.sync_cache.lock().await;
+            cache.insert(from.clone(), Instant::now());
+            cache.insert(to.clone(), Instant::now());
+        }
+
+        println!("Moved {:?} -> {:?} to follow the cloud", from, to);
+        on_status(SyncStatus::Moved {
+            pair_id: pair.id,
+            from: from.to_string_lossy().to_string(),
+            path: to.to_string_lossy().to_string(),
+        });
+        Ok(())
+    }
+
+    /// Remote id of the folder at `dir_key` as of the last sync ("" is the pair root)
+    async fn remote_dir_id(&self, pair: &SyncPair, dir_key: &str) -> Result<Option<String>> {
+        if dir_key.is_empty() {
+            return Ok(Some(pair.remote_path.clone()));
+        }
+        Ok(self.get_file_state(pair.id, dir_key).await?.and_then(|s| s.remote_id))
+    }
+
+    /// Apply a local rename or move to the remote copy. Falls back to deleting and
+    /// re-uploading when the entry was never synced or the provider cannot move.
+    pub async fn move_remote_entry<F>(&self, from: &Path, to: &Path, pair: &SyncPair, on_status: Arc<F>, cancel: Option<Arc<std::sync::atomic::AtomicBool>>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        if let Some(c) = &cancel {
+            if c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
+        }
+        let from_key = relative_key(pair, from).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+        let to_key = relative_key(pair, to).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+
+        let base = self.get_file_state(pair.id, &from_key).await?;
+        if base.is_none() && self.get_file_state(pair.id, &to_key).await?.is_some() {
+            // The watcher seeing a move this engine made to follow the cloud
+            return Ok(());
+        }
+        let from_parent = self.remote_dir_id(pair, split_key(&from_key).0).await?;
+        let moved = match (base.as_ref().and_then(|b| b.remote_id.as_deref()), from_parent) {
+            (Some(remote_id), Some(from_parent)) => {
+                let parent = to.parent().ok_or_else(|| anyhow::anyhow!("No parent"))?;
+                let to_parent = self.ensure_remote_path_exists(provider.as_ref(), pair, parent).await?;
+                let new_name = split_key(&to_key).1;
+                let result = if from_parent == to_parent {
+                    provider.rename_file(remote_id, &from_parent, new_name).await
+                } else {
+                    provider.move_file(remote_id, &from_parent, &to_parent, new_name).await
+                };
+                match result {
+                    Ok(moved) => moved.map(|remote| (remote_id.to_string(), remote)),
+                    Err(CloudError::Unauthenticated) => {
+                        let _ = self.disconnect_account(&pair.account_id).await;
+                        on_status(SyncStatus::AuthExpired { account_id: pair.account_id.clone() });
+                        return Err(CloudError::Unauthenticated.into());
+                    }
+                    Err(e) => {
+                        eprintln!("Move failed for {:?}, re-uploading instead: {:?}", from, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let Some((old_remote_id, remote)) = moved else {
+            self.delete_remote_file(from, pair, on_status.clone(), cancel.clone()).await?;
+            if to.is_dir() {
+                return self.run_sync(pair, on_status, Some(vec![to_key])).await;
+            }
+            return self.sync_file(to, pair, on_status, cancel).await;
+        };
+
+        self.rekey_file_states(pair.id, &from_key, &to_key, &old_remote_id, &remote.id).await?;
+        if !remote.is_dir {
+            let hash = base.and_then(|b| b.hash);
+            self.record_file_state(pair.id, &to_key, to, hash.as_deref(), &remote).await?;
+        }
+        self.sync_cache.lock().await.insert(to.to_path_buf(), Instant::now());
+        on_status(SyncStatus::Moved {
+            pair_id: pair.id,
+            from: from.to_string_lossy().to_string(),
+            path: to.to_string_lossy().to_string(),
+        });
+        Ok(())
+    }
+
+    async fn ensure_remote_path_exists(&self, provider: &dyn CloudProvider, pair: &SyncPair, local_path: &Path) -> Result<String> {
+        let relative = local_path.strip_prefix(&pair.local_path)?;
+        let mut current_id = pair.remote_path.clone();
+        
+        for component in relative.components() {
+            let name = component.as_os_str().to_string_lossy();
+            if name == "." || name == "/" { continue; }
+
+            let entries = provider.list_files(&current_id).await?;
+            if let Some(folder) = entries.iter().find(|e| e.name == name && e.is_dir) {
+                current_id = folder.id.clone();
+            } else {
+                current_id = provider.create_folder(&name, &current_id).await?;
+            }
+        }
+        
+        Ok(current_id)
+    }
+
+    pub async fn sync_remote_to_local<F>(&self, remote: &RemoteFile, dest: &Path, pair: &SyncPair, on_status: Arc<F>, cancel: Option<Arc<std::sync::atomic::AtomicBool>>) -> Result<()>
+    where
+        F: Fn(SyncStatus) + Send + Sync + 'static,
+    {
+        if let Some(c) = &cancel {
+            if c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
+        }
+        if !pair.sync_mode().downloads() {
+            return Ok(());
+        }
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+
+        let path_str = dest.to_string_lossy().to_string();
+        let pair_id = pair.id;
+        on_status(SyncStatus::Downloading { pair_id, path: path_str.clone() });
+
+        // Download into the staging folder first. The version about to be replaced is only
+        // copied to the trash once the new one is complete and verified, and a failed
+        // download leaves both the local file and the trash untouched.
+        let staging = Path::new(&pair.local_path)
+            .join(crate::download::STAGING_DIR)
+            .join(format!("{:016x}", thread_rng().gen::<u64>()));
+        let placed: Result<()> = async {
+            let name = dest.file_name().ok_or_else(|| anyhow::anyhow!("Invalid filename: {:?}", dest))?;
+            let staged = staging.join(name);
+            tokio::fs::create_dir_all(&staging).await?;
+            provider.download_remote(remote, &staged).await?;
+            if dest.is_file() {
+                self.trash_local_entry(pair, dest, TrashReason::Overwritten).await?;
+            }
+            crate::trash::move_entry(&staged, dest).await?;
+            Ok(())
+        }.await;
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+
+        if let Err(e) = placed {
+            eprintln!("Download error: {:?}", e);
             on_status(SyncStatus::Error { pair_id, path: path_str.clone(), message: e.to_string() });
-            return Err(e.into());
+            return Err(e);
         } else {
             println!("Successfully downloaded -> {:?}", dest);
-            
+
+            if let Some(key) = relative_key(pair, dest) {
+                let local_hash = self.compute_local_hash(dest, provider.as_ref()).await?;
+                self.record_file_state(pair.id, &key, dest, Some(&local_hash), remote).await?;
+            }
+
             {
                 let mut cache = self.sync_cache.lock().await;
                 cache.insert(dest.to_path_buf(), Instant::now());
@@ -618,14 +1516,7 @@ impl SyncEngine {
     }
 
     pub async fn remove_sync_pair(&self, id: i64) -> Result<()> {
-        let pair: Option<SyncPair> = sqlx::query_as::<_, SyncPair>(
-            "SELECT id, local_path, remote_path, remote_name, provider_id, account_id, status, created_at, last_sync_at FROM sync_pairs WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        if let Some(pair) = pair {
+        if let Some(pair) = self.get_sync_pair(id).await? {
             let mut watcher = self.watcher.lock().await;
             // Check if any other pair still uses this path before unwatching
             let pairs = self.get_sync_pairs().await.unwrap_or_default();
@@ -647,24 +1538,572 @@ impl SyncEngine {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("DELETE FROM files WHERE pair_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("DELETE FROM conflicts WHERE pair_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        // Trashed files stay on disk under the local folder, and in the provider's trash
+        sqlx::query("DELETE FROM trash_items WHERE pair_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("DELETE FROM remote_trash_items WHERE pair_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
         Ok(())
     }
 
     pub async fn get_sync_pairs(&self) -> Result<Vec<SyncPair>> {
-        let pairs = sqlx::query_as::<_, SyncPair>(
+        let pairs = sqlx::query_as::<_, SyncPair>(&format!("SELECT {} FROM sync_pairs", PAIR_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(pairs)
+    }
+
+    pub async fn get_sync_pair(&self, id: i64) -> Result<Option<SyncPair>> {
+        Ok(sqlx::query_as::<_, SyncPair>(&format!("SELECT {} FROM sync_pairs WHERE id = ?", PAIR_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    /// Replace a pair's ignore rules (gitignore syntax, one pattern per line).
+    /// They apply from the next sync pass; entries already synced stay where they are.
+    pub async fn set_ignore_rules(&self, pair_id: i64, rules: &str) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET ignore_rules = ? WHERE id = ?")
+            .bind(rules)
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        Ok(())
+    }
+
+    /// The remote folders directly inside `parent_key` of a pair, with their selective sync
+    /// state. `parent_id` is that folder's remote id from an earlier listing; it can only be
+    /// left out for the root and for folders that have been synced.
+    pub async fn list_folder_tree(&self, pair_id: i64, parent_key: &str, parent_id: Option<&str>) -> Result<Vec<FolderNode>> {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let parent_key = parent_key.trim_matches('/');
+        let parent_id = match parent_id {
+            Some(id) => id.to_string(),
+            None => self.remote_dir_id(&pair, parent_key).await?
+                .ok_or_else(|| anyhow::anyhow!("Folder {:?} has not been synced, pass its remote id", parent_key))?,
+        };
+
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+        let excluded: Vec<&str> = pair.excluded_keys().collect();
+
+        let mut folders: Vec<FolderNode> = provider.list_files(&parent_id).await?
+            .into_iter()
+            .filter(|f| f.is_dir)
+            .map(|f| {
+                let path = join_key(parent_key, &f.name);
+                let state = if excluded.iter().any(|e| key_within(&path, e)) {
+                    FolderCheckState::Excluded
+                } else if excluded.iter().any(|e| key_within(e, &path)) {
+                    FolderCheckState::Partial
+                } else {
+                    FolderCheckState::Included
+                };
+                FolderNode { id: f.id, path, name: f.name, state }
+            })
+            .collect();
+        folders.sort_by_key(|f| f.name.to_lowercase());
+        Ok(folders)
+    }
+
+    /// Include a remote folder of a pair in syncing or leave it out (selective sync).
+    /// A folder left out is neither downloaded nor deleted remotely; its local copy, if any,
+    /// stays as it is. Including it again merges both sides on the next sync. Folders
+    /// inside an excluded one can't be included on their own.
+    pub async fn set_folder_excluded(&self, pair_id: i64, key: &str, excluded: bool) -> Result<()> {
+        let pair = self.get_sync_pair(pair_id).await?.ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let key = key.trim_matches('/');
+        if key.is_empty() {
+            anyhow::bail!("The root of a sync pair can't be excluded");
+        }
+
+        // Anything inside the folder is covered by whatever is decided for the folder
+        let mut keys: Vec<String> = pair.excluded_keys().filter(|k| !key_within(k, key)).map(|k| k.to_string()).collect();
+        if excluded {
+            if !keys.iter().any(|k| key_within(key, k)) {
+                keys.push(key.to_string());
+            }
+            // Right away, so removing the local copy before the next sync isn't sent to the cloud
+            self.forget_file_state(pair_id, key).await?;
+        } else if let Some(parent) = keys.iter().find(|k| key_within(key, k)) {
+            anyhow::bail!("{:?} is inside the excluded folder {:?}", key, parent);
+        }
+        keys.sort();
+
+        sqlx::query("UPDATE sync_pairs SET excluded_folders = ? WHERE id = ?")
+            .bind(keys.join("\n"))
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Pause or resume a pair. Paused pairs are neither watched for changes nor polled.
+    pub async fn set_pair_active(&self, pair_id: i64, active: bool) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET status = ? WHERE id = ?")
+            .bind(if active { "active" } else { "paused" })
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        if let (true, Some(pair)) = (active, self.get_sync_pair(pair_id).await?) {
+            // Paused pairs are not watched after a restart
+            self.watcher.lock().await.watch(Path::new(&pair.local_path))?;
+        }
+        Ok(())
+    }
+
+    /// Set the mass-deletion limits of a pair: a file count and a percentage of its
+    /// synced files, 0 turning either off
+    pub async fn set_delete_limits(&self, pair_id: i64, limit: i64, limit_percent: i64) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET delete_limit = ?, delete_limit_percent = ? WHERE id = ?")
+            .bind(limit.max(0))
+            .bind(limit_percent.clamp(0, 100))
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        Ok(())
+    }
+
+    /// Set how many days displaced local files are kept in a pair's trash, 0 keeping them forever
+    pub async fn set_trash_retention(&self, pair_id: i64, days: i64) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET trash_retention_days = ? WHERE id = ?")
+            .bind(days.max(0))
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        Ok(())
+    }
+
+    /// Choose whether a pair deletes on the cloud for good or moves entries to the
+    /// provider's trash (the default)
+    pub async fn set_permanent_delete(&self, pair_id: i64, permanent: bool) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET permanent_delete = ? WHERE id = ?")
+            .bind(permanent)
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        Ok(())
+    }
+
+    /// Change which way a pair syncs. Takes effect from the next sync pass.
+    pub async fn set_sync_mode(&self, pair_id: i64, mode: SyncMode) -> Result<()> {
+        let result = sqlx::query("UPDATE sync_pairs SET mode = ? WHERE id = ?")
+            .bind(mode.as_str())
+            .bind(pair_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            anyhow::bail!("No sync pair with id {}", pair_id);
+        }
+        Ok(())
+    }
+
+    // ---- File State Index ----
+
+    /// Base states recorded at the last sync for every entry of a pair
+    pub async fn get_file_states(&self, pair_id: i64) -> Result<Vec<FileState>> {
+        let states = sqlx::query_as::<_, FileState>(
             r#"
-            SELECT id, local_path, remote_path, remote_name, provider_id, account_id, status, created_at, last_sync_at
-            FROM sync_pairs
+            SELECT pair_id, path, is_dir, hash, size, modified_at, remote_id, remote_hash, remote_revision, remote_modified_at, status, synced_at
+            FROM files
+            WHERE pair_id = ?
+            ORDER BY path
             "#
         )
+        .bind(pair_id)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(pairs)
+        Ok(states)
+    }
+
+    async fn get_file_state(&self, pair_id: i64, key: &str) -> Result<Option<FileState>> {
+        let state = sqlx::query_as::<_, FileState>(
+            r#"
+            SELECT pair_id, path, is_dir, hash, size, modified_at, remote_id, remote_hash, remote_revision, remote_modified_at, status, synced_at
+            FROM files
+            WHERE pair_id = ? AND path = ?
+            "#
+        )
+        .bind(pair_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(state)
+    }
+
+    /// Record `local_path` and `remote` as the synced base version of `key`
+    async fn record_file_state(&self, pair_id: i64, key: &str, local_path: &Path, local_hash: Option<&str>, remote: &RemoteFile) -> Result<()> {
+        let meta = tokio::fs::metadata(local_path).await?;
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+
+        sqlx::query(
+            r#"
+            INSERT INTO files (pair_id, path, is_dir, hash, size, modified_at, remote_id, remote_hash, remote_revision, remote_modified_at, status, synced_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'synced', ?)
+            ON CONFLICT (pair_id, path) DO UPDATE SET
+                is_dir = excluded.is_dir,
+                hash = excluded.hash,
+                size = excluded.size,
+                modified_at = excluded.modified_at,
+                remote_id = excluded.remote_id,
+                remote_hash = excluded.remote_hash,
+                remote_revision = excluded.remote_revision,
+                remote_modified_at = excluded.remote_modified_at,
+                status = excluded.status,
+                synced_at = excluded.synced_at
+            "#
+        )
+        .bind(pair_id)
+        .bind(key)
+        .bind(meta.is_dir())
+        .bind(local_hash)
+        .bind(if meta.is_dir() { None } else { Some(meta.len() as i64) })
+        .bind(mtime_secs(&meta))
+        .bind(&remote.id)
+        .bind(&remote.hash)
+        .bind(&remote.revision)
+        .bind(remote.modified_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Files in the index at `key` or, for directories, below it
+    async fn count_files_under(&self, pair_id: i64, key: &str) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE pair_id = ? AND NOT is_dir AND (path = ? OR substr(path, 1, length(?) + 1) = ? || '/')")
+            .bind(pair_id)
+            .bind(key)
+            .bind(key)
+            .bind(key)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Drop the base record of `key` and, for directories, everything below it
+    async fn forget_file_state(&self, pair_id: i64, key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM files WHERE pair_id = ? AND (path = ? OR substr(path, 1, length(?) + 1) = ? || '/')")
+            .bind(pair_id)
+            .bind(key)
+            .bind(key)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Move the base records of `from_key` (and everything below it) to `to_key`.
+    /// Path-addressed providers change the ids of everything inside a moved folder,
+    /// so remote ids under the old id are rewritten the same way.
+    async fn rekey_file_states(&self, pair_id: i64, from_key: &str, to_key: &str, old_remote_id: &str, new_remote_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM files WHERE pair_id = ? AND (path = ? OR substr(path, 1, length(?) + 1) = ? || '/')")
+            .bind(pair_id)
+            .bind(to_key)
+            .bind(to_key)
+            .bind(to_key)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE files SET
+                path = ? || substr(path, length(?) + 1),
+                remote_id = CASE
+                    WHEN remote_id = ? THEN ?
+                    WHEN substr(remote_id, 1, length(?) + 1) = ? || '/' THEN ? || substr(remote_id, length(?) + 1)
+                    ELSE remote_id
+                END
+            WHERE pair_id = ? AND (path = ? OR substr(path, 1, length(?) + 1) = ? || '/')
+            "#
+        )
+        .bind(to_key)
+        .bind(from_key)
+        .bind(old_remote_id)
+        .bind(new_remote_id)
+        .bind(old_remote_id)
+        .bind(old_remote_id)
+        .bind(new_remote_id)
+        .bind(old_remote_id)
+        .bind(pair_id)
+        .bind(from_key)
+        .bind(from_key)
+        .bind(from_key)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // ---- Conflicts ----
+
+    /// Unresolved conflicts, optionally restricted to one pair
+    pub async fn list_conflicts(&self, pair_id: Option<i64>) -> Result<Vec<Conflict>> {
+        let conflicts = sqlx::query_as::<_, Conflict>(
+            r#"
+            SELECT id, pair_id, path, conflict_path, detected_at, resolved_at, resolution
+            FROM conflicts
+            WHERE resolved_at IS NULL AND (? IS NULL OR pair_id = ?)
+            ORDER BY detected_at DESC
+            "#
+        )
+        .bind(pair_id)
+        .bind(pair_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(conflicts)
+    }
+
+    /// Settle a conflict locally; the next sync pass propagates the result to the cloud.
+    pub async fn resolve_conflict(&self, id: i64, resolution: ConflictResolution) -> Result<()> {
+        let conflict = sqlx::query_as::<_, Conflict>(
+            "SELECT id, pair_id, path, conflict_path, detected_at, resolved_at, resolution FROM conflicts WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Conflict not found"))?;
+
+        let pairs = self.get_sync_pairs().await?;
+        let pair = pairs.iter().find(|p| p.id == conflict.pair_id)
+            .ok_or_else(|| anyhow::anyhow!("Sync pair not found"))?;
+        let original = Path::new(&pair.local_path).join(&conflict.path);
+        let copy = Path::new(&pair.local_path).join(&conflict.conflict_path);
+
+        match resolution {
+            ConflictResolution::KeepLocal => {
+                if copy.exists() {
+                    tokio::fs::rename(&copy, &original).await?;
+                }
+            }
+            ConflictResolution::KeepRemote => {
+                if copy.exists() {
+                    tokio::fs::remove_file(&copy).await?;
+                }
+            }
+            ConflictResolution::KeepBoth => {}
+        }
+
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        sqlx::query("UPDATE conflicts SET resolved_at = ?, resolution = ? WHERE id = ?")
+            .bind(now)
+            .bind(resolution.as_str())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ---- Local Trash ----
+
+    /// Keep a local entry the engine is about to delete or overwrite in the pair's trash.
+    /// Files about to be overwritten are copied, as downloads replace them in place.
+    async fn trash_local_entry(&self, pair: &SyncPair, path: &Path, reason: TrashReason) -> Result<i64> {
+        let key = relative_key(pair, path).filter(|k| !k.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("{:?} is not inside sync pair {}", path, pair.id))?;
+        let meta = tokio::fs::symlink_metadata(path).await?;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO trash_items (pair_id, path, is_dir, size, reason) VALUES (?, ?, ?, ?, ?) RETURNING id"
+        )
+        .bind(pair.id)
+        .bind(&key)
+        .bind(meta.is_dir())
+        .bind((!meta.is_dir()).then_some(meta.len() as i64))
+        .bind(reason.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let stored = crate::trash::item_path(Path::new(&pair.local_path), id, split_key(&key).1);
+        let moved = match reason {
+            TrashReason::Overwritten => crate::trash::copy_entry(path, &stored).await,
+            TrashReason::Deleted | TrashReason::Replaced => crate::trash::move_entry(path, &stored).await,
+        };
+        if let Err(e) = moved {
+            sqlx::query("DELETE FROM trash_items WHERE id = ?").bind(id).execute(&self.pool).await?;
+            return Err(anyhow::Error::new(e).context(format!("Failed to move {:?} to the trash", path)));
+        }
+        Ok(id)
+    }
+
+    /// Entries in the local trash, newest first, for one pair or all of them
+    pub async fn list_trash(&self, pair_id: Option<i64>) -> Result<Vec<TrashItem>> {
+        let items = sqlx::query_as::<_, TrashItem>(&format!(
+            "SELECT {} FROM trash_items WHERE (? IS NULL OR pair_id = ?) ORDER BY trashed_at DESC, id DESC",
+            TRASH_COLUMNS
+        ))
+        .bind(pair_id)
+        .bind(pair_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(items)
+    }
+
+    /// A trash item and the pair it belongs to
+    async fn trash_item_with_pair(&self, id: i64) -> Result<(TrashItem, SyncPair)> {
+        let item = sqlx::query_as::<_, TrashItem>(&format!("SELECT {} FROM trash_items WHERE id = ?", TRASH_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No trash item with id {}", id))?;
+        let pair = self.get_sync_pair(item.pair_id).await?
+            .ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", item.pair_id))?;
+        Ok((item, pair))
+    }
+
+    /// Put a trashed entry back at its original location. Whatever is there now goes to
+    /// the trash in its place, so restoring an older version can be undone the same way.
+    /// The next sync pass uploads the restored entry.
+    pub async fn restore_trash_item(&self, id: i64) -> Result<()> {
+        let (item, pair) = self.trash_item_with_pair(id).await?;
+        let root = Path::new(&pair.local_path);
+        let stored = crate::trash::item_path(root, id, split_key(&item.path).1);
+        if tokio::fs::symlink_metadata(&stored).await.is_err() {
+            anyhow::bail!("Trash item {} is missing from {:?}", id, stored);
+        }
+
+        let original = root.join(&item.path);
+        if tokio::fs::symlink_metadata(&original).await.is_ok() {
+            self.trash_local_entry(&pair, &original, TrashReason::Replaced).await?;
+        }
+        crate::trash::move_entry(&stored, &original).await?;
+        self.delete_trash_item(id).await
+    }
+
+    /// Permanently delete a trash item
+    pub async fn delete_trash_item(&self, id: i64) -> Result<()> {
+        let (_, pair) = self.trash_item_with_pair(id).await?;
+        crate::trash::remove_item(Path::new(&pair.local_path), id).await?;
+        sqlx::query("DELETE FROM trash_items WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Permanently delete the trash items older than their pair's retention period.
+    /// Returns how many were deleted.
+    pub async fn purge_trash(&self) -> Result<usize> {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let expired: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT t.id FROM trash_items t JOIN sync_pairs p ON p.id = t.pair_id
+            WHERE p.trash_retention_days > 0 AND t.trashed_at <= ? - p.trash_retention_days * 86400
+            "#
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        for id in &expired {
+            self.delete_trash_item(*id).await?;
+        }
+        Ok(expired.len())
+    }
+
+    // ---- Remote Trash ----
+
+    /// Remove an entry on the cloud: into the provider's trash, unless the pair deletes
+    /// for good or the provider has no trash
+    async fn remove_remote_entry(&self, provider: &dyn CloudProvider, pair: &SyncPair, key: &str, name: &str, parent_id: &str) -> crate::provider::CloudResult<()> {
+        if pair.permanent_delete || !provider.supports_trash() {
+            return provider.delete_file(name, parent_id).await;
+        }
+        if let Some(trashed) = provider.trash_file(name, parent_id).await? {
+            sqlx::query("INSERT INTO remote_trash_items (pair_id, path, trash_id, is_dir) VALUES (?, ?, ?, ?)")
+                .bind(pair.id)
+                .bind(key)
+                .bind(&trashed.id)
+                .bind(trashed.is_dir)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| CloudError::Other(e.into()))?;
+        }
+        Ok(())
+    }
+
+    /// Entries a pair moved to the provider's trash that are still there, newest first.
+    /// Entries restored or purged outside the app are forgotten.
+    pub async fn list_remote_trash(&self, pair_id: i64) -> Result<Vec<RemoteTrashItem>> {
+        let pair = self.get_sync_pair(pair_id).await?
+            .ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", pair_id))?;
+        let items = sqlx::query_as::<_, RemoteTrashItem>(&format!(
+            "SELECT {} FROM remote_trash_items WHERE pair_id = ? ORDER BY trashed_at DESC, id DESC",
+            REMOTE_TRASH_COLUMNS
+        ))
+        .bind(pair_id)
+        .fetch_all(&self.pool)
+        .await?;
+        if items.is_empty() {
+            return Ok(items);
+        }
+
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+        let trashed: HashSet<String> = provider.list_trashed().await?.into_iter().map(|t| t.id).collect();
+        let (present, gone): (Vec<_>, Vec<_>) = items.into_iter().partition(|i| trashed.contains(&i.trash_id));
+        for item in gone {
+            sqlx::query("DELETE FROM remote_trash_items WHERE id = ?").bind(item.id).execute(&self.pool).await?;
+        }
+        Ok(present)
+    }
+
+    /// Move an entry back out of the provider's trash. The next sync pass downloads it.
+    pub async fn restore_remote_trash_item(&self, id: i64) -> Result<()> {
+        let item = sqlx::query_as::<_, RemoteTrashItem>(&format!("SELECT {} FROM remote_trash_items WHERE id = ?", REMOTE_TRASH_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No remote trash item with id {}", id))?;
+        let pair = self.get_sync_pair(item.pair_id).await?
+            .ok_or_else(|| anyhow::anyhow!("No sync pair with id {}", item.pair_id))?;
+        let creds = self.get_valid_credentials(&pair.account_id).await?.ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let provider = self.make_provider(&creds)?;
+
+        provider.restore_file(&item.trash_id).await?;
+        sqlx::query("DELETE FROM remote_trash_items WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     // ---- Credential Operations (multi-account) ----
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn set_credentials(
         &self, 
         account_id: &str,
@@ -677,9 +2116,10 @@ impl SyncEngine {
         user_avatar: Option<String>,
     ) -> Result<()> {
         let expires_at = expires_in.map(|secs| (std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64) + secs);
+        let (access_token, refresh_token, in_store) = self.stash_secrets(account_id, access_token, refresh_token)?;
         
         sqlx::query(
-            "INSERT OR REPLACE INTO credentials (account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT OR REPLACE INTO credentials (account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar, secret_in_store) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(account_id)
         .bind(provider_id)
@@ -689,18 +2129,114 @@ impl SyncEngine {
         .bind(user_name)
         .bind(user_email)
         .bind(user_avatar)
+        .bind(in_store)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    /// Connect an account that is configured with a secret and connection settings
+    /// rather than OAuth (e.g. S3). The connection is checked before it is stored.
+    /// Returns the new account_id.
+    pub async fn connect_account(&self, provider_id: &str, config: serde_json::Value, secret: &str) -> Result<String> {
+        let form = self.registry.read().unwrap_or_else(|e| e.into_inner()).form(provider_id)
+            .ok_or_else(|| anyhow::anyhow!("Provider {} does not use a connection form", provider_id))?;
+        let ConnectedAccount { account_id, user_name, user_email, config } = form.connect(config).await?;
+        if provider_type(&account_id) != provider_id {
+            anyhow::bail!("Account {} does not belong to provider {}", account_id, provider_id);
+        }
+
+        let creds = crate::models::Credentials {
+            account_id: account_id.clone(),
+            provider_id: provider_id.to_string(),
+            access_token: secret.to_string(),
+            refresh_token: None,
+            expires_at: None,
+            user_name: Some(user_name),
+            user_email: Some(user_email),
+            user_avatar: None,
+            config: Some(config.to_string()),
+        };
+        let provider = self.make_provider(&creds)?;
+        provider.list_files("root").await
+            .map_err(|e| anyhow::anyhow!("Could not connect: {}", e))?;
+
+        let (secret, _, in_store) = self.stash_secrets(&creds.account_id, &creds.access_token, None)?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO credentials (account_id, provider_id, access_token, user_name, user_email, config, secret_in_store) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&creds.account_id)
+        .bind(&creds.provider_id)
+        .bind(secret)
+        .bind(&creds.user_name)
+        .bind(&creds.user_email)
+        .bind(&creds.config)
+        .bind(in_store)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(account_id)
+    }
+
     pub async fn get_credentials(&self, account_id: &str) -> Result<Option<crate::models::Credentials>> {
-        let creds: Option<crate::models::Credentials> = sqlx::query_as(
-            "SELECT account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar FROM credentials WHERE account_id = ?"
+        let row = sqlx::query(
+            &format!("SELECT {}, secret_in_store FROM credentials WHERE account_id = ?", CREDENTIAL_COLUMNS)
         )
         .bind(account_id)
         .fetch_optional(&self.pool)
         .await?;
+        row.map(|row| self.credentials_from_row(&row)).transpose()
+    }
+
+    /// Keep account tokens and secrets in `store` instead of the database from now on,
+    /// moving the ones already in the database there. Returns how many accounts moved.
+    pub async fn use_credential_store(&self, store: Arc<dyn CredentialStore>) -> Result<usize> {
+        *self.credential_store.write().unwrap_or_else(|e| e.into_inner()) = Some(store.clone());
+
+        let rows: Vec<(String, String, Option<String>)> = sqlx::query_as(
+            "SELECT account_id, access_token, refresh_token FROM credentials WHERE secret_in_store = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for (account_id, access_token, refresh_token) in &rows {
+            let secrets = StoredSecrets { access_token: access_token.clone(), refresh_token: refresh_token.clone() };
+            store.set(account_id, &serde_json::to_string(&secrets)?)?;
+            sqlx::query("UPDATE credentials SET access_token = '', refresh_token = NULL, secret_in_store = 1 WHERE account_id = ?")
+                .bind(account_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(rows.len())
+    }
+
+    fn credential_store(&self) -> Option<Arc<dyn CredentialStore>> {
+        self.credential_store.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Hand the tokens of an account to the credential store, if one is set. Returns what
+    /// the database row gets instead: (access_token, refresh_token, secret_in_store).
+    fn stash_secrets<'a>(&self, account_id: &str, access_token: &'a str, refresh_token: Option<&'a str>) -> Result<(&'a str, Option<&'a str>, bool)> {
+        let Some(store) = self.credential_store() else {
+            return Ok((access_token, refresh_token, false));
+        };
+        let secrets = StoredSecrets { access_token: access_token.to_string(), refresh_token: refresh_token.map(|t| t.to_string()) };
+        store.set(account_id, &serde_json::to_string(&secrets)?)?;
+        Ok(("", None, true))
+    }
+
+    /// A credentials row with its tokens filled in from the credential store where they are kept
+    fn credentials_from_row(&self, row: &SqliteRow) -> Result<crate::models::Credentials> {
+        let mut creds = crate::models::Credentials::from_row(row)?;
+        if !row.try_get::<bool, _>("secret_in_store")? {
+            return Ok(creds);
+        }
+        let store = self.credential_store()
+            .ok_or_else(|| anyhow::anyhow!("The tokens of {} are in the OS keyring, which is not available", creds.account_id))?;
+        let stored = store.get(&creds.account_id)?
+            .ok_or_else(|| anyhow::anyhow!("No stored tokens for {}; connect the account again", creds.account_id))?;
+        let secrets: StoredSecrets = serde_json::from_str(&stored)?;
+        creds.access_token = secrets.access_token;
+        creds.refresh_token = secrets.refresh_token;
         Ok(creds)
     }
 
@@ -710,46 +2246,45 @@ impl SyncEngine {
             let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
             
             // If expires within 10 minutes, refresh proactively
-            if let Some(expires_at) = creds.expires_at {
+            let ptype = provider_type(account_id).to_string();
+            if let (Some(expires_at), Some(refresh_token), Some(auth)) = (creds.expires_at, creds.refresh_token.clone(), self.auth_provider(&ptype)) {
                 if expires_at - now < 600 {
-                    let ptype = provider_type(account_id);
-                    if ptype == "gdrive" {
-                        if let Some(refresh_token) = &creds.refresh_token {
-                            println!("Refreshing Google token for {} (expires in {}s)...", account_id, expires_at - now);
-                            match self.refresh_google_token(refresh_token).await {
-                                Ok((new_access, new_expires)) => {
-                                    let mut user_name = creds.user_name.clone();
-                                    let mut user_email = creds.user_email.clone();
-                                    let mut user_avatar = creds.user_avatar.clone();
-                                    
-                                    if user_name.is_none() {
-                                        if let Ok((n, e, a)) = self.fetch_google_user_info(&new_access).await {
-                                            user_name = n;
-                                            user_email = e;
-                                            user_avatar = a;
-                                        }
-                                    }
-
-                                    self.set_credentials(account_id, "gdrive", &new_access, Some(refresh_token), new_expires, user_name.clone(), user_email.clone(), user_avatar.clone()).await?;
-                                    creds.access_token = new_access;
-                                    creds.expires_at = new_expires.map(|secs| now + secs);
-                                    creds.user_name = user_name;
-                                    creds.user_email = user_email;
-                                    creds.user_avatar = user_avatar;
-                                    return Ok(Some(creds));
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to refresh token for {}: {}", account_id, e);
-                                    if expires_at > now {
-                                        eprintln!("Token still valid for {}s, using current token", expires_at - now);
-                                        return Ok(Some(creds));
-                                    }
-                                    // Token fully expired — disconnect
-                                    eprintln!("Token fully expired and refresh failed, disconnecting account: {}", account_id);
-                                    let _ = self.disconnect_account(account_id).await;
-                                    return Ok(None);
+                    println!("Refreshing {} token for {} (expires in {}s)...", ptype, account_id, expires_at - now);
+                    match auth.refresh(&refresh_token).await {
+                        Ok(tokens) => {
+                            // Microsoft rotates refresh tokens; Google keeps the original one
+                            let refresh_token = tokens.refresh_token.unwrap_or(refresh_token);
+                            let mut user_name = creds.user_name.clone();
+                            let mut user_email = creds.user_email.clone();
+                            let mut user_avatar = creds.user_avatar.clone();
+                            
+                            if user_name.is_none() {
+                                if let Ok(user) = auth.user_info(&tokens.access_token).await {
+                                    user_name = user.name;
+                                    user_email = user.email;
+                                    user_avatar = user.avatar;
                                 }
                             }
+
+                            self.set_credentials(account_id, &ptype, &tokens.access_token, Some(&refresh_token), tokens.expires_in, user_name.clone(), user_email.clone(), user_avatar.clone()).await?;
+                            creds.access_token = tokens.access_token;
+                            creds.refresh_token = Some(refresh_token);
+                            creds.expires_at = tokens.expires_in.map(|secs| now + secs);
+                            creds.user_name = user_name;
+                            creds.user_email = user_email;
+                            creds.user_avatar = user_avatar;
+                            return Ok(Some(creds));
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to refresh token for {}: {}", account_id, e);
+                            if expires_at > now {
+                                eprintln!("Token still valid for {}s, using current token", expires_at - now);
+                                return Ok(Some(creds));
+                            }
+                            // Token fully expired — disconnect
+                            eprintln!("Token fully expired and refresh failed, disconnecting account: {}", account_id);
+                            let _ = self.disconnect_account(account_id).await;
+                            return Ok(None);
                         }
                     }
                 }
@@ -759,81 +2294,72 @@ impl SyncEngine {
         Ok(None)
     }
 
-    async fn refresh_google_token(&self, refresh_token: &str) -> Result<(String, Option<i64>)> {
-        let client_id = crate::config::get_google_client_id();
-        let client_secret = crate::config::get_google_client_secret();
-        
-        let client = reqwest::Client::new();
-        let params = [
-            ("client_id", client_id.as_str()),
-            ("client_secret", client_secret.as_str()),
-            ("refresh_token", refresh_token),
-            ("grant_type", "refresh_token"),
-        ];
-
-        let response = client.post("https://oauth2.googleapis.com/token")
-            .form(&params)
-            .send()
-            .await?;
+    // ---- Account Management (multi-account) ----
 
-        if !response.status().is_success() {
-            let err = response.text().await?;
-            return Err(anyhow::anyhow!("Token refresh failed: {}", err));
+    /// Disconnect an account the user chose to remove, revoking its grant first where the
+    /// provider allows. A failed revocation is logged, not returned.
+    pub async fn sign_out(&self, account_id: &str) -> Result<()> {
+        if let Some(auth) = self.auth_provider(provider_type(account_id)) {
+            match self.get_credentials(account_id).await {
+                Ok(Some(creds)) => {
+                    if let Err(e) = auth.revoke(&creds.access_token, creds.refresh_token.as_deref()).await {
+                        eprintln!("Failed to revoke the tokens of {}: {}", account_id, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to revoke the tokens of {}: {}", account_id, e),
+            }
         }
-
-        let tokens: serde_json::Value = response.json().await?;
-        let access_token = tokens["access_token"].as_str()
-            .ok_or_else(|| anyhow::anyhow!("No access token returned"))?.to_string();
-        let expires_in = tokens["expires_in"].as_i64();
-        
-        Ok((access_token, expires_in))
+        self.disconnect_account(account_id).await
     }
 
-    async fn fetch_google_user_info(&self, access_token: &str) -> Result<(Option<String>, Option<String>, Option<String>)> {
-        let client = reqwest::Client::new();
-        let response = client.get("https://www.googleapis.com/drive/v3/about?fields=user")
-            .bearer_auth(access_token)
-            .send()
+    pub async fn disconnect_account(&self, account_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM credentials WHERE account_id = ?")
+            .bind(account_id)
+            .execute(&self.pool)
             .await?;
-
-        if !response.status().is_success() {
-            return Ok((None, None, None));
+        sqlx::query("DELETE FROM change_tokens WHERE account_id = ?")
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        if let Some(store) = self.credential_store() {
+            store.delete(account_id)?;
         }
-
-        let body: serde_json::Value = response.json().await?;
-        let user = &body["user"];
-        
-        let name = user["displayName"].as_str().map(|s| s.to_string());
-        let email = user["emailAddress"].as_str().map(|s| s.to_string());
-        let avatar = user["photoLink"].as_str().map(|s| s.to_string());
-        
-        Ok((name, email, avatar))
+        Ok(())
     }
 
-    // ---- Account Management (multi-account) ----
+    async fn get_change_cursor(&self, account_id: &str) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar("SELECT cursor FROM change_tokens WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
 
-    pub async fn disconnect_account(&self, account_id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM credentials WHERE account_id = ?")
+    async fn set_change_cursor(&self, account_id: &str, cursor: &str) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO change_tokens (account_id, cursor, updated_at) VALUES (?, ?, unixepoch())")
             .bind(account_id)
+            .bind(cursor)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    /// Get all connected accounts
+    /// Get all connected accounts. Tokens kept in the credential store are left
+    /// empty; `get_credentials` loads them.
     pub async fn get_all_accounts(&self) -> Result<Vec<crate::models::Credentials>> {
         let accounts = sqlx::query_as::<_, crate::models::Credentials>(
-            "SELECT account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar FROM credentials ORDER BY account_id"
+            &format!("SELECT {} FROM credentials ORDER BY account_id", CREDENTIAL_COLUMNS)
         )
         .fetch_all(&self.pool)
         .await?;
         Ok(accounts)
     }
 
-    /// Get all connected accounts for a specific provider
+    /// Get all connected accounts for a specific provider, without the tokens kept
+    /// in the credential store
     pub async fn get_accounts_for_provider(&self, provider_id: &str) -> Result<Vec<crate::models::Credentials>> {
         let accounts = sqlx::query_as::<_, crate::models::Credentials>(
-            "SELECT account_id, provider_id, access_token, refresh_token, expires_at, user_name, user_email, user_avatar FROM credentials WHERE provider_id = ? ORDER BY account_id"
+            &format!("SELECT {} FROM credentials WHERE provider_id = ? ORDER BY account_id", CREDENTIAL_COLUMNS)
         )
         .bind(provider_id)
         .fetch_all(&self.pool)
@@ -845,20 +2371,15 @@ impl SyncEngine {
         let creds = self.get_valid_credentials(account_id).await?
             .ok_or_else(|| anyhow::anyhow!("Account not connected"))?;
 
-        let ptype = provider_type(account_id);
-        if ptype == "gdrive" {
-            let provider = crate::providers::gdrive::GoogleDriveProvider::new(creds.access_token);
-            match provider.list_folders().await {
-                Ok(folders) => Ok(folders),
-                Err(CloudError::Unauthenticated) => {
-                    eprintln!("Authentication failed, disconnecting account: {}", account_id);
-                    let _ = self.disconnect_account(account_id).await;
-                    Err(CloudError::Unauthenticated.into())
-                }
-                Err(e) => Err(e.into()),
+        let provider = self.make_provider(&creds)?;
+        match provider.list_folders().await {
+            Ok(folders) => Ok(folders),
+            Err(CloudError::Unauthenticated) => {
+                eprintln!("Authentication failed, disconnecting account: {}", account_id);
+                let _ = self.disconnect_account(account_id).await;
+                Err(CloudError::Unauthenticated.into())
             }
-        } else {
-            Err(anyhow::anyhow!("Provider not supported yet"))
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -875,17 +2396,9 @@ impl SyncEngine {
         (verifier, challenge)
     }
 
-    /// Returns the account_id of the authenticated account (e.g., "gdrive:user@gmail.com")
-    pub async fn authenticate_google(&self, client_id: &str, client_secret: &str, code_verifier: String) -> Result<String> {
-        let redirect_uri = "http://127.0.0.1:4420";
-        let auth_url = format!(
-            "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&response_type=code&scope=https://www.googleapis.com/auth/drive&access_type=offline&prompt=consent",
-            client_id, redirect_uri
-        );
-
-        let listener = TcpListener::bind("127.0.0.1:4420").await?;
-        println!("Please visit this URL to authenticate: {}", auth_url);
-        
+    /// Wait for the browser to hit the loopback redirect and extract the `code` parameter.
+    /// The socket is returned so the caller can answer once the account is stored.
+    async fn receive_oauth_code(listener: &TcpListener) -> Result<(String, TcpStream)> {
         let (mut socket, _) = listener.accept().await?;
         
         let mut buffer = [0; 4096];
@@ -900,40 +2413,10 @@ impl SyncEngine {
             })
             .ok_or_else(|| anyhow::anyhow!("Failed to extract authorization code"))?;
 
-        let client = reqwest::Client::new();
-        let params = [
-            ("code", code.as_str()),
-            ("client_id", client_id),
-            ("client_secret", client_secret),
-            ("redirect_uri", redirect_uri),
-            ("grant_type", "authorization_code"),
-            ("code_verifier", &code_verifier),
-        ];
-
-        let response = client.post("https://oauth2.googleapis.com/token")
-            .form(&params)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let err = response.text().await?;
-            return Err(anyhow::anyhow!("Token exchange failed: {}", err));
-        }
-
-        let tokens: serde_json::Value = response.json().await?;
-        let access_token = tokens["access_token"].as_str()
-            .ok_or_else(|| anyhow::anyhow!("No access token returned"))?;
-        let refresh_token = tokens["refresh_token"].as_str();
-        let expires_in = tokens["expires_in"].as_i64();
-        
-        // Fetch user info to build account_id
-        let (user_name, user_email, user_avatar) = self.fetch_google_user_info(access_token).await.unwrap_or((None, None, None));
-        
-        let email = user_email.clone().unwrap_or_else(|| "unknown".to_string());
-        let account_id = format!("gdrive:{}", email);
-        
-        self.set_credentials(&account_id, "gdrive", access_token, refresh_token, expires_in, user_name, user_email, user_avatar).await?;
+        Ok((code, socket))
+    }
 
+    async fn send_oauth_success(mut socket: TcpStream) -> Result<()> {
         let response_body = "<html><body style='font-family:sans-serif;text-align:center;padding-top:50px;'><h1>✅ Authentication Successful!</h1><p>OmniSync is now connected. You can close this window now.</p></body></html>";
         let response_http = format!(
             "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n{}",
@@ -942,10 +2425,86 @@ impl SyncEngine {
         );
         socket.write_all(response_http.as_bytes()).await?;
         socket.flush().await?;
+        Ok(())
+    }
+
+    /// Browser login: wait for the authorization redirect on the loopback address, then
+    /// store the account. The caller opens `oauth_authorize_url` once this is listening.
+    /// Returns the account_id of the authenticated account (e.g., "gdrive:user@gmail.com")
+    pub async fn authenticate(&self, provider_id: &str, code_verifier: String) -> Result<String> {
+        let auth = self.oauth_provider(provider_id)?;
+        let listener = TcpListener::bind("127.0.0.1:4420").await?;
+        println!("Waiting for {} authorization on {}", provider_id, OAUTH_REDIRECT_URI);
+
+        let (code, socket) = Self::receive_oauth_code(&listener).await?;
+        let tokens = auth.exchange_code(&code, OAUTH_REDIRECT_URI, &code_verifier).await?;
+        let account_id = self.store_oauth_account(provider_id, auth.as_ref(), tokens).await?;
+
+        Self::send_oauth_success(socket).await?;
 
         Ok(account_id)
     }
 
+    /// Authorization URL of the PKCE code flow, redirecting to the loopback address that
+    /// `authenticate` listens on. A headless login with `complete_oauth` uses it too:
+    /// the browser can be on any machine, and the address it fails to load carries the code.
+    pub fn oauth_authorize_url(&self, provider_id: &str, code_challenge: &str) -> Result<String> {
+        self.oauth_provider(provider_id)?.authorize_url(OAUTH_REDIRECT_URI, code_challenge)
+    }
+
+    /// Finish a headless login started with `oauth_authorize_url`. `redirect` is the address
+    /// the browser ended on, or just its `code` parameter.
+    /// Returns the account_id of the authenticated account.
+    pub async fn complete_oauth(&self, provider_id: &str, redirect: &str, code_verifier: &str) -> Result<String> {
+        let auth = self.oauth_provider(provider_id)?;
+        let code = Self::parse_oauth_code(redirect)?;
+        let tokens = auth.exchange_code(&code, OAUTH_REDIRECT_URI, code_verifier).await?;
+        self.store_oauth_account(provider_id, auth.as_ref(), tokens).await
+    }
+
+    /// The authorization code in a pasted redirect address, or the pasted text itself
+    /// when it is a bare code. Errors when the provider redirected with an error instead.
+    pub fn parse_oauth_code(redirect: &str) -> Result<String> {
+        let redirect = redirect.trim();
+        if !redirect.contains("://") {
+            if redirect.is_empty() || redirect.contains(char::is_whitespace) {
+                anyhow::bail!("Expected the address the browser was redirected to, or its code");
+            }
+            return Ok(redirect.to_string());
+        }
+
+        let url = url::Url::parse(redirect).context("Not a valid address")?;
+        let param = |name: &str| url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned());
+        if let Some(code) = param("code") {
+            return Ok(code);
+        }
+        match param("error") {
+            Some(error) => anyhow::bail!("Authorization was not granted: {}", param("error_description").unwrap_or(error)),
+            None => anyhow::bail!("The address has no authorization code"),
+        }
+    }
+
+    fn oauth_provider(&self, provider_id: &str) -> Result<Arc<dyn AuthProvider>> {
+        self.auth_provider(provider_id)
+            .ok_or_else(|| anyhow::anyhow!("{} accounts do not sign in through a browser; use `accounts add`", provider_id))
+    }
+
+    /// Store a newly signed-in account with its refresh token, expiry and user info.
+    /// Returns the account_id.
+    async fn store_oauth_account(&self, provider_id: &str, auth: &dyn AuthProvider, tokens: TokenResponse) -> Result<String> {
+        // Fetch user info to build account_id
+        let user = auth.user_info(&tokens.access_token).await.unwrap_or_default();
+
+        let email = user.email.clone().unwrap_or_else(|| "unknown".to_string());
+        let account_id = format!("{}:{}", provider_id, email);
+
+        self.set_credentials(&account_id, provider_id, &tokens.access_token, tokens.refresh_token.as_deref(), tokens.expires_in, user.name, user.email, user.avatar).await?;
+
+        Ok(account_id)
+    }
+
+    /// Propagate a local removal the watcher saw. Only entries synced before are removed,
+    /// and a remote file edited since then is left for the next pass to download.
     pub async fn delete_remote_file<F>(&self, path: &Path, pair: &SyncPair, on_status: Arc<F>, cancel: Option<Arc<std::sync::atomic::AtomicBool>>) -> Result<()>
     where
         F: Fn(SyncStatus) + Send + Sync + 'static,
@@ -953,30 +2512,34 @@ impl SyncEngine {
         if let Some(c) = &cancel {
             if c.load(std::sync::atomic::Ordering::Relaxed) { return Ok(()); }
         }
+        let key = relative_key(pair, path).ok_or_else(|| anyhow::anyhow!("Path outside sync pair"))?;
+        let Some(base) = self.get_file_state(pair.id, &key).await? else {
+            // Never synced, so the remote copy is not ours to remove
+            return Ok(());
+        };
         let creds = self.get_valid_credentials(&pair.account_id).await.unwrap_or(None);
         if let Some(creds) = creds {
-            let provider = match make_provider(&pair.account_id, creds.access_token) {
-                Some(p) => p,
-                None => return Ok(()),
-            };
+            let provider = self.make_provider(&creds)?;
 
-            let filename = path.file_name()
-                .and_then(|n| n.to_str())
-                .ok_or_else(|| anyhow::anyhow!("Invalid filename"))?;
-
-            let parent = path.parent().ok_or_else(|| anyhow::anyhow!("No parent"))?;
-            let remote_parent_id = match self.ensure_remote_path_exists(provider.as_ref(), pair, parent).await {
-                Ok(id) => id,
-                Err(e) => {
-                    eprintln!("Failed to resolve remote parent for deletion {:?}: {:?}", path, e);
-                    return Err(e);
-                }
+            let (parent_key, filename) = split_key(&key);
+            let Some(remote_parent_id) = self.remote_dir_id(pair, parent_key).await? else {
+                return Ok(());
+            };
+            let listed = provider.list_files(&remote_parent_id).await?;
+            let Some(remote) = listed.iter().find(|r| r.name == filename) else {
+                // Already gone remotely
+                return self.forget_file_state(pair.id, &key).await;
             };
+            // Folders are not compared, as in a full pass
+            if !base.is_dir && remote_changed(remote, &base) {
+                println!("Keeping remote {:?}: it changed since the last sync", key);
+                return Ok(());
+            }
 
             let path_str = path.to_string_lossy().to_string();
             let pair_id = pair.id;
-            
-            if let Err(e) = provider.delete_file(filename, &remote_parent_id).await {
+
+            if let Err(e) = self.remove_remote_entry(provider.as_ref(), pair, &key, filename, &remote_parent_id).await {
                 eprintln!("Delete error: {:?}", e);
                 if matches!(e, CloudError::Unauthenticated) {
                     eprintln!("Token expired during delete, disconnecting account: {}", pair.account_id);
@@ -985,6 +2548,9 @@ impl SyncEngine {
                 }
                 return Err(e.into());
             } else {
+                let files = self.count_files_under(pair.id, &key).await?;
+                self.recent_deletions.lock().await.entry(pair_id).or_default().extend(std::iter::repeat_n(Instant::now(), files));
+                self.forget_file_state(pair.id, &key).await?;
                 on_status(SyncStatus::Deleted { pair_id, path: path_str.clone() });
                 
                 let on_status_clone = on_status.clone();
@@ -997,16 +2563,8 @@ impl SyncEngine {
         Ok(())
     }
 
-    async fn compute_local_hash(&self, path: &Path) -> Result<String> {
-        let mut file = tokio::fs::File::open(path).await?;
-        let mut hasher = md5::Context::new();
-        let mut buffer = [0u8; 8192];
-        loop {
-            let n = file.read(&mut buffer).await?;
-            if n == 0 { break; }
-            hasher.consume(&buffer[..n]);
-        }
-        let digest = hasher.compute();
-        Ok(format!("{:x}", digest))
+    /// Hash a local file the same way `provider` hashes its remote copies
+    async fn compute_local_hash(&self, path: &Path, provider: &dyn CloudProvider) -> Result<String> {
+        Ok(crate::hashing::hash_file(path, provider.hash_kind()).await?)
     }
 }
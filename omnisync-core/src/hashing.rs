@@ -0,0 +1,214 @@
+use crate::provider::HashKind;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Hash a local file with the algorithm a provider reports in `RemoteFile.hash`
+pub async fn hash_file(path: &Path, kind: HashKind) -> std::io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut hasher = ContentHasher::new(kind);
+    loop {
+        let n = file.read(&mut buffer).await?;
+        if n == 0 { break; }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Incremental hasher for any `HashKind`, for content that arrives in pieces
+pub enum ContentHasher {
+    Md5(md5::Context),
+    QuickXor(QuickXorHash),
+    Dropbox(DropboxContentHasher),
+    S3Etag(S3EtagHasher),
+    Sha256(Sha256),
+}
+
+impl ContentHasher {
+    pub fn new(kind: HashKind) -> Self {
+        match kind {
+            HashKind::Md5 => Self::Md5(md5::Context::new()),
+            HashKind::QuickXor => Self::QuickXor(QuickXorHash::new()),
+            HashKind::Dropbox => Self::Dropbox(DropboxContentHasher::new()),
+            HashKind::S3Etag => Self::S3Etag(S3EtagHasher::new(crate::providers::s3::PART_SIZE)),
+            HashKind::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Md5(hasher) => hasher.consume(bytes),
+            Self::QuickXor(hasher) => hasher.update(bytes),
+            Self::Dropbox(hasher) => hasher.update(bytes),
+            Self::S3Etag(hasher) => hasher.update(bytes),
+            Self::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub fn finalize(self) -> String {
+        match self {
+            Self::Md5(hasher) => format!("{:x}", hasher.compute()),
+            Self::QuickXor(hasher) => hasher.finalize(),
+            Self::Dropbox(hasher) => hasher.finalize(),
+            Self::S3Etag(hasher) => hasher.finalize(),
+            Self::Sha256(hasher) => hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// The ETag S3 assigns to an object uploaded by us: plain MD5 when it fits in one
+/// part, otherwise the MD5 of the concatenated part digests followed by "-<parts>".
+/// Objects uploaded elsewhere with a different part size will not match.
+pub struct S3EtagHasher {
+    part_size: u64,
+    part: md5::Context,
+    part_pos: u64,
+    digests: Vec<u8>,
+    parts: usize,
+}
+
+impl S3EtagHasher {
+    pub fn new(part_size: u64) -> Self {
+        Self { part_size, part: md5::Context::new(), part_pos: 0, digests: Vec::new(), parts: 0 }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.part_pos == self.part_size {
+                let digest = std::mem::replace(&mut self.part, md5::Context::new()).compute();
+                self.digests.extend_from_slice(&digest.0);
+                self.parts += 1;
+                self.part_pos = 0;
+            }
+            let take = bytes.len().min((self.part_size - self.part_pos) as usize);
+            self.part.consume(&bytes[..take]);
+            self.part_pos += take as u64;
+            bytes = &bytes[take..];
+        }
+    }
+
+    pub fn finalize(mut self) -> String {
+        let last = self.part.compute();
+        if self.parts == 0 {
+            return format!("{:x}", last);
+        }
+        self.digests.extend_from_slice(&last.0);
+        format!("{:x}-{}", md5::compute(&self.digests), self.parts + 1)
+    }
+}
+
+const DROPBOX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Dropbox's content_hash: the file is split into 4 MiB blocks, each block is
+/// hashed with SHA-256, and the concatenated digests are hashed again. Output is hex.
+pub struct DropboxContentHasher {
+    overall: Sha256,
+    block: Sha256,
+    block_pos: usize,
+}
+
+impl Default for DropboxContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DropboxContentHasher {
+    pub fn new() -> Self {
+        Self { overall: Sha256::new(), block: Sha256::new(), block_pos: 0 }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.block_pos == DROPBOX_BLOCK_SIZE {
+                let digest = std::mem::take(&mut self.block).finalize();
+                self.overall.update(digest);
+                self.block_pos = 0;
+            }
+            let take = bytes.len().min(DROPBOX_BLOCK_SIZE - self.block_pos);
+            self.block.update(&bytes[..take]);
+            self.block_pos += take;
+            bytes = &bytes[take..];
+        }
+    }
+
+    pub fn finalize(mut self) -> String {
+        if self.block_pos > 0 {
+            let digest = self.block.finalize();
+            self.overall.update(digest);
+        }
+        self.overall.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+const QXH_WIDTH_IN_BITS: usize = 160;
+const QXH_SHIFT: usize = 11;
+
+/// OneDrive's QuickXorHash: a 160-bit rotating XOR of the content, with the total
+/// length XOR-ed into the last 8 bytes. Output is base64 encoded.
+pub struct QuickXorHash {
+    data: [u64; 3],
+    length: u64,
+    shift: usize,
+}
+
+impl Default for QuickXorHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuickXorHash {
+    pub fn new() -> Self {
+        Self { data: [0; 3], length: 0, shift: 0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut cell = self.shift / 64;
+        let mut offset = self.shift % 64;
+
+        for i in 0..bytes.len().min(QXH_WIDTH_IN_BITS) {
+            let is_last_cell = cell == self.data.len() - 1;
+            let bits_in_cell = if is_last_cell { QXH_WIDTH_IN_BITS % 64 } else { 64 };
+
+            // Every byte that lands on the same bit position can be folded together first
+            let mut folded: u8 = 0;
+            for b in bytes.iter().skip(i).step_by(QXH_WIDTH_IN_BITS) {
+                folded ^= b;
+            }
+
+            if offset <= bits_in_cell - 8 {
+                self.data[cell] ^= (folded as u64) << offset;
+            } else {
+                let next = if is_last_cell { 0 } else { cell + 1 };
+                self.data[cell] ^= (folded as u64) << offset;
+                self.data[next] ^= (folded as u64) >> (bits_in_cell - offset);
+            }
+
+            offset += QXH_SHIFT;
+            while offset >= bits_in_cell {
+                cell = if is_last_cell { 0 } else { cell + 1 };
+                offset -= bits_in_cell;
+            }
+        }
+
+        self.shift = (self.shift + QXH_SHIFT * (bytes.len() % QXH_WIDTH_IN_BITS)) % QXH_WIDTH_IN_BITS;
+        self.length += bytes.len() as u64;
+    }
+
+    pub fn finalize(&self) -> String {
+        let mut out = [0u8; QXH_WIDTH_IN_BITS / 8];
+        out[..8].copy_from_slice(&self.data[0].to_le_bytes());
+        out[8..16].copy_from_slice(&self.data[1].to_le_bytes());
+        out[16..].copy_from_slice(&self.data[2].to_le_bytes()[..4]);
+
+        let length = self.length.to_le_bytes();
+        let start = out.len() - length.len();
+        for (i, b) in length.iter().enumerate() {
+            out[start + i] ^= b;
+        }
+        STANDARD.encode(out)
+    }
+}
@@ -0,0 +1,123 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Per-directory rule files, gitignore syntax, relative to the directory they are in
+pub const IGNORE_FILE: &str = ".omnisyncignore";
+
+/// Always skipped: the engine's own bookkeeping and in-flight downloads
+const BUILTIN_RULES: &[&str] = &[".omnisync/", ".*.omnisync-part"];
+
+/// Decides which entries of a pair are left out of syncing, on both sides.
+/// Rules come from three places, most specific first:
+/// `.omnisyncignore` files at any level (deeper files override shallower ones),
+/// then the pair's own rules from the database. A `!pattern` re-includes an entry
+/// an earlier source excluded, as in git. Built-in rules cannot be overridden.
+/// Entries inside an ignored folder are ignored too.
+///
+/// Folders excluded by selective sync are matched by key and win over everything else.
+///
+/// Rule files are read on first use and cached, so build a new matcher per sync pass
+/// to pick up edits.
+pub struct IgnoreMatcher {
+    root: PathBuf,
+    builtin: Gitignore,
+    pair_rules: Gitignore,
+    excluded: Vec<String>,
+    files: Mutex<HashMap<PathBuf, Option<Gitignore>>>,
+}
+
+fn build(root: &Path, lines: &[&str]) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    for line in lines {
+        if let Err(e) = builder.add_line(None, line) {
+            eprintln!("Ignoring invalid ignore rule {:?}: {}", line, e);
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+impl IgnoreMatcher {
+    /// A matcher for the pair rooted at `root` with its stored rules (one pattern per line)
+    pub fn new(root: &Path, pair_rules: &str) -> Self {
+        let lines: Vec<&str> = pair_rules.lines().collect();
+        Self {
+            root: root.to_path_buf(),
+            builtin: build(root, BUILTIN_RULES),
+            pair_rules: build(root, &lines),
+            excluded: Vec::new(),
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also leave out these folders and everything inside them (keys relative to the root)
+    pub fn excluding<'a>(mut self, folders: impl IntoIterator<Item = &'a str>) -> Self {
+        self.excluded.extend(folders.into_iter().map(|f| f.trim_matches('/').to_string()));
+        self
+    }
+
+    /// Whether `key` ('/' separated, relative to the pair root) is excluded
+    pub fn is_ignored(&self, key: &str, is_dir: bool) -> bool {
+        let key = key.trim_matches('/');
+        if self.excluded.iter().any(|f| key == f || key.strip_prefix(f.as_str()).is_some_and(|rest| rest.starts_with('/'))) {
+            return true;
+        }
+        let segments: Vec<&str> = key.split('/').filter(|s| !s.is_empty()).collect();
+        let mut path = self.root.clone();
+        for (i, segment) in segments.iter().enumerate() {
+            path.push(segment);
+            let last = i + 1 == segments.len();
+            if self.matches(&path, if last { is_dir } else { true }) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Same as `is_ignored` for an absolute local path inside the pair
+    pub fn is_ignored_path(&self, path: &Path) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root) else { return false };
+        let key: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+        self.is_ignored(&key.join("/"), path.is_dir())
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.builtin.matched(path, is_dir).is_ignore() {
+            return true;
+        }
+
+        // Rule files from the entry's own folder up to the root
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| d.starts_with(&self.root)) {
+            if let Some(rules) = self.rule_file(d) {
+                match rules.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            dir = d.parent();
+        }
+
+        self.pair_rules.matched(path, is_dir).is_ignore()
+    }
+
+    fn rule_file(&self, dir: &Path) -> Option<Gitignore> {
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files.entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let file = dir.join(IGNORE_FILE);
+                if !file.is_file() {
+                    return None;
+                }
+                let (rules, error) = Gitignore::new(&file);
+                if let Some(e) = error {
+                    eprintln!("Problem reading {:?}: {}", file, e);
+                }
+                Some(rules)
+            })
+            .clone()
+    }
+}
@@ -1,8 +1,18 @@
+pub mod auth;
 pub mod config;
+#[cfg(unix)]
+pub mod control;
+pub mod credential_store;
+pub mod download;
 pub mod engine;
+pub mod hashing;
+pub mod ignore_rules;
 pub mod models;
 pub mod provider;
 pub mod providers;
+pub mod reconcile;
+pub mod registry;
+pub mod trash;
 pub mod watcher;
 
 pub use engine::SyncEngine;
@@ -12,6 +12,33 @@ pub struct SyncPair {
     pub status: String,
     pub created_at: i64,
     pub last_sync_at: Option<i64>,
+    /// gitignore-style patterns, one per line; see `ignore_rules::IgnoreMatcher`
+    pub ignore_rules: String,
+    /// Selective sync: remote folders left out of this pair, as keys relative to
+    /// the pair root, one per line
+    pub excluded_folders: String,
+    /// Sync direction, see `SyncMode`
+    pub mode: String,
+    /// Most files one pass may delete remotely before the pair is paused (0: no limit)
+    pub delete_limit: i64,
+    /// Same as a percentage of the pair's synced files (0: no limit)
+    pub delete_limit_percent: i64,
+    /// Days displaced local files stay in the pair's trash (0: forever)
+    pub trash_retention_days: i64,
+    /// Delete on the cloud for good instead of moving entries to the provider's trash
+    pub permanent_delete: bool,
+}
+
+impl SyncPair {
+    /// Keys of the folders excluded by selective sync
+    pub fn excluded_keys(&self) -> impl Iterator<Item = &str> {
+        self.excluded_folders.lines().filter(|l| !l.is_empty())
+    }
+
+    /// The pair's sync direction; unknown values fall back to two-way
+    pub fn sync_mode(&self) -> SyncMode {
+        self.mode.parse().unwrap_or(SyncMode::TwoWay)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -24,4 +51,196 @@ pub struct Credentials {
     pub user_name: Option<String>,
     pub user_email: Option<String>,
     pub user_avatar: Option<String>,
+    /// Provider-specific connection settings as JSON (non-OAuth providers)
+    pub config: Option<String>,
+}
+
+/// Last-synced (base) state of a file or directory within a sync pair.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FileState {
+    pub pair_id: i64,
+    pub path: String,
+    pub is_dir: bool,
+    pub hash: Option<String>,
+    pub size: Option<i64>,
+    pub modified_at: Option<i64>,
+    pub remote_id: Option<String>,
+    pub remote_hash: Option<String>,
+    pub remote_revision: Option<String>,
+    pub remote_modified_at: Option<i64>,
+    pub status: String,
+    pub synced_at: i64,
+}
+
+/// Which way changes flow between the two sides of a pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    /// Changes and deletions go both ways
+    TwoWay,
+    /// Backup: local changes are uploaded, nothing is ever deleted remotely
+    /// and remote changes are not downloaded
+    UploadOnly,
+    /// Remote changes and deletions are applied locally; local changes stay local
+    DownloadOnly,
+    /// The remote side is made identical to the local one, undoing remote changes
+    Mirror,
+}
+
+impl SyncMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncMode::TwoWay => "two_way",
+            SyncMode::UploadOnly => "upload_only",
+            SyncMode::DownloadOnly => "download_only",
+            SyncMode::Mirror => "mirror",
+        }
+    }
+
+    /// Whether local changes are sent to the cloud
+    pub fn uploads(&self) -> bool {
+        !matches!(self, SyncMode::DownloadOnly)
+    }
+
+    /// Whether remote changes are applied locally
+    pub fn downloads(&self) -> bool {
+        matches!(self, SyncMode::TwoWay | SyncMode::DownloadOnly)
+    }
+
+    /// Whether entries removed locally are removed remotely too
+    pub fn deletes_remote(&self) -> bool {
+        matches!(self, SyncMode::TwoWay | SyncMode::Mirror)
+    }
+}
+
+impl std::str::FromStr for SyncMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "two_way" | "two-way" => Ok(SyncMode::TwoWay),
+            "upload_only" | "upload-only" | "backup" => Ok(SyncMode::UploadOnly),
+            "download_only" | "download-only" => Ok(SyncMode::DownloadOnly),
+            "mirror" => Ok(SyncMode::Mirror),
+            _ => Err(anyhow::anyhow!("Unknown sync mode: {}", s)),
+        }
+    }
+}
+
+/// Whether a remote folder is mirrored locally under selective sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderCheckState {
+    /// Synced with everything inside it
+    Included,
+    /// Left out, on its own or through a parent folder
+    Excluded,
+    /// Synced, but some folders inside it are left out
+    Partial,
+}
+
+/// One remote folder of a pair, for picking what selective sync mirrors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderNode {
+    /// Remote id, to list the folder's own children
+    pub id: String,
+    /// Key relative to the pair root (e.g. "photos/2023")
+    pub path: String,
+    pub name: String,
+    pub state: FolderCheckState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Conflict {
+    pub id: i64,
+    pub pair_id: i64,
+    pub path: String,
+    pub conflict_path: String,
+    pub detected_at: i64,
+    pub resolved_at: Option<i64>,
+    pub resolution: Option<String>,
+}
+
+/// A local file or folder the engine deleted or overwrote, kept in the pair's trash
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrashItem {
+    pub id: i64,
+    pub pair_id: i64,
+    /// Original location, relative to the pair root
+    pub path: String,
+    pub is_dir: bool,
+    pub size: Option<i64>,
+    /// Why it was displaced, see `TrashReason`
+    pub reason: String,
+    pub trashed_at: i64,
+}
+
+/// An entry the engine moved to the provider's trash
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RemoteTrashItem {
+    pub id: i64,
+    pub pair_id: i64,
+    /// Original location, relative to the pair root
+    pub path: String,
+    /// Provider id to restore it by
+    pub trash_id: String,
+    pub is_dir: bool,
+    pub trashed_at: i64,
+}
+
+/// Why a local entry ended up in the trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashReason {
+    /// Deleted because it was removed from the cloud
+    Deleted,
+    /// Previous version of a file replaced by a download
+    Overwritten,
+    /// Moved aside to restore another trash item in its place
+    Replaced,
+}
+
+impl TrashReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashReason::Deleted => "deleted",
+            TrashReason::Overwritten => "overwritten",
+            TrashReason::Replaced => "replaced",
+        }
+    }
+}
+
+/// How to settle a conflict between an original file and its conflicted copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Keep the local version (the conflicted copy) in place of the original
+    KeepLocal,
+    /// Keep the remote version and discard the conflicted copy
+    KeepRemote,
+    /// Keep both files as they are
+    KeepBoth,
+}
+
+impl ConflictResolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictResolution::KeepLocal => "keep_local",
+            ConflictResolution::KeepRemote => "keep_remote",
+            ConflictResolution::KeepBoth => "keep_both",
+        }
+    }
+}
+
+impl std::str::FromStr for ConflictResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "keep_local" | "local" => Ok(ConflictResolution::KeepLocal),
+            "keep_remote" | "remote" => Ok(ConflictResolution::KeepRemote),
+            "keep_both" | "both" => Ok(ConflictResolution::KeepBoth),
+            _ => Err(anyhow::anyhow!("Unknown conflict resolution: {}", s)),
+        }
+    }
 }
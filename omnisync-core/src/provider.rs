@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(thiserror::Error, Debug)]
 pub enum CloudError {
@@ -11,25 +12,119 @@ pub enum CloudError {
     Network(#[from] reqwest::Error),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Change cursor is no longer valid")]
+    InvalidCursor,
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
 
 pub type CloudResult<T> = std::result::Result<T, CloudError>;
 
+/// Content hash algorithm a provider reports in `RemoteFile.hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+    /// Hex-encoded MD5 (Google Drive)
+    Md5,
+    /// Base64-encoded QuickXorHash (OneDrive)
+    QuickXor,
+    /// Hex-encoded Dropbox content_hash (SHA-256 over per-4 MiB-block SHA-256 digests)
+    Dropbox,
+    /// S3 ETag: hex MD5 for single-part objects, MD5 of the part MD5s plus "-N" for multipart ones
+    S3Etag,
+    /// Hex-encoded SHA-256 (SFTP, computed by `sha256sum` on the server)
+    Sha256,
+}
+
+/// The local content an upload session was started for. A stored session is only
+/// resumed while the file still has the same size and modification time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadKey {
+    pub local_path: String,
+    pub remote_parent: String,
+    pub size: u64,
+    pub modified_at: i64,
+}
+
+/// Persists resumable upload session URIs, so an interrupted upload can continue
+/// after a restart. Failures to persist are not fatal; the upload just starts over.
+#[async_trait]
+pub trait UploadSessionStore: Send + Sync {
+    async fn load(&self, key: &UploadKey) -> Option<String>;
+    async fn save(&self, key: &UploadKey, session_uri: &str);
+    async fn clear(&self, key: &UploadKey);
+}
+
+/// Called with (bytes sent, total bytes) as an upload progresses
+pub type ProgressFn = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Optional hooks for long-running transfers
+#[derive(Clone, Default)]
+pub struct Transfer {
+    pub sessions: Option<Arc<dyn UploadSessionStore>>,
+    pub progress: Option<ProgressFn>,
+}
+
+impl Transfer {
+    pub fn report(&self, sent: u64, total: u64) {
+        if let Some(progress) = &self.progress {
+            progress(sent, total);
+        }
+    }
+}
+
 #[async_trait]
 pub trait CloudProvider: Send + Sync {
     /// return the identifier of the provider (e.g., "gdrive", "onedrive")
     fn id(&self) -> &str;
 
-    /// Upload a file to the cloud
-    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<()>;
+    /// Hash algorithm used for `RemoteFile.hash`, so local files can be hashed comparably
+    fn hash_kind(&self) -> HashKind {
+        HashKind::Md5
+    }
+
+    /// Upload a file to the cloud, returning the resulting remote entry
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile>;
+
+    /// Upload a file, resuming an earlier session and reporting progress where the provider supports it
+    async fn upload_file_with(&self, local_path: &Path, cloud_path: &str, transfer: &Transfer) -> CloudResult<RemoteFile> {
+        let _ = transfer;
+        self.upload_file(local_path, cloud_path).await
+    }
 
     /// Download a file from the cloud
     async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()>;
+
+    /// Download a listed file. Providers that can check the content against `remote.hash`
+    /// do so before the file appears at `local_path`.
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_file(&remote.id, local_path).await
+    }
     
     /// Delete a file on the cloud
     async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()>;
+
+    /// Whether the provider has a trash that deleted entries can be restored from
+    fn supports_trash(&self) -> bool {
+        false
+    }
+
+    /// Move an entry to the provider's trash instead of deleting it. Returns None when
+    /// there is no such entry. Only called when `supports_trash` is true.
+    async fn trash_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<Option<TrashedFile>> {
+        let _ = (filename, cloud_parent);
+        Err(CloudError::ApiError(format!("{} has no trash", self.id())))
+    }
+
+    /// Entries that were moved to the trash themselves (not just inside a trashed folder)
+    async fn list_trashed(&self) -> CloudResult<Vec<TrashedFile>> {
+        Ok(Vec::new())
+    }
+
+    /// Move a trashed entry back to where it was
+    async fn restore_file(&self, trash_id: &str) -> CloudResult<RemoteFile> {
+        let _ = trash_id;
+        Err(CloudError::ApiError(format!("{} has no trash", self.id())))
+    }
     
     /// Get metadata for a file (hash, size, modified_at)
     async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata>;
@@ -42,6 +137,46 @@ pub trait CloudProvider: Send + Sync {
 
     /// Create a folder in the cloud
     async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String>;
+
+    /// Move an entry to `to_parent` under `new_name`, keeping its identity on the server
+    /// (revision history, sharing). Returns None when the provider cannot move entries,
+    /// in which case the caller deletes and re-uploads.
+    async fn move_file(&self, file_id: &str, from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let _ = (file_id, from_parent, to_parent, new_name);
+        Ok(None)
+    }
+
+    /// Rename an entry in place
+    async fn rename_file(&self, file_id: &str, parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        self.move_file(file_id, parent, parent, new_name).await
+    }
+
+    /// Changes since `cursor`, or just a cursor for "now" when `cursor` is None.
+    /// Returns None when the provider has no change feed and must be walked in full;
+    /// `CloudError::InvalidCursor` means the cursor expired and a full walk is needed.
+    async fn changes_since(&self, cursor: Option<&str>) -> CloudResult<Option<ChangeSet>> {
+        let _ = cursor;
+        Ok(None)
+    }
+}
+
+/// One changed entry from a provider's change feed
+#[derive(Debug, Clone)]
+pub struct RemoteChange {
+    pub file_id: String,
+    /// Deleted, trashed, or no longer visible to us
+    pub removed: bool,
+    /// Current name; None when removed
+    pub name: Option<String>,
+    /// Current parent folder ids; empty when removed
+    pub parents: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    pub changes: Vec<RemoteChange>,
+    /// Pass to the next `changes_since` call
+    pub cursor: String,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -52,6 +187,22 @@ pub struct RemoteFile {
     pub size: Option<u64>,
     pub modified_at: Option<i64>,
     pub hash: Option<String>,
+    /// Opaque version token (a WebDAV ETag) that changes whenever the content does.
+    /// Unlike `hash` it cannot be compared with local content, only with an earlier listing.
+    #[serde(default)]
+    pub revision: Option<String>,
+}
+
+/// An entry in a provider's trash
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TrashedFile {
+    /// Pass to `restore_file`
+    pub id: String,
+    pub name: String,
+    pub is_dir: bool,
+    /// Folder it was trashed from, when the provider reports it
+    pub parent: Option<String>,
+    pub trashed_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -0,0 +1,405 @@
+use crate::auth::{check_client_id, request_tokens, AuthProvider, TokenResponse, UserInfo};
+use crate::download::{save_response, Expected};
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteFile, RemoteFolder};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::{Client, Response, StatusCode};
+use serde_json::{json, Value};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+const API_URL: &str = "https://api.dropboxapi.com/2";
+const CONTENT_URL: &str = "https://content.dropboxapi.com/2";
+pub const AUTHORIZE_URL: &str = "https://www.dropbox.com/oauth2/authorize";
+pub const TOKEN_URL: &str = "https://api.dropboxapi.com/oauth2/token";
+
+/// Files up to this size are sent in a single request, larger ones through an upload session
+const SIMPLE_UPLOAD_LIMIT: u64 = 8 * 1024 * 1024;
+/// Upload session chunks; Dropbox recommends a multiple of 4 MiB
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+/// Upper bound on folders returned by `list_folders`
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Dropbox through the v2 HTTP API.
+/// Dropbox is path-addressed: folder and file ids are paths ("/Photos/2024"), and
+/// the root is the empty string. "root" and "/" are accepted as aliases for it.
+pub struct DropboxProvider {
+    client: Client,
+    access_token: String,
+    api_url: String,
+    content_url: String,
+}
+
+impl DropboxProvider {
+    pub fn new(access_token: String) -> Self {
+        Self::with_base_urls(access_token, API_URL, CONTENT_URL)
+    }
+
+    /// Use different API/content endpoints, e.g. a local mock server
+    pub fn with_base_urls(access_token: String, api_url: &str, content_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            access_token,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            content_url: content_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn normalize(path: &str) -> &str {
+        match path {
+            "" | "/" | "root" => "",
+            p => p.trim_end_matches('/'),
+        }
+    }
+
+    fn join(parent: &str, name: &str) -> String {
+        format!("{}/{}", Self::normalize(parent), name)
+    }
+
+    /// Call an RPC endpoint (JSON in, JSON out)
+    async fn rpc(&self, endpoint: &str, args: &Value) -> CloudResult<Response> {
+        let response = self.client
+            .post(format!("{}/{}", self.api_url, endpoint))
+            .bearer_auth(&self.access_token)
+            .json(args)
+            .send()
+            .await?;
+        Ok(response)
+    }
+
+    /// Call a content-upload endpoint; arguments travel in the Dropbox-API-Arg header
+    async fn upload_call(&self, endpoint: &str, args: &Value, body: Vec<u8>) -> CloudResult<Response> {
+        let response = self.client
+            .post(format!("{}/{}", self.content_url, endpoint))
+            .bearer_auth(&self.access_token)
+            .header("Dropbox-API-Arg", api_arg(args))
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(body)
+            .send()
+            .await?;
+        Ok(response)
+    }
+
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        let response = self.client
+            .post(format!("{}/files/download", self.content_url))
+            .bearer_auth(&self.access_token)
+            .header("Dropbox-API-Arg", api_arg(&json!({ "path": file_id })))
+            .send()
+            .await?;
+        save_response(Self::check(response, "Download").await?, local_path, expected).await
+    }
+
+    async fn check(response: Response, action: &str) -> CloudResult<Response> {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(CloudError::Unauthenticated);
+        }
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(CloudError::ApiError(format!("{} failed: {}", action, error_text)));
+        }
+        Ok(response)
+    }
+
+    /// For a 409 response, the `error_summary` (e.g. "path/not_found/..") that endpoint errors carry
+    async fn error_summary(response: Response) -> String {
+        let body: Value = response.json().await.unwrap_or_default();
+        body["error_summary"].as_str().unwrap_or_default().to_string()
+    }
+
+    /// Parse file/folder metadata. Upload responses carry no ".tag" and are files.
+    fn parse_entry(entry: &Value) -> RemoteFile {
+        let is_dir = entry[".tag"].as_str() == Some("folder");
+        RemoteFile {
+            id: entry["path_display"].as_str().unwrap_or_default().to_string(),
+            name: entry["name"].as_str().unwrap_or_default().to_string(),
+            is_dir,
+            size: entry["size"].as_u64(),
+            modified_at: entry["server_modified"].as_str().and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp())
+            }),
+            hash: entry["content_hash"].as_str().map(|s| s.to_string()),
+            revision: None,
+        }
+    }
+
+    /// Fetch every page of a folder listing
+    async fn list_folder(&self, path: &str, recursive: bool) -> CloudResult<Vec<RemoteFile>> {
+        let mut all_files = Vec::new();
+        let response = self.rpc("files/list_folder", &json!({
+            "path": Self::normalize(path),
+            "recursive": recursive,
+            "include_deleted": false,
+            "limit": 2000,
+        })).await?;
+        let mut body: Value = Self::check(response, "List files").await?.json().await?;
+
+        loop {
+            let entries = body["entries"].as_array().ok_or_else(|| CloudError::ApiError("Invalid body".to_string()))?;
+            all_files.extend(
+                entries.iter()
+                    .filter(|e| matches!(e[".tag"].as_str(), Some("file") | Some("folder")))
+                    .map(Self::parse_entry),
+            );
+
+            if !body["has_more"].as_bool().unwrap_or(false) {
+                break;
+            }
+            let cursor = body["cursor"].as_str().unwrap_or_default().to_string();
+            let response = self.rpc("files/list_folder/continue", &json!({ "cursor": cursor })).await?;
+            body = Self::check(response, "List files").await?.json().await?;
+        }
+
+        Ok(all_files)
+    }
+
+    /// Upload in chunks through an upload session
+    async fn upload_session(&self, local_path: &Path, commit: Value) -> CloudResult<RemoteFile> {
+        let mut file = tokio::fs::File::open(local_path).await?;
+        let mut offset: u64 = 0;
+        let mut session_id: Option<String> = None;
+
+        loop {
+            let mut chunk = Vec::with_capacity(UPLOAD_CHUNK_SIZE);
+            (&mut file).take(UPLOAD_CHUNK_SIZE as u64).read_to_end(&mut chunk).await?;
+            let len = chunk.len() as u64;
+
+            let Some(id) = session_id.clone() else {
+                let response = self.upload_call("files/upload_session/start", &json!({ "close": false }), chunk).await?;
+                let body: Value = Self::check(response, "Start upload session").await?.json().await?;
+                let id = body["session_id"].as_str()
+                    .ok_or_else(|| CloudError::ApiError("No session_id returned".to_string()))?;
+                session_id = Some(id.to_string());
+                offset += len;
+                continue;
+            };
+
+            let cursor = json!({ "session_id": id, "offset": offset });
+            if len == 0 {
+                let response = self.upload_call("files/upload_session/finish", &json!({ "cursor": cursor, "commit": commit }), chunk).await?;
+                let entry: Value = Self::check(response, "Finish upload session").await?.json().await?;
+                return Ok(Self::parse_entry(&entry));
+            }
+
+            let response = self.upload_call("files/upload_session/append_v2", &json!({ "cursor": cursor, "close": false }), chunk).await?;
+            Self::check(response, "Append to upload session").await?;
+            offset += len;
+        }
+    }
+}
+
+/// Dropbox-API-Arg must be ASCII, so non-ASCII characters are sent as \uXXXX escapes
+fn api_arg(args: &Value) -> String {
+    let mut out = String::new();
+    for c in args.to_string().chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                out.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    out
+}
+
+#[async_trait]
+impl CloudProvider for DropboxProvider {
+    fn id(&self) -> &str {
+        "dropbox"
+    }
+
+    fn hash_kind(&self) -> HashKind {
+        HashKind::Dropbox
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+
+        let local_meta = tokio::fs::metadata(local_path).await?;
+        let mut commit = json!({
+            "path": Self::join(cloud_path, filename),
+            "mode": "overwrite",
+            "mute": true,
+        });
+        if let Some(secs) = local_meta.modified().ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+        {
+            if let Some(dt) = chrono::DateTime::from_timestamp(secs, 0) {
+                commit["client_modified"] = json!(dt.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+            }
+        }
+
+        let remote = if local_meta.len() <= SIMPLE_UPLOAD_LIMIT {
+            let contents = tokio::fs::read(local_path).await?;
+            let response = self.upload_call("files/upload", &commit, contents).await?;
+            let entry: Value = Self::check(response, "Upload").await?.json().await?;
+            Self::parse_entry(&entry)
+        } else {
+            self.upload_session(local_path, commit).await?
+        };
+
+        println!("Uploaded {} to Dropbox", filename);
+        Ok(remote)
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::listed(remote, HashKind::Dropbox)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let response = self.rpc("files/delete_v2", &json!({ "path": Self::join(cloud_parent, filename) })).await?;
+
+        if response.status() == StatusCode::CONFLICT {
+            let summary = Self::error_summary(response).await;
+            if summary.starts_with("path_lookup/not_found") {
+                return Ok(());
+            }
+            return Err(CloudError::ApiError(format!("Delete failed: {}", summary)));
+        }
+        Self::check(response, "Delete").await?;
+        println!("Deleted {} from Dropbox", filename);
+        Ok(())
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let response = self.rpc("files/get_metadata", &json!({ "path": Self::normalize(cloud_path) })).await?;
+        let entry: Value = Self::check(response, "Get metadata").await?.json().await?;
+        let remote = Self::parse_entry(&entry);
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        self.list_folder(folder_id, false).await
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        let mut folders: Vec<RemoteFolder> = self.list_folder("", true).await?
+            .into_iter()
+            .filter(|f| f.is_dir)
+            .map(|f| RemoteFolder { name: f.id.trim_start_matches('/').to_string(), id: f.id })
+            .collect();
+        folders.sort_by_key(|f| f.name.to_lowercase());
+        folders.truncate(MAX_LISTED_FOLDERS);
+        Ok(folders)
+    }
+
+    async fn move_file(&self, file_id: &str, _from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let args = json!({ "from_path": file_id, "to_path": Self::join(to_parent, new_name), "autorename": false });
+        let response = self.rpc("files/move_v2", &args).await?;
+        let body: Value = Self::check(response, "Move").await?.json().await?;
+
+        println!("Moved {} to {} on Dropbox", file_id, new_name);
+        Ok(Some(Self::parse_entry(&body["metadata"])))
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let path = Self::join(parent_id, name);
+        let response = self.rpc("files/create_folder_v2", &json!({ "path": path, "autorename": false })).await?;
+
+        // Already exists: the path is the folder's id
+        if response.status() == StatusCode::CONFLICT {
+            let summary = Self::error_summary(response).await;
+            if summary.starts_with("path/conflict/folder") {
+                return Ok(path);
+            }
+            return Err(CloudError::ApiError(format!("Create folder failed: {}", summary)));
+        }
+
+        let body: Value = Self::check(response, "Create folder").await?.json().await?;
+        let id = body["metadata"]["path_display"].as_str().unwrap_or(&path).to_string();
+
+        println!("Created folder {} on Dropbox", name);
+        Ok(id)
+    }
+}
+
+/// Authorization URL for the PKCE code flow, requesting a refresh token
+pub fn authorize_url(client_id: &str, redirect_uri: &str, code_challenge: &str) -> String {
+    let mut url = url::Url::parse(AUTHORIZE_URL).expect("valid authorize URL");
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("response_type", "code")
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("token_access_type", "offline")
+        .append_pair("code_challenge", code_challenge)
+        .append_pair("code_challenge_method", "S256");
+    url.to_string()
+}
+
+/// Dropbox sign-in (PKCE, no app secret). Refresh tokens do not rotate.
+pub struct DropboxAuth {
+    client_id: String,
+}
+
+impl DropboxAuth {
+    pub fn new(client_id: String) -> Self {
+        Self { client_id }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for DropboxAuth {
+    fn authorize_url(&self, redirect_uri: &str, code_challenge: &str) -> anyhow::Result<String> {
+        check_client_id(&self.client_id, "Dropbox")?;
+        Ok(authorize_url(&self.client_id, redirect_uri, code_challenge))
+    }
+
+    async fn exchange_code(&self, code: &str, redirect_uri: &str, code_verifier: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("code", code),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", code_verifier),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token exchange").await
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token refresh").await
+    }
+
+    async fn user_info(&self, access_token: &str) -> anyhow::Result<UserInfo> {
+        let response = Client::new().post(format!("{}/users/get_current_account", API_URL))
+            .bearer_auth(access_token)
+            .json(&Value::Null)
+            .send()
+            .await?;
+        let user: Value = response.error_for_status()?.json().await?;
+        Ok(UserInfo {
+            name: user["name"]["display_name"].as_str().map(|s| s.to_string()),
+            email: user["email"].as_str().map(|s| s.to_string()),
+            avatar: user["profile_photo_url"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    async fn revoke(&self, access_token: &str, _refresh_token: Option<&str>) -> anyhow::Result<()> {
+        Client::new().post(format!("{}/auth/token/revoke", API_URL))
+            .bearer_auth(access_token)
+            .json(&Value::Null)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
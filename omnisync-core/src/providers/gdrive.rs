@@ -1,21 +1,63 @@
-use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult};
+use crate::auth::{check_client_id, request_tokens, AuthProvider, TokenResponse, UserInfo};
+use crate::download::{save_response, Expected};
+use crate::provider::{ChangeSet, CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteChange, RemoteFile, Transfer, TrashedFile, UploadKey};
 use anyhow::anyhow;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, Response, StatusCode};
+use std::io::SeekFrom;
 use std::path::Path;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+pub const AUTHORIZE_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+pub const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+pub const SCOPES: &str = "https://www.googleapis.com/auth/drive";
+
+const API_URL: &str = "https://www.googleapis.com/drive/v3";
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3";
+
+const FILE_FIELDS: &str = "id,name,mimeType,size,modifiedTime,md5Checksum";
+const TRASHED_FIELDS: &str = "id,name,mimeType,parents,trashedTime,explicitlyTrashed";
+
+/// Files larger than this are sent through a resumable upload session
+pub const RESUMABLE_THRESHOLD: u64 = 5 * 1024 * 1024;
+/// Resumable upload chunks must be a multiple of 256 KiB
+pub const UPLOAD_CHUNK_SIZE: u64 = 32 * 256 * 1024;
 
 pub struct GoogleDriveProvider {
     client: Client,
+    /// Resumable uploads answer with 308 Resume Incomplete, which must not be followed as a redirect
+    upload_client: Client,
     access_token: String,
+    api_url: String,
+    upload_url: String,
+}
+
+/// Where a resumable upload session stands
+enum UploadState {
+    /// The server has this many bytes
+    Partial(u64),
+    /// The upload is done; the file resource
+    Complete(serde_json::Value),
 }
 
 impl GoogleDriveProvider {
     pub fn new(access_token: String) -> Self {
+        Self::with_base_urls(access_token, API_URL, UPLOAD_URL)
+    }
+
+    /// Point the provider at another API host (used by tests)
+    pub fn with_base_urls(access_token: String, api_url: &str, upload_url: &str) -> Self {
         Self {
             client: Client::new(),
+            upload_client: Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to build HTTP client"),
             access_token,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            upload_url: upload_url.trim_end_matches('/').to_string(),
         }
     }
 
@@ -23,7 +65,7 @@ impl GoogleDriveProvider {
         let escaped_name = name.replace("'", "\\'");
         let q = format!("name = '{}' and '{}' in parents and trashed = false", escaped_name, parent_id);
         let response = self.client
-            .get("https://www.googleapis.com/drive/v3/files")
+            .get(format!("{}/files", self.api_url))
             .query(&[("q", q.as_str()), ("fields", "files(id, size, modifiedTime, md5Checksum)")])
             .bearer_auth(&self.access_token)
             .send()
@@ -55,17 +97,235 @@ impl GoogleDriveProvider {
         Ok(None)
     }
 
+    fn parse_remote_file(f: &serde_json::Value) -> RemoteFile {
+        let mime_type = f["mimeType"].as_str().unwrap_or_default();
+        RemoteFile {
+            id: f["id"].as_str().unwrap_or_default().to_string(),
+            name: f["name"].as_str().unwrap_or_default().to_string(),
+            is_dir: mime_type == "application/vnd.google-apps.folder",
+            size: f["size"].as_str().and_then(|s| s.parse().ok()),
+            modified_at: f["modifiedTime"].as_str().and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp())
+            }),
+            hash: f["md5Checksum"].as_str().map(|s| s.to_string()),
+            revision: None,
+        }
+    }
+
+    fn parse_trashed_file(f: &serde_json::Value) -> TrashedFile {
+        TrashedFile {
+            id: f["id"].as_str().unwrap_or_default().to_string(),
+            name: f["name"].as_str().unwrap_or_default().to_string(),
+            is_dir: f["mimeType"].as_str() == Some("application/vnd.google-apps.folder"),
+            parent: f["parents"][0].as_str().map(|p| p.to_string()),
+            trashed_at: f["trashedTime"].as_str().and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp())
+            }),
+        }
+    }
+
+    /// Set a file's trashed flag
+    async fn set_trashed(&self, file_id: &str, trashed: bool, fields: &str) -> CloudResult<serde_json::Value> {
+        let response = self.client
+            .patch(format!("{}/files/{}", self.api_url, file_id))
+            .query(&[("fields", fields)])
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({ "trashed": trashed }))
+            .send()
+            .await?;
+        let action = if trashed { "Trash" } else { "Restore" };
+        Ok(Self::check(response, action).await?.json().await?)
+    }
+
     async fn compute_local_hash(&self, path: &Path) -> CloudResult<String> {
-        let mut file = File::open(path).await?;
-        let mut hasher = md5::Context::new();
-        let mut buffer = [0u8; 8192];
+        Ok(crate::hashing::hash_file(path, HashKind::Md5).await?)
+    }
+
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        let response = self.client
+            .get(format!("{}/files/{}?alt=media", self.api_url, file_id))
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+        save_response(Self::check(response, "Download").await?, local_path, expected).await
+    }
+
+    async fn get_json(&self, url: &str) -> CloudResult<serde_json::Value> {
+        let response = self.client.get(url).bearer_auth(&self.access_token).send().await?;
+        Ok(Self::check(response, "Request").await?.json().await?)
+    }
+
+    async fn check(response: Response, action: &str) -> CloudResult<Response> {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(CloudError::Unauthenticated);
+        }
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(CloudError::ApiError(format!("{} failed: {}", action, error_text)));
+        }
+        Ok(response)
+    }
+
+    /// Create (`file_id` None) or update a file with a single multipart request
+    async fn upload_multipart(&self, file_id: Option<&str>, metadata: &serde_json::Value, contents: Vec<u8>) -> CloudResult<serde_json::Value> {
+        let form = reqwest::multipart::Form::new()
+            .part("metadata", reqwest::multipart::Part::text(metadata.to_string()).mime_str("application/json")?)
+            .part("file", reqwest::multipart::Part::bytes(contents).mime_str("application/octet-stream")?);
+
+        let request = match file_id {
+            Some(id) => self.client.patch(format!("{}/files/{}?uploadType=multipart&fields={}", self.upload_url, id, FILE_FIELDS)),
+            None => self.client.post(format!("{}/files?uploadType=multipart&fields={}", self.upload_url, FILE_FIELDS)),
+        };
+        let response = request
+            .bearer_auth(&self.access_token)
+            .multipart(form)
+            .send()
+            .await?;
+
+        let action = if file_id.is_some() { "Update" } else { "Upload" };
+        Ok(Self::check(response, action).await?.json().await?)
+    }
+
+    /// Open a resumable upload session and return its URI
+    async fn start_upload_session(&self, file_id: Option<&str>, metadata: &serde_json::Value, size: u64) -> CloudResult<String> {
+        let request = match file_id {
+            Some(id) => self.client.patch(format!("{}/files/{}?uploadType=resumable&fields={}", self.upload_url, id, FILE_FIELDS)),
+            None => self.client.post(format!("{}/files?uploadType=resumable&fields={}", self.upload_url, FILE_FIELDS)),
+        };
+        let response = request
+            .bearer_auth(&self.access_token)
+            .header("X-Upload-Content-Type", "application/octet-stream")
+            .header("X-Upload-Content-Length", size)
+            .json(metadata)
+            .send()
+            .await?;
+
+        let response = Self::check(response, "Start upload session").await?;
+        response.headers().get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .ok_or_else(|| CloudError::ApiError("Upload session has no Location".to_string()))
+    }
+
+    /// Interpret the answer to a chunk upload or status query
+    async fn upload_state(response: Response) -> CloudResult<UploadState> {
+        match response.status().as_u16() {
+            200 | 201 => Ok(UploadState::Complete(response.json().await?)),
+            // Resume Incomplete; "Range: bytes=0-N" is absent when nothing was received yet
+            308 => {
+                let received = response.headers().get(reqwest::header::RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.rsplit('-').next())
+                    .and_then(|end| end.parse::<u64>().ok())
+                    .map(|end| end + 1)
+                    .unwrap_or(0);
+                Ok(UploadState::Partial(received))
+            }
+            401 => Err(CloudError::Unauthenticated),
+            404 | 410 => Err(CloudError::ApiError("Upload session expired".to_string())),
+            _ => {
+                let status = response.status();
+                let error_text = response.text().await?;
+                Err(CloudError::ApiError(format!("Upload failed: {} {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Ask the server how much of a session's content it already has
+    async fn query_upload(&self, session_uri: &str, size: u64) -> CloudResult<UploadState> {
+        let response = self.upload_client
+            .put(session_uri)
+            .bearer_auth(&self.access_token)
+            .header(reqwest::header::CONTENT_RANGE, format!("bytes */{}", size))
+            .header(reqwest::header::CONTENT_LENGTH, 0)
+            .send()
+            .await?;
+        Self::upload_state(response).await
+    }
+
+    /// Stream a file through a resumable session, chunk by chunk from disk.
+    /// A session stored for the same content is resumed from the server's offset.
+    async fn upload_resumable(&self, local_path: &Path, file_id: Option<&str>, metadata: &serde_json::Value, key: &UploadKey, transfer: &Transfer) -> CloudResult<serde_json::Value> {
+        let size = key.size;
+        let mut resumed = None;
+        if let Some(store) = &transfer.sessions {
+            if let Some(uri) = store.load(key).await {
+                match self.query_upload(&uri, size).await {
+                    Ok(UploadState::Complete(file)) => {
+                        store.clear(key).await;
+                        return Ok(file);
+                    }
+                    Ok(UploadState::Partial(offset)) => {
+                        println!("Resuming upload of {:?} at byte {}", local_path, offset);
+                        resumed = Some((uri, offset));
+                    }
+                    Err(CloudError::Unauthenticated) => return Err(CloudError::Unauthenticated),
+                    Err(e) => {
+                        println!("Starting over with {:?}: {}", local_path, e);
+                        store.clear(key).await;
+                    }
+                }
+            }
+        }
+
+        let (session_uri, mut offset) = match resumed {
+            Some(resumed) => resumed,
+            None => {
+                let uri = self.start_upload_session(file_id, metadata, size).await?;
+                if let Some(store) = &transfer.sessions {
+                    store.save(key, &uri).await;
+                }
+                (uri, 0)
+            }
+        };
+
+        let mut file = File::open(local_path).await?;
         loop {
-            let n = file.read(&mut buffer).await?;
-            if n == 0 { break; }
-            hasher.consume(&buffer[..n]);
+            if offset > size {
+                // Not our content; the next attempt starts a new session
+                if let Some(store) = &transfer.sessions {
+                    store.clear(key).await;
+                }
+                return Err(CloudError::ApiError(format!("Upload session has {} bytes of a {} byte file", offset, size)));
+            }
+            transfer.report(offset, size);
+            let state = if offset == size {
+                // Nothing left to send (or an empty file): ask for the finished file
+                match self.query_upload(&session_uri, size).await? {
+                    UploadState::Partial(received) if received == size => {
+                        return Err(CloudError::ApiError("Upload session did not complete".to_string()));
+                    }
+                    state => state,
+                }
+            } else {
+                let len = UPLOAD_CHUNK_SIZE.min(size - offset);
+                let mut chunk = vec![0u8; len as usize];
+                file.seek(SeekFrom::Start(offset)).await?;
+                file.read_exact(&mut chunk).await?;
+
+                let response = self.upload_client
+                    .put(&session_uri)
+                    .bearer_auth(&self.access_token)
+                    .header(reqwest::header::CONTENT_RANGE, format!("bytes {}-{}/{}", offset, offset + len - 1, size))
+                    .body(chunk)
+                    .send()
+                    .await?;
+                Self::upload_state(response).await?
+            };
+
+            match state {
+                UploadState::Complete(uploaded) => {
+                    transfer.report(size, size);
+                    if let Some(store) = &transfer.sessions {
+                        store.clear(key).await;
+                    }
+                    return Ok(uploaded);
+                }
+                // The server may keep less than was sent; continue from what it has
+                UploadState::Partial(received) => offset = received,
+            }
         }
-        let digest = hasher.compute();
-        Ok(format!("{:x}", digest))
     }
 }
 
@@ -75,11 +335,11 @@ impl CloudProvider for GoogleDriveProvider {
         "gdrive"
     }
 
-    async fn upload_file(&self, local_path: &Path, _cloud_path: &str) -> CloudResult<()> {
-        let mut file = File::open(local_path).await?;
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await?;
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        self.upload_file_with(local_path, cloud_path, &Transfer::default()).await
+    }
 
+    async fn upload_file_with(&self, local_path: &Path, cloud_path: &str, transfer: &Transfer) -> CloudResult<RemoteFile> {
         let filename = local_path
             .file_name()
             .and_then(|n| n.to_str())
@@ -88,11 +348,13 @@ impl CloudProvider for GoogleDriveProvider {
         // Check if file already exists
         let local_meta = tokio::fs::metadata(local_path).await?;
         let local_size = local_meta.len();
+        let local_mtime = local_meta.modified().ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
         
         // Get file timestamps for preserving dates
-        let modified_time = local_meta.modified().ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0).unwrap_or_default().to_rfc3339());
+        let modified_time = local_mtime
+            .map(|secs| chrono::DateTime::from_timestamp(secs, 0).unwrap_or_default().to_rfc3339());
         let created_time = local_meta.created().ok()
             .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
             .map(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0).unwrap_or_default().to_rfc3339());
@@ -100,12 +362,13 @@ impl CloudProvider for GoogleDriveProvider {
         // Compute hash for more reliable comparison
         let local_hash = self.compute_local_hash(local_path).await?;
 
-        let existing_info = self.find_file_info(filename, _cloud_path).await?;
+        let existing_info = self.find_file_info(filename, cloud_path).await?;
 
-        if let Some((file_id, r_size, _r_mtime, r_hash)) = existing_info {
+        let mut metadata = serde_json::json!({});
+        let file_id = if let Some((file_id, r_size, r_mtime, r_hash)) = existing_info {
             // Compare hash if available, otherwise fallback to size
-            let matches = if let Some(hash) = r_hash {
-                hash == local_hash
+            let matches = if let Some(hash) = &r_hash {
+                *hash == local_hash
             } else if let Some(size) = r_size {
                 local_size == size
             } else {
@@ -113,98 +376,62 @@ impl CloudProvider for GoogleDriveProvider {
             };
 
             if matches {
-                return Ok(());
-            }
-
-            // Update existing file with metadata (to preserve modifiedTime)
-            let mut metadata = serde_json::json!({});
-            if let Some(ref mtime) = modified_time {
-                metadata["modifiedTime"] = serde_json::json!(mtime);
-            }
-
-            let metadata_part = metadata.to_string();
-            let form = reqwest::multipart::Form::new()
-                .part("metadata", reqwest::multipart::Part::text(metadata_part).mime_str("application/json")?)
-                .part("file", reqwest::multipart::Part::bytes(contents).mime_str("application/octet-stream")?);
-
-            let response = self.client
-                .patch(format!("https://www.googleapis.com/upload/drive/v3/files/{}?uploadType=multipart", file_id))
-                .bearer_auth(&self.access_token)
-                .multipart(form)
-                .send()
-                .await?;
-
-            if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-                return Err(CloudError::Unauthenticated);
-            }
-
-            if !response.status().is_success() {
-                let error_text = response.text().await?;
-                return Err(CloudError::ApiError(format!("Update failed: {}", error_text)));
+                return Ok(RemoteFile {
+                    id: file_id,
+                    name: filename.to_string(),
+                    is_dir: false,
+                    size: r_size,
+                    modified_at: r_mtime,
+                    hash: r_hash,
+                    revision: None,
+                });
             }
-            println!("Updated {} on Google Drive", filename);
+            // Update the existing file in place
+            Some(file_id)
         } else {
-            // Create new file
-            let mut metadata: serde_json::Value = serde_json::json!({
-                "name": filename,
-            });
-
-            if _cloud_path != "root" && !_cloud_path.is_empty() {
-                metadata["parents"] = serde_json::json!([_cloud_path]);
+            metadata["name"] = serde_json::json!(filename);
+            if cloud_path != "root" && !cloud_path.is_empty() {
+                metadata["parents"] = serde_json::json!([cloud_path]);
             }
             if let Some(ref ctime) = created_time {
                 metadata["createdTime"] = serde_json::json!(ctime);
             }
-            if let Some(ref mtime) = modified_time {
-                metadata["modifiedTime"] = serde_json::json!(mtime);
-            }
-
-            let metadata_part = metadata.to_string();
-            let form = reqwest::multipart::Form::new()
-                .part("metadata", reqwest::multipart::Part::text(metadata_part).mime_str("application/json")?)
-                .part("file", reqwest::multipart::Part::bytes(contents).mime_str("application/octet-stream")?);
-
-            let response = self.client
-                .post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart")
-                .bearer_auth(&self.access_token)
-                .multipart(form)
-                .send()
-                .await?;
-
-            if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-                return Err(CloudError::Unauthenticated);
-            }
-
-            if !response.status().is_success() {
-                let error_text = response.text().await?;
-                return Err(CloudError::ApiError(format!("Upload failed: {}", error_text)));
-            }
-            println!("Created {} on Google Drive", filename);
+            None
+        };
+        // Preserve the local modification time
+        if let Some(ref mtime) = modified_time {
+            metadata["modifiedTime"] = serde_json::json!(mtime);
         }
 
-        Ok(())
+        let uploaded = if local_size > RESUMABLE_THRESHOLD {
+            let key = UploadKey {
+                local_path: local_path.to_string_lossy().to_string(),
+                remote_parent: cloud_path.to_string(),
+                size: local_size,
+                modified_at: local_mtime.unwrap_or_default(),
+            };
+            self.upload_resumable(local_path, file_id.as_deref(), &metadata, &key, transfer).await?
+        } else {
+            let mut contents = Vec::new();
+            File::open(local_path).await?.read_to_end(&mut contents).await?;
+            let uploaded = self.upload_multipart(file_id.as_deref(), &metadata, contents).await?;
+            transfer.report(local_size, local_size);
+            uploaded
+        };
+
+        match file_id {
+            Some(_) => println!("Updated {} on Google Drive", filename),
+            None => println!("Created {} on Google Drive", filename),
+        }
+        Ok(Self::parse_remote_file(&uploaded))
     }
 
     async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
-        let response = self.client
-            .get(format!("https://www.googleapis.com/drive/v3/files/{}?alt=media", file_id))
-            .bearer_auth(&self.access_token)
-            .send()
-            .await?;
-
-        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
-            return Err(CloudError::Unauthenticated);
-        }
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(CloudError::ApiError(format!("Download failed: {}", error_text)));
-        }
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
 
-        let bytes = response.bytes().await?;
-        tokio::fs::write(local_path, bytes).await?;
-        
-        Ok(())
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::listed(remote, HashKind::Md5)).await
     }
 
     async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
@@ -220,7 +447,7 @@ impl CloudProvider for GoogleDriveProvider {
         });
 
         let response = self.client
-            .post("https://www.googleapis.com/drive/v3/files")
+            .post(format!("{}/files", self.api_url))
             .bearer_auth(&self.access_token)
             .json(&metadata)
             .send()
@@ -248,7 +475,7 @@ impl CloudProvider for GoogleDriveProvider {
         if let Some((file_id, _, _, _)) = existing_info {
             // DELETE https://www.googleapis.com/drive/v3/files/{fileId}
             let response = self.client
-                .delete(format!("https://www.googleapis.com/drive/v3/files/{}", file_id))
+                .delete(format!("{}/files/{}", self.api_url, file_id))
                 .bearer_auth(&self.access_token)
                 .send()
                 .await?;
@@ -267,6 +494,53 @@ impl CloudProvider for GoogleDriveProvider {
         Ok(())
     }
 
+    fn supports_trash(&self) -> bool {
+        true
+    }
+
+    async fn trash_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<Option<TrashedFile>> {
+        let Some((file_id, _, _, _)) = self.find_file_info(filename, cloud_parent).await? else {
+            return Ok(None);
+        };
+        let file = self.set_trashed(&file_id, true, TRASHED_FIELDS).await?;
+        println!("Moved {} to the Google Drive trash", filename);
+        Ok(Some(Self::parse_trashed_file(&file)))
+    }
+
+    async fn list_trashed(&self) -> CloudResult<Vec<TrashedFile>> {
+        let fields = format!("nextPageToken, files({})", TRASHED_FIELDS);
+        let mut trashed = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut request = self.client
+                .get(format!("{}/files", self.api_url))
+                .query(&[("q", "trashed = true"), ("fields", fields.as_str()), ("pageSize", "1000")])
+                .bearer_auth(&self.access_token);
+            if let Some(token) = &page_token {
+                request = request.query(&[("pageToken", token.as_str())]);
+            }
+            let body: serde_json::Value = Self::check(request.send().await?, "List trash").await?.json().await?;
+
+            // Entries inside a trashed folder are trashed too, but restored with it
+            trashed.extend(body["files"].as_array().into_iter().flatten()
+                .filter(|f| f["explicitlyTrashed"].as_bool().unwrap_or(false))
+                .map(Self::parse_trashed_file));
+
+            match body["nextPageToken"].as_str() {
+                Some(next) => page_token = Some(next.to_string()),
+                None => break,
+            }
+        }
+        Ok(trashed)
+    }
+
+    async fn restore_file(&self, trash_id: &str) -> CloudResult<RemoteFile> {
+        let file = self.set_trashed(trash_id, false, FILE_FIELDS).await?;
+        println!("Restored {} from the Google Drive trash", trash_id);
+        Ok(Self::parse_remote_file(&file))
+    }
+
     async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<crate::provider::RemoteFile>> {
         let q = format!("'{}' in parents and trashed = false", folder_id);
         let mut all_files = Vec::new();
@@ -274,7 +548,7 @@ impl CloudProvider for GoogleDriveProvider {
 
         loop {
             let mut request = self.client
-                .get("https://www.googleapis.com/drive/v3/files")
+                .get(format!("{}/files", self.api_url))
                 .query(&[("q", q.as_str()), ("fields", "nextPageToken, files(id, name, mimeType, size, modifiedTime, md5Checksum)"), ("pageSize", "1000")])
                 .bearer_auth(&self.access_token);
 
@@ -297,24 +571,7 @@ impl CloudProvider for GoogleDriveProvider {
             let files_json = body["files"].as_array().ok_or_else(|| CloudError::ApiError("Invalid body".to_string()))?;
             
             for f in files_json {
-                let id = f["id"].as_str().unwrap_or_default().to_string();
-                let name = f["name"].as_str().unwrap_or_default().to_string();
-                let mime_type = f["mimeType"].as_str().unwrap_or_default();
-                let is_dir = mime_type == "application/vnd.google-apps.folder";
-                let size = f["size"].as_str().and_then(|s| s.parse().ok());
-                let hash = f["md5Checksum"].as_str().map(|s| s.to_string());
-                let modified_at = f["modifiedTime"].as_str().and_then(|s| {
-                    chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp())
-                });
-
-                all_files.push(crate::provider::RemoteFile { 
-                    id, 
-                    name, 
-                    is_dir,
-                    size,
-                    modified_at,
-                    hash,
-                });
+                all_files.push(Self::parse_remote_file(f));
             }
 
             if let Some(next_token) = body["nextPageToken"].as_str() {
@@ -337,7 +594,7 @@ impl CloudProvider for GoogleDriveProvider {
 
     async fn list_folders(&self) -> CloudResult<Vec<crate::provider::RemoteFolder>> {
         let response = self.client
-            .get("https://www.googleapis.com/drive/v3/files")
+            .get(format!("{}/files", self.api_url))
             .query(&[
                 ("q", "mimeType='application/vnd.google-apps.folder' and trashed=false"),
                 ("fields", "files(id, name)"),
@@ -366,4 +623,162 @@ impl CloudProvider for GoogleDriveProvider {
 
         Ok(folders)
     }
+
+    async fn move_file(&self, file_id: &str, from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let mut request = self.client
+            .patch(format!("{}/files/{}", self.api_url, file_id))
+            .query(&[("fields", FILE_FIELDS)]);
+        if from_parent != to_parent {
+            request = request.query(&[("addParents", to_parent), ("removeParents", from_parent)]);
+        }
+        let response = request
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({ "name": new_name }))
+            .send()
+            .await?;
+
+        let file: serde_json::Value = Self::check(response, "Move").await?.json().await?;
+        println!("Moved {} to {} on Google Drive", file_id, new_name);
+        Ok(Some(Self::parse_remote_file(&file)))
+    }
+
+    async fn changes_since(&self, cursor: Option<&str>) -> CloudResult<Option<ChangeSet>> {
+        let Some(cursor) = cursor else {
+            let body = self.get_json(&format!("{}/changes/startPageToken", self.api_url)).await?;
+            let token = body["startPageToken"].as_str()
+                .ok_or_else(|| CloudError::ApiError("Invalid start page token response".to_string()))?;
+            return Ok(Some(ChangeSet { changes: Vec::new(), cursor: token.to_string() }));
+        };
+
+        // Parents name My Drive by its real id; pairs usually store the "root" alias
+        let root_id = self.get_json(&format!("{}/files/root?fields=id", self.api_url)).await?["id"]
+            .as_str().unwrap_or_default().to_string();
+
+        let mut changes = Vec::new();
+        let mut page_token = cursor.to_string();
+        loop {
+            let response = self.client
+                .get(format!("{}/changes", self.api_url))
+                .query(&[
+                    ("pageToken", page_token.as_str()),
+                    ("pageSize", "1000"),
+                    ("includeRemoved", "true"),
+                    ("fields", "nextPageToken,newStartPageToken,changes(fileId,removed,file(name,parents,trashed))"),
+                ])
+                .bearer_auth(&self.access_token)
+                .send()
+                .await?;
+            // An expired or malformed token is rejected outright
+            if matches!(response.status(), StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::GONE) {
+                return Err(CloudError::InvalidCursor);
+            }
+            let body: serde_json::Value = Self::check(response, "List changes").await?.json().await?;
+
+            for change in body["changes"].as_array().into_iter().flatten() {
+                let Some(file_id) = change["fileId"].as_str() else { continue };
+                let removed = change["removed"].as_bool().unwrap_or(false)
+                    || change["file"]["trashed"].as_bool().unwrap_or(false);
+                let parents = if removed {
+                    Vec::new()
+                } else {
+                    change["file"]["parents"].as_array().into_iter().flatten()
+                        .filter_map(|p| p.as_str())
+                        .map(|p| if p == root_id { "root".to_string() } else { p.to_string() })
+                        .collect()
+                };
+                let name = if removed { None } else { change["file"]["name"].as_str().map(|n| n.to_string()) };
+                changes.push(RemoteChange { file_id: file_id.to_string(), removed, name, parents });
+            }
+
+            if let Some(next) = body["nextPageToken"].as_str() {
+                page_token = next.to_string();
+            } else {
+                let cursor = body["newStartPageToken"].as_str()
+                    .ok_or_else(|| CloudError::ApiError("Change list has no new start page token".to_string()))?;
+                return Ok(Some(ChangeSet { changes, cursor: cursor.to_string() }));
+            }
+        }
+    }
+}
+
+/// Authorization URL for the PKCE code flow
+pub fn authorize_url(client_id: &str, redirect_uri: &str, code_challenge: &str) -> String {
+    let mut url = url::Url::parse(AUTHORIZE_URL).expect("valid authorize URL");
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("response_type", "code")
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("scope", SCOPES)
+        .append_pair("access_type", "offline")
+        .append_pair("prompt", "consent")
+        .append_pair("code_challenge", code_challenge)
+        .append_pair("code_challenge_method", "S256");
+    url.to_string()
+}
+
+/// Google sign-in for Drive accounts. Google wants the client secret even with PKCE.
+pub struct GoogleAuth {
+    client_id: String,
+    client_secret: String,
+}
+
+impl GoogleAuth {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self { client_id, client_secret }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for GoogleAuth {
+    fn authorize_url(&self, redirect_uri: &str, code_challenge: &str) -> anyhow::Result<String> {
+        check_client_id(&self.client_id, "Google")?;
+        Ok(authorize_url(&self.client_id, redirect_uri, code_challenge))
+    }
+
+    async fn exchange_code(&self, code: &str, redirect_uri: &str, code_verifier: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("code", code),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", code_verifier),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token exchange").await
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token refresh").await
+    }
+
+    async fn user_info(&self, access_token: &str) -> anyhow::Result<UserInfo> {
+        let response = Client::new().get(format!("{}/about?fields=user", API_URL))
+            .bearer_auth(access_token)
+            .send()
+            .await?;
+        let body: serde_json::Value = response.error_for_status()?.json().await?;
+        let user = &body["user"];
+        Ok(UserInfo {
+            name: user["displayName"].as_str().map(|s| s.to_string()),
+            email: user["emailAddress"].as_str().map(|s| s.to_string()),
+            avatar: user["photoLink"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    async fn revoke(&self, access_token: &str, refresh_token: Option<&str>) -> anyhow::Result<()> {
+        // Revoking the refresh token ends the whole grant
+        let token = refresh_token.unwrap_or(access_token);
+        Client::new().post(REVOKE_URL)
+            .form(&[("token", token)])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 }
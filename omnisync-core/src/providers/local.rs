@@ -0,0 +1,327 @@
+use crate::download::{commit_part, part_path, Expected};
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, RemoteFile, RemoteFolder, TrashedFile};
+use crate::registry::{ConnectedAccount, ConnectionForm};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Upper bound on folders returned by `list_folders`, which walks the tree one level at a time
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Bookkeeping folder in the target directory, left out of listings
+const META_DIR: &str = ".omnisync";
+/// Trashed entries, named "<trashed at, ns>-<original id with '/' as %2F>"
+const TRASH_DIR: &str = ".omnisync/trash";
+
+fn mtime_secs(meta: &std::fs::Metadata) -> Option<i64> {
+    meta.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+}
+
+/// Settings for a local target directory, stored as JSON in `credentials.config`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalConfig {
+    /// Directory to mirror into, e.g. a NAS mount or a USB drive
+    pub path: String,
+}
+
+impl LocalConfig {
+    /// Account identifier, e.g. "local:/mnt/backup"
+    pub fn account_id(&self) -> String {
+        format!("local:{}", self.path)
+    }
+}
+
+/// Sets up local-folder accounts from a `LocalConfig`
+pub struct LocalForm;
+
+#[async_trait]
+impl ConnectionForm for LocalForm {
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount> {
+        let local: LocalConfig = serde_json::from_value(config.clone())?;
+        let name = Path::new(&local.path).file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| local.path.clone());
+        Ok(ConnectedAccount { account_id: local.account_id(), user_name: name, user_email: local.path, config })
+    }
+}
+
+/// Another directory on this machine, treated as the "cloud" side of a pair.
+/// Ids are '/' separated paths relative to the target directory ("photos/2024",
+/// "photos/2024/a.jpg"), and the root is "" ("root" and "/" are aliases).
+/// Listings report no hash, so changes are detected by size and modification time
+/// instead of re-reading every file on every poll.
+pub struct LocalProvider {
+    root: PathBuf,
+}
+
+impl LocalProvider {
+    pub fn new(config: LocalConfig) -> Self {
+        Self { root: PathBuf::from(config.path) }
+    }
+
+    fn normalize(id: &str) -> &str {
+        match id.trim_matches('/') {
+            "root" => "",
+            p => p,
+        }
+    }
+
+    fn join(parent: &str, name: &str) -> String {
+        match Self::normalize(parent) {
+            "" => name.to_string(),
+            p => format!("{}/{}", p, name),
+        }
+    }
+
+    /// Filesystem path for an id. Ids never escape the target directory.
+    fn full_path(&self, id: &str) -> CloudResult<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in Self::normalize(id).split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if segment == ".." {
+                return Err(CloudError::Other(anyhow!("Invalid path: {}", id)));
+            }
+            path.push(segment);
+        }
+        Ok(path)
+    }
+
+    /// Trash entry name for an id, and back
+    fn trash_name(id: &str, nanos: u128) -> String {
+        format!("{}-{}", nanos, id.replace('%', "%25").replace('/', "%2F"))
+    }
+
+    fn parse_trash_name(name: &str) -> Option<(String, i64)> {
+        let (nanos, id) = name.split_once('-')?;
+        let secs = (nanos.parse::<u128>().ok()? / 1_000_000_000) as i64;
+        Some((id.replace("%2F", "/").replace("%25", "%"), secs))
+    }
+
+    fn trashed_file(name: &str, is_dir: bool) -> Option<TrashedFile> {
+        let (id, trashed_at) = Self::parse_trash_name(name)?;
+        let (parent, file_name) = id.rsplit_once('/').unwrap_or(("", &id));
+        Some(TrashedFile {
+            id: name.to_string(),
+            name: file_name.to_string(),
+            is_dir,
+            parent: Some(parent.to_string()),
+            trashed_at: Some(trashed_at),
+        })
+    }
+
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        let source = self.full_path(file_id)?;
+        let temp = part_path(local_path)?;
+
+        // Keep the source modification time, as uploads do
+        let copied: CloudResult<()> = async {
+            tokio::fs::copy(&source, &temp).await?;
+            let modified = tokio::fs::metadata(&source).await?.modified()?;
+            std::fs::File::options().write(true).open(&temp)?.set_modified(modified)?;
+            Ok(())
+        }.await;
+        if let Err(e) = copied {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+        commit_part(local_path, expected).await
+    }
+
+    fn to_remote(id: String, meta: &std::fs::Metadata) -> RemoteFile {
+        RemoteFile {
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            is_dir: meta.is_dir(),
+            size: if meta.is_dir() { None } else { Some(meta.len()) },
+            modified_at: mtime_secs(meta),
+            hash: None,
+            revision: None,
+            id,
+        }
+    }
+}
+
+#[async_trait]
+impl CloudProvider for LocalProvider {
+    fn id(&self) -> &str {
+        "local"
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+        let id = Self::join(cloud_path, filename);
+        let dest = self.full_path(&id)?;
+
+        // Copy next to the destination and rename, so the target never holds a partial file
+        let temp = dest.with_file_name(format!(".{}.omnisync-part", filename));
+        tokio::fs::copy(local_path, &temp).await?;
+        let modified = tokio::fs::metadata(local_path).await?.modified()?;
+        let file = std::fs::File::options().write(true).open(&temp)?;
+        file.set_modified(modified)?;
+        drop(file);
+        tokio::fs::rename(&temp, &dest).await?;
+
+        println!("Copied {} to {:?}", filename, dest);
+        let meta = tokio::fs::metadata(&dest).await?;
+        Ok(Self::to_remote(id, &meta))
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::size_of(remote)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let path = self.full_path(&Self::join(cloud_parent, filename))?;
+        let result = match tokio::fs::symlink_metadata(&path).await {
+            Ok(meta) if meta.is_dir() => tokio::fs::remove_dir_all(&path).await,
+            Ok(_) => tokio::fs::remove_file(&path).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+            Ok(()) => {
+                println!("Deleted {:?}", path);
+                Ok(())
+            }
+        }
+    }
+
+    fn supports_trash(&self) -> bool {
+        true
+    }
+
+    async fn trash_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<Option<TrashedFile>> {
+        let id = Self::join(cloud_parent, filename);
+        let path = self.full_path(&id)?;
+        let meta = match tokio::fs::symlink_metadata(&path).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            meta => meta?,
+        };
+
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let name = Self::trash_name(&id, nanos);
+        let trash = self.root.join(TRASH_DIR);
+        tokio::fs::create_dir_all(&trash).await?;
+        tokio::fs::rename(&path, trash.join(&name)).await?;
+        println!("Moved {:?} to the trash", path);
+        Ok(Self::trashed_file(&name, meta.is_dir()))
+    }
+
+    async fn list_trashed(&self) -> CloudResult<Vec<TrashedFile>> {
+        let mut entries = match tokio::fs::read_dir(self.root.join(TRASH_DIR)).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            entries => entries?,
+        };
+        let mut trashed = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(|n| n.to_string()) else { continue };
+            let is_dir = entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false);
+            trashed.extend(Self::trashed_file(&name, is_dir));
+        }
+        trashed.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(trashed)
+    }
+
+    async fn restore_file(&self, trash_id: &str) -> CloudResult<RemoteFile> {
+        let (id, _) = Self::parse_trash_name(trash_id)
+            .filter(|_| !trash_id.contains('/'))
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid trash id: {}", trash_id)))?;
+        let dest = self.full_path(&id)?;
+        if tokio::fs::symlink_metadata(&dest).await.is_ok() {
+            return Err(CloudError::Other(anyhow!("{} already exists", id)));
+        }
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(self.root.join(TRASH_DIR).join(trash_id), &dest).await?;
+
+        println!("Restored {:?} from the trash", dest);
+        let meta = tokio::fs::metadata(&dest).await?;
+        Ok(Self::to_remote(id, &meta))
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let path = self.full_path(cloud_path)?;
+        let meta = tokio::fs::metadata(&path).await?;
+        let remote = Self::to_remote(Self::normalize(cloud_path).to_string(), &meta);
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let folder = Self::normalize(folder_id).to_string();
+        let mut entries = tokio::fs::read_dir(self.full_path(&folder)?).await?;
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(|n| n.to_string()) else { continue };
+            if folder.is_empty() && name == META_DIR {
+                continue;
+            }
+            // Symlinks are followed; dangling ones are skipped
+            let Ok(meta) = tokio::fs::metadata(entry.path()).await else { continue };
+            files.push(Self::to_remote(Self::join(&folder, &name), &meta));
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        let mut folders = Vec::new();
+        let mut queue: VecDeque<String> = VecDeque::from([String::new()]);
+
+        while let Some(id) = queue.pop_front() {
+            let mut entries = tokio::fs::read_dir(self.full_path(&id)?).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let Some(name) = entry.file_name().to_str().map(|n| n.to_string()) else { continue };
+                if id.is_empty() && name == META_DIR {
+                    continue;
+                }
+                if !tokio::fs::metadata(entry.path()).await.map(|m| m.is_dir()).unwrap_or(false) {
+                    continue;
+                }
+                let child = Self::join(&id, &name);
+                folders.push(RemoteFolder { id: child.clone(), name: child.clone() });
+                if folders.len() >= MAX_LISTED_FOLDERS {
+                    return Ok(folders);
+                }
+                queue.push_back(child);
+            }
+        }
+
+        Ok(folders)
+    }
+
+    async fn move_file(&self, file_id: &str, _from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let id = Self::join(to_parent, new_name);
+        let dest = self.full_path(&id)?;
+        if tokio::fs::symlink_metadata(&dest).await.is_ok() {
+            return Err(CloudError::Other(anyhow!("{} already exists", id)));
+        }
+        tokio::fs::rename(self.full_path(file_id)?, &dest).await?;
+
+        println!("Moved {} to {:?}", file_id, dest);
+        let meta = tokio::fs::metadata(&dest).await?;
+        Ok(Some(Self::to_remote(id, &meta)))
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let id = Self::join(parent_id, name);
+        tokio::fs::create_dir_all(self.full_path(&id)?).await?;
+        Ok(id)
+    }
+}
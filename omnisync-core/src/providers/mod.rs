@@ -1 +1,7 @@
 pub mod gdrive;
+pub mod onedrive;
+pub mod dropbox;
+pub mod s3;
+pub mod webdav;
+pub mod sftp;
+pub mod local;
@@ -0,0 +1,404 @@
+use crate::auth::{check_client_id, request_tokens, AuthProvider, TokenResponse, UserInfo};
+use crate::download::{save_response, Expected};
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteFile, RemoteFolder};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{Client, Response, StatusCode};
+use std::collections::VecDeque;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0";
+pub const AUTHORIZE_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/authorize";
+pub const TOKEN_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/token";
+pub const SCOPES: &str = "offline_access Files.ReadWrite.All User.Read";
+
+const ITEM_SELECT: &str = "id,name,size,lastModifiedDateTime,file,folder";
+/// Files up to this size are sent in a single PUT, larger ones through an upload session
+const SIMPLE_UPLOAD_LIMIT: u64 = 4 * 1024 * 1024;
+/// Upload session fragments must be a multiple of 320 KiB
+const UPLOAD_CHUNK_SIZE: usize = 32 * 320 * 1024;
+/// Upper bound on folders returned by `list_folders`, which walks the whole tree
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Characters escaped when a file name is used in a path-addressed URL
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'/').add(b':').add(b'<').add(b'>')
+    .add(b'?').add(b'[').add(b']').add(b'\\').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}');
+
+/// Microsoft OneDrive (personal and business) through Microsoft Graph.
+/// Folder and file ids are drive item ids; "root" addresses the drive root.
+pub struct OneDriveProvider {
+    client: Client,
+    access_token: String,
+    base_url: String,
+}
+
+impl OneDriveProvider {
+    pub fn new(access_token: String) -> Self {
+        Self::with_base_url(access_token, GRAPH_URL)
+    }
+
+    /// Use a different Graph endpoint, e.g. a national cloud or a local mock server
+    pub fn with_base_url(access_token: String, base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            access_token,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn item_url(&self, id: &str) -> String {
+        if id.is_empty() || id == "root" {
+            format!("{}/me/drive/root", self.base_url)
+        } else {
+            format!("{}/me/drive/items/{}", self.base_url, id)
+        }
+    }
+
+    /// Address a child of `parent_id` by name: `.../items/{parent}:/{name}`
+    fn child_url(&self, parent_id: &str, name: &str) -> String {
+        format!("{}:/{}", self.item_url(parent_id), utf8_percent_encode(name, PATH_SEGMENT))
+    }
+
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        // Graph answers with a redirect to a pre-authenticated download URL
+        let response = self.client
+            .get(format!("{}/content", self.item_url(file_id)))
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+        save_response(Self::check(response, "Download").await?, local_path, expected).await
+    }
+
+    async fn check(response: Response, action: &str) -> CloudResult<Response> {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(CloudError::Unauthenticated);
+        }
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(CloudError::ApiError(format!("{} failed: {}", action, error_text)));
+        }
+        Ok(response)
+    }
+
+    fn parse_item(item: &serde_json::Value) -> RemoteFile {
+        RemoteFile {
+            id: item["id"].as_str().unwrap_or_default().to_string(),
+            name: item["name"].as_str().unwrap_or_default().to_string(),
+            is_dir: item["folder"].is_object(),
+            size: item["size"].as_u64(),
+            modified_at: item["lastModifiedDateTime"].as_str().and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.timestamp())
+            }),
+            hash: item["file"]["hashes"]["quickXorHash"].as_str().map(|s| s.to_string()),
+            revision: None,
+        }
+    }
+
+    /// Fetch every page of a children listing
+    async fn list_children(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let mut all_files = Vec::new();
+        let mut next = Some(format!("{}/children?$select={}&$top=1000", self.item_url(folder_id), ITEM_SELECT));
+
+        while let Some(url) = next {
+            let response = self.client
+                .get(&url)
+                .bearer_auth(&self.access_token)
+                .send()
+                .await?;
+            let body: serde_json::Value = Self::check(response, "List files").await?.json().await?;
+            let items = body["value"].as_array().ok_or_else(|| CloudError::ApiError("Invalid body".to_string()))?;
+            all_files.extend(items.iter().map(Self::parse_item));
+            next = body["@odata.nextLink"].as_str().map(|s| s.to_string());
+        }
+
+        Ok(all_files)
+    }
+
+    async fn upload_small(&self, local_path: &Path, parent_id: &str, filename: &str) -> CloudResult<RemoteFile> {
+        let contents = tokio::fs::read(local_path).await?;
+        let response = self.client
+            .put(format!("{}:/content", self.child_url(parent_id, filename)))
+            .bearer_auth(&self.access_token)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(contents)
+            .send()
+            .await?;
+
+        let item: serde_json::Value = Self::check(response, "Upload").await?.json().await?;
+        Ok(Self::parse_item(&item))
+    }
+
+    /// Upload through a resumable upload session in 320 KiB-aligned fragments
+    async fn upload_large(&self, local_path: &Path, parent_id: &str, filename: &str, size: u64, modified_time: Option<String>) -> CloudResult<RemoteFile> {
+        let mut item = serde_json::json!({ "@microsoft.graph.conflictBehavior": "replace" });
+        if let Some(mtime) = modified_time {
+            item["fileSystemInfo"] = serde_json::json!({ "lastModifiedDateTime": mtime });
+        }
+
+        let response = self.client
+            .post(format!("{}:/createUploadSession", self.child_url(parent_id, filename)))
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({ "item": item }))
+            .send()
+            .await?;
+        let session: serde_json::Value = Self::check(response, "Create upload session").await?.json().await?;
+        let upload_url = session["uploadUrl"].as_str()
+            .ok_or_else(|| CloudError::ApiError("No uploadUrl returned".to_string()))?
+            .to_string();
+
+        let mut file = tokio::fs::File::open(local_path).await?;
+        let mut offset: u64 = 0;
+        loop {
+            let mut chunk = Vec::with_capacity(UPLOAD_CHUNK_SIZE);
+            (&mut file).take(UPLOAD_CHUNK_SIZE as u64).read_to_end(&mut chunk).await?;
+            if chunk.is_empty() {
+                return Err(CloudError::ApiError("Upload session ended before the file was complete".to_string()));
+            }
+            let end = offset + chunk.len() as u64 - 1;
+
+            // The upload URL is pre-authenticated; Graph rejects an Authorization header here
+            let response = self.client
+                .put(&upload_url)
+                .header(reqwest::header::CONTENT_RANGE, format!("bytes {}-{}/{}", offset, end, size))
+                .body(chunk)
+                .send()
+                .await?;
+
+            match response.status() {
+                StatusCode::ACCEPTED => offset = end + 1,
+                StatusCode::OK | StatusCode::CREATED => {
+                    let item: serde_json::Value = response.json().await?;
+                    return Ok(Self::parse_item(&item));
+                }
+                _ => {
+                    let error_text = response.text().await?;
+                    let _ = self.client.delete(&upload_url).send().await;
+                    return Err(CloudError::ApiError(format!("Upload fragment failed: {}", error_text)));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CloudProvider for OneDriveProvider {
+    fn id(&self) -> &str {
+        "onedrive"
+    }
+
+    fn hash_kind(&self) -> HashKind {
+        HashKind::QuickXor
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+
+        let local_meta = tokio::fs::metadata(local_path).await?;
+        let remote = if local_meta.len() <= SIMPLE_UPLOAD_LIMIT {
+            self.upload_small(local_path, cloud_path, filename).await?
+        } else {
+            let modified_time = local_meta.modified().ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0).unwrap_or_default().to_rfc3339());
+            self.upload_large(local_path, cloud_path, filename, local_meta.len(), modified_time).await?
+        };
+
+        println!("Uploaded {} to OneDrive", filename);
+        Ok(remote)
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::listed(remote, HashKind::QuickXor)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let response = self.client
+            .delete(self.child_url(cloud_parent, filename))
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check(response, "Delete").await?;
+        println!("Deleted {} from OneDrive", filename);
+        Ok(())
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let response = self.client
+            .get(format!("{}?$select={}", self.item_url(cloud_path), ITEM_SELECT))
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        let item: serde_json::Value = Self::check(response, "Get metadata").await?.json().await?;
+        let remote = Self::parse_item(&item);
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        self.list_children(folder_id).await
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        // Graph has no flat "all folders" query, so walk the tree breadth-first
+        let mut folders = Vec::new();
+        let mut queue: VecDeque<(String, String)> = VecDeque::from([("root".to_string(), String::new())]);
+
+        while let Some((id, prefix)) = queue.pop_front() {
+            for child in self.list_children(&id).await?.into_iter().filter(|f| f.is_dir) {
+                let name = if prefix.is_empty() { child.name.clone() } else { format!("{}/{}", prefix, child.name) };
+                folders.push(RemoteFolder { id: child.id.clone(), name: name.clone() });
+                if folders.len() >= MAX_LISTED_FOLDERS {
+                    return Ok(folders);
+                }
+                queue.push_back((child.id, name));
+            }
+        }
+
+        Ok(folders)
+    }
+
+    async fn move_file(&self, file_id: &str, from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let mut body = serde_json::json!({ "name": new_name });
+        if from_parent != to_parent {
+            body["parentReference"] = if to_parent.is_empty() || to_parent == "root" {
+                serde_json::json!({ "path": "/drive/root:" })
+            } else {
+                serde_json::json!({ "id": to_parent })
+            };
+        }
+        let response = self.client
+            .patch(format!("{}?$select={}", self.item_url(file_id), ITEM_SELECT))
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await?;
+
+        let item: serde_json::Value = Self::check(response, "Move").await?.json().await?;
+        println!("Moved {} to {} on OneDrive", file_id, new_name);
+        Ok(Some(Self::parse_item(&item)))
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let metadata = serde_json::json!({
+            "name": name,
+            "folder": {},
+            "@microsoft.graph.conflictBehavior": "fail"
+        });
+
+        let response = self.client
+            .post(format!("{}/children", self.item_url(parent_id)))
+            .bearer_auth(&self.access_token)
+            .json(&metadata)
+            .send()
+            .await?;
+
+        // Already exists: return the existing folder's id
+        let response = if response.status() == StatusCode::CONFLICT {
+            self.client
+                .get(format!("{}?$select={}", self.child_url(parent_id, name), ITEM_SELECT))
+                .bearer_auth(&self.access_token)
+                .send()
+                .await?
+        } else {
+            response
+        };
+
+        let item: serde_json::Value = Self::check(response, "Create folder").await?.json().await?;
+        let id = item["id"].as_str().ok_or_else(|| CloudError::ApiError("No ID returned".to_string()))?.to_string();
+
+        println!("Created folder {} on OneDrive", name);
+        Ok(id)
+    }
+}
+
+/// Authorization URL for the PKCE code flow
+pub fn authorize_url(client_id: &str, redirect_uri: &str, code_challenge: &str) -> String {
+    let mut url = url::Url::parse(AUTHORIZE_URL).expect("valid authorize URL");
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("response_type", "code")
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("response_mode", "query")
+        .append_pair("scope", SCOPES)
+        .append_pair("code_challenge", code_challenge)
+        .append_pair("code_challenge_method", "S256");
+    url.to_string()
+}
+
+/// Microsoft sign-in for OneDrive accounts (public client, no secret). Microsoft has no
+/// endpoint to revoke a single grant, so `revoke` does nothing.
+pub struct OneDriveAuth {
+    client_id: String,
+}
+
+impl OneDriveAuth {
+    pub fn new(client_id: String) -> Self {
+        Self { client_id }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for OneDriveAuth {
+    fn authorize_url(&self, redirect_uri: &str, code_challenge: &str) -> anyhow::Result<String> {
+        check_client_id(&self.client_id, "OneDrive")?;
+        Ok(authorize_url(&self.client_id, redirect_uri, code_challenge))
+    }
+
+    async fn exchange_code(&self, code: &str, redirect_uri: &str, code_verifier: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("code", code),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", code_verifier),
+            ("scope", SCOPES),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token exchange").await
+    }
+
+    /// Microsoft rotates refresh tokens, so the answer carries a new one
+    async fn refresh(&self, refresh_token: &str) -> anyhow::Result<TokenResponse> {
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+            ("scope", SCOPES),
+        ];
+        request_tokens(TOKEN_URL, &params, "Token refresh").await
+    }
+
+    async fn user_info(&self, access_token: &str) -> anyhow::Result<UserInfo> {
+        let response = Client::new().get(format!("{}/me?$select=displayName,mail,userPrincipalName", GRAPH_URL))
+            .bearer_auth(access_token)
+            .send()
+            .await?;
+        let user: serde_json::Value = response.error_for_status()?.json().await?;
+        Ok(UserInfo {
+            name: user["displayName"].as_str().map(|s| s.to_string()),
+            // Personal accounts often have no `mail`; the UPN is the sign-in address
+            email: user["mail"].as_str()
+                .or_else(|| user["userPrincipalName"].as_str())
+                .map(|s| s.to_string()),
+            avatar: None,
+        })
+    }
+}
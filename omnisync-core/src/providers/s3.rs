@@ -0,0 +1,565 @@
+use crate::download::{save_response, Expected};
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteFile, RemoteFolder};
+use crate::registry::{ConnectedAccount, ConnectionForm};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Multipart part size. Files larger than this are uploaded in parts, and the
+/// local ETag computation (`HashKind::S3Etag`) assumes the same size.
+pub const PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Sort an object's ETag into a content hash, when it is one `HashKind::S3Etag` reproduces,
+/// or a revision that only tells whether the object changed. That takes a plain MD5 for
+/// an object of one part, or the part count `PART_SIZE` gives for its size, and no
+/// SSE-KMS or SSE-C encryption. Listings do not say whether an object is encrypted; a
+/// wrong hash there only costs a download to compare contents.
+fn etag_fields(etag: Option<String>, size: u64, encrypted: bool) -> (Option<String>, Option<String>) {
+    let Some(etag) = etag.map(|e| e.trim_matches('"').to_string()) else {
+        return (None, None);
+    };
+    let is_md5 = |s: &str| s.len() == 32 && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let verifiable = !encrypted && match etag.split_once('-') {
+        None => is_md5(&etag) && size <= PART_SIZE,
+        Some((digest, parts)) => is_md5(digest) && size > PART_SIZE && parts.parse() == Ok(size.div_ceil(PART_SIZE)),
+    };
+    if verifiable { (Some(etag), None) } else { (None, Some(etag)) }
+}
+
+/// Upper bound on folders returned by `list_folders`, which walks every prefix
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Connection settings for an S3-compatible bucket, stored as JSON in `credentials.config`.
+/// The secret access key is stored separately, in `credentials.access_token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    /// e.g. "https://s3.eu-west-1.amazonaws.com" or "http://localhost:9000"
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    /// Address the bucket as `{endpoint}/{bucket}` instead of `{bucket}.{host}`.
+    /// MinIO and most self-hosted servers need this.
+    #[serde(default = "default_path_style")]
+    pub path_style: bool,
+}
+
+fn default_path_style() -> bool {
+    true
+}
+
+impl S3Config {
+    /// Account identifier, e.g. "s3:backups@minio.local:9000"
+    pub fn account_id(&self) -> String {
+        let host = url::Url::parse(&self.endpoint).ok()
+            .and_then(|u| u.host_str().map(|h| match u.port() {
+                Some(port) => format!("{}:{}", h, port),
+                None => h.to_string(),
+            }))
+            .unwrap_or_else(|| self.endpoint.clone());
+        format!("s3:{}@{}", self.bucket, host)
+    }
+}
+
+/// Sets up S3 accounts from an `S3Config`
+pub struct S3Form;
+
+#[async_trait]
+impl ConnectionForm for S3Form {
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount> {
+        let s3: S3Config = serde_json::from_value(config.clone())?;
+        Ok(ConnectedAccount { account_id: s3.account_id(), user_name: s3.bucket, user_email: s3.endpoint, config })
+    }
+}
+
+/// S3-compatible object storage (AWS S3, MinIO, Cloudflare R2, Wasabi, ...).
+/// Folders are key prefixes: a folder id is a prefix ending in "/" ("photos/2024/"),
+/// a file id is the full object key, and the bucket root is "" ("root" and "/" are aliases).
+/// `create_folder` writes an empty "name/" marker object so empty folders survive.
+pub struct S3Provider {
+    client: Client,
+    config: S3Config,
+    secret_access_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<ObjectEntry>,
+    #[serde(default)]
+    common_prefixes: Vec<CommonPrefix>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObjectEntry {
+    key: String,
+    size: u64,
+    last_modified: Option<String>,
+    #[serde(rename = "ETag")]
+    etag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CommonPrefix {
+    prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct S3ErrorBody {
+    code: String,
+    message: Option<String>,
+}
+
+impl S3Provider {
+    pub fn new(config: S3Config, secret_access_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+            secret_access_key,
+        }
+    }
+
+    /// Normalize a folder id into a key prefix: "" for the root, otherwise ending in "/"
+    fn prefix(folder_id: &str) -> String {
+        match folder_id.trim_start_matches('/') {
+            "" | "root" => String::new(),
+            p if p.ends_with('/') => p.to_string(),
+            p => format!("{}/", p),
+        }
+    }
+
+    fn object_url(&self, key: &str) -> CloudResult<url::Url> {
+        let mut url = url::Url::parse(&self.config.endpoint)
+            .map_err(|e| CloudError::Other(anyhow!("Invalid endpoint: {}", e)))?;
+        if self.config.path_style {
+            url.set_path(&format!("/{}/{}", self.config.bucket, uri_encode(key, false)));
+        } else {
+            let host = format!("{}.{}", self.config.bucket, url.host_str().unwrap_or_default());
+            url.set_host(Some(&host)).map_err(|e| CloudError::Other(anyhow!("Invalid endpoint: {}", e)))?;
+            url.set_path(&format!("/{}", uri_encode(key, false)));
+        }
+        Ok(url)
+    }
+
+    /// Send a SigV4-signed request for `key` (an empty key addresses the bucket itself)
+    async fn send(&self, method: Method, key: &str, query: &[(&str, &str)], body: Vec<u8>, headers: &[(&str, String)]) -> CloudResult<Response> {
+        let mut url = self.object_url(key)?;
+        let canonical_query = canonical_query(query);
+        url.set_query(if canonical_query.is_empty() { None } else { Some(&canonical_query) });
+
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex(&Sha256::digest(&body));
+
+        let mut signed: Vec<(String, String)> = headers.iter().map(|(k, v)| (k.to_lowercase(), v.clone())).collect();
+        signed.push(("x-amz-content-sha256".to_string(), payload_hash.clone()));
+        signed.push(("x-amz-date".to_string(), amz_date.clone()));
+
+        let authorization = authorization_header(
+            method.as_str(), &url, &signed, &payload_hash,
+            &self.config.access_key_id, &self.secret_access_key, &self.config.region, &amz_date,
+        );
+
+        let mut request = self.client.request(method, url).header(reqwest::header::AUTHORIZATION, authorization);
+        for (name, value) in &signed {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        Ok(request.body(body).send().await?)
+    }
+
+    async fn check(response: Response, action: &str) -> CloudResult<Response> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let error_text = response.text().await?;
+        let error: Option<S3ErrorBody> = quick_xml::de::from_str(&error_text).ok();
+        match error {
+            Some(e) if matches!(e.code.as_str(), "InvalidAccessKeyId" | "SignatureDoesNotMatch" | "ExpiredToken") => {
+                Err(CloudError::Unauthenticated)
+            }
+            Some(e) => Err(CloudError::ApiError(format!("{} failed: {} {}", action, e.code, e.message.unwrap_or_default()))),
+            None => Err(CloudError::ApiError(format!("{} failed: {} {}", action, status, error_text))),
+        }
+    }
+
+    /// One level of a prefix listing: (objects, sub-prefixes), following continuation tokens
+    async fn list_level(&self, prefix: &str) -> CloudResult<(Vec<ObjectEntry>, Vec<String>)> {
+        let mut objects = Vec::new();
+        let mut prefixes = Vec::new();
+        let mut token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix), ("delimiter", "/")];
+            if let Some(t) = &token {
+                query.push(("continuation-token", t.as_str()));
+            }
+            let response = self.send(Method::GET, "", &query, Vec::new(), &[]).await?;
+            let body = Self::check(response, "List objects").await?.text().await?;
+            let page: ListBucketResult = quick_xml::de::from_str(&body)
+                .map_err(|e| CloudError::ApiError(format!("Invalid listing: {}", e)))?;
+
+            objects.extend(page.contents);
+            prefixes.extend(page.common_prefixes.into_iter().map(|p| p.prefix));
+
+            match page.next_continuation_token {
+                Some(t) if page.is_truncated => token = Some(t),
+                _ => break,
+            }
+        }
+
+        Ok((objects, prefixes))
+    }
+
+    /// Every key under `prefix`, at any depth
+    async fn list_all_keys(&self, prefix: &str) -> CloudResult<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(t) = &token {
+                query.push(("continuation-token", t.as_str()));
+            }
+            let response = self.send(Method::GET, "", &query, Vec::new(), &[]).await?;
+            let body = Self::check(response, "List objects").await?.text().await?;
+            let page: ListBucketResult = quick_xml::de::from_str(&body)
+                .map_err(|e| CloudError::ApiError(format!("Invalid listing: {}", e)))?;
+
+            keys.extend(page.contents.into_iter().map(|o| o.key));
+
+            match page.next_continuation_token {
+                Some(t) if page.is_truncated => token = Some(t),
+                _ => break,
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn head(&self, key: &str) -> CloudResult<RemoteFile> {
+        let response = self.send(Method::HEAD, key, &[], Vec::new(), &[]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(CloudError::ApiError(format!("Object not found: {}", key)));
+        }
+        let response = Self::check(response, "Head object").await?;
+        let headers = response.headers();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+
+        let size: Option<u64> = header("content-length").and_then(|s| s.parse().ok());
+        let encrypted = header("x-amz-server-side-encryption").is_some_and(|s| s.starts_with("aws:kms"))
+            || header("x-amz-server-side-encryption-customer-algorithm").is_some();
+        let (hash, revision) = etag_fields(header("etag"), size.unwrap_or(0), encrypted);
+
+        Ok(RemoteFile {
+            id: key.to_string(),
+            name: key.rsplit('/').next().unwrap_or(key).to_string(),
+            is_dir: false,
+            size,
+            modified_at: header("last-modified")
+                .and_then(|s| chrono::DateTime::parse_from_rfc2822(&s).ok())
+                .map(|dt| dt.timestamp()),
+            hash,
+            revision,
+        })
+    }
+
+    async fn put_object(&self, key: &str, contents: Vec<u8>) -> CloudResult<()> {
+        let content_md5 = STANDARD.encode(md5::compute(&contents).0);
+        let response = self.send(Method::PUT, key, &[], contents, &[("content-md5", content_md5)]).await?;
+        Self::check(response, "Upload").await?;
+        Ok(())
+    }
+
+    async fn upload_multipart(&self, local_path: &Path, key: &str) -> CloudResult<()> {
+        let response = self.send(Method::POST, key, &[("uploads", "")], Vec::new(), &[]).await?;
+        let body = Self::check(response, "Create multipart upload").await?.text().await?;
+        let upload: InitiateMultipartUploadResult = quick_xml::de::from_str(&body)
+            .map_err(|e| CloudError::ApiError(format!("Invalid multipart response: {}", e)))?;
+
+        match self.upload_parts(local_path, key, &upload.upload_id).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // Abort so the bucket does not keep billing for orphaned parts
+                let _ = self.send(Method::DELETE, key, &[("uploadId", &upload.upload_id)], Vec::new(), &[]).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_parts(&self, local_path: &Path, key: &str, upload_id: &str) -> CloudResult<()> {
+        let mut file = tokio::fs::File::open(local_path).await?;
+        let mut parts = Vec::new();
+
+        loop {
+            let mut chunk = Vec::with_capacity(PART_SIZE as usize);
+            (&mut file).take(PART_SIZE).read_to_end(&mut chunk).await?;
+            if chunk.is_empty() {
+                break;
+            }
+
+            let part_number = (parts.len() + 1).to_string();
+            let content_md5 = STANDARD.encode(md5::compute(&chunk).0);
+            let response = self.send(
+                Method::PUT, key,
+                &[("partNumber", &part_number), ("uploadId", upload_id)],
+                chunk, &[("content-md5", content_md5)],
+            ).await?;
+            let response = Self::check(response, "Upload part").await?;
+            let etag = response.headers().get("etag").and_then(|v| v.to_str().ok())
+                .ok_or_else(|| CloudError::ApiError("No ETag for uploaded part".to_string()))?
+                .to_string();
+            parts.push((part_number, etag));
+        }
+
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (number, etag) in &parts {
+            complete.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", number, xml_escape(etag)));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+
+        let response = self.send(Method::POST, key, &[("uploadId", upload_id)], complete.into_bytes(), &[]).await?;
+        let body = Self::check(response, "Complete multipart upload").await?.text().await?;
+        // CompleteMultipartUpload can fail after sending 200 OK; the error is in the body
+        if let Ok(error) = quick_xml::de::from_str::<S3ErrorBody>(&body) {
+            return Err(CloudError::ApiError(format!("Complete multipart upload failed: {}", error.code)));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CloudProvider for S3Provider {
+    fn id(&self) -> &str {
+        "s3"
+    }
+
+    fn hash_kind(&self) -> HashKind {
+        HashKind::S3Etag
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+        let key = format!("{}{}", Self::prefix(cloud_path), filename);
+
+        let local_meta = tokio::fs::metadata(local_path).await?;
+        if local_meta.len() <= PART_SIZE {
+            let contents = tokio::fs::read(local_path).await?;
+            self.put_object(&key, contents).await?;
+        } else {
+            self.upload_multipart(local_path, &key).await?;
+        }
+
+        println!("Uploaded {} to S3", filename);
+        self.head(&key).await
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        let response = self.send(Method::GET, file_id, &[], Vec::new(), &[]).await?;
+        save_response(Self::check(response, "Download").await?, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        // Only the size is checked: the ETag of an object uploaded with another part size,
+        // or encrypted with SSE-KMS, is not something we can recompute
+        let response = self.send(Method::GET, &remote.id, &[], Vec::new(), &[]).await?;
+        save_response(Self::check(response, "Download").await?, local_path, Expected::size_of(remote)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let key = format!("{}{}", Self::prefix(cloud_parent), filename);
+
+        // A "folder" is everything under its prefix, plus its marker object
+        for child in self.list_all_keys(&format!("{}/", key)).await? {
+            let response = self.send(Method::DELETE, &child, &[], Vec::new(), &[]).await?;
+            Self::check(response, "Delete").await?;
+        }
+
+        // S3 answers 204 whether or not the key existed
+        let response = self.send(Method::DELETE, &key, &[], Vec::new(), &[]).await?;
+        Self::check(response, "Delete").await?;
+        println!("Deleted {} from S3", filename);
+        Ok(())
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let remote = self.head(cloud_path).await?;
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let prefix = Self::prefix(folder_id);
+        let (objects, prefixes) = self.list_level(&prefix).await?;
+
+        let mut files: Vec<RemoteFile> = prefixes.into_iter()
+            .map(|p| RemoteFile {
+                name: p[prefix.len()..].trim_end_matches('/').to_string(),
+                id: p,
+                is_dir: true,
+                size: None,
+                modified_at: None,
+                hash: None,
+                revision: None,
+            })
+            .collect();
+
+        // The folder's own marker object is not a child
+        files.extend(objects.into_iter().filter(|o| o.key != prefix).map(|o| {
+            let (hash, revision) = etag_fields(o.etag, o.size, false);
+            RemoteFile {
+                name: o.key[prefix.len()..].to_string(),
+                id: o.key,
+                is_dir: false,
+                size: Some(o.size),
+                modified_at: o.last_modified.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok()).map(|dt| dt.timestamp()),
+                hash,
+                revision,
+            }
+        }));
+
+        Ok(files)
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        let mut folders = Vec::new();
+        let mut queue: VecDeque<String> = VecDeque::from([String::new()]);
+
+        while let Some(prefix) = queue.pop_front() {
+            let (_, prefixes) = self.list_level(&prefix).await?;
+            for p in prefixes {
+                folders.push(RemoteFolder { id: p.clone(), name: p.trim_end_matches('/').to_string() });
+                if folders.len() >= MAX_LISTED_FOLDERS {
+                    return Ok(folders);
+                }
+                queue.push_back(p);
+            }
+        }
+
+        Ok(folders)
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let key = format!("{}{}/", Self::prefix(parent_id), name);
+        self.put_object(&key, Vec::new()).await?;
+        println!("Created folder {} on S3", name);
+        Ok(key)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// AWS URI encoding: every byte except unreserved characters is %XX-escaped.
+/// `/` is kept in object key paths and escaped in query components.
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<(String, String)> = query.iter()
+        .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+        .collect();
+    pairs.sort();
+    pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// SigV4 `Authorization` header value. `headers` are the lowercase names and values to
+/// sign besides `host`; `url` must already carry the canonical query string.
+#[allow(clippy::too_many_arguments)]
+pub fn authorization_header(
+    method: &str,
+    url: &url::Url,
+    headers: &[(String, String)],
+    payload_hash: &str,
+    access_key_id: &str,
+    secret_access_key: &str,
+    region: &str,
+    amz_date: &str,
+) -> String {
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut all: Vec<(String, String)> = headers.to_vec();
+    all.push(("host".to_string(), host));
+    all.sort();
+
+    let canonical_headers: String = all.iter().map(|(k, v)| format!("{}:{}\n", k, v.trim())).collect();
+    let signed_headers = all.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>().join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        url.path(),
+        url.query().unwrap_or_default(),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let k_date = hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date);
+    let k_region = hmac_sha256(&k_date, region);
+    let k_service = hmac_sha256(&k_region, "s3");
+    let k_signing = hmac_sha256(&k_service, "aws4_request");
+    let signature = hex(&hmac_sha256(&k_signing, &string_to_sign));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    )
+}
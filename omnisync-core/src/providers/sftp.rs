@@ -0,0 +1,600 @@
+use crate::download::{commit_part, part_path, Expected};
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteFile, RemoteFolder};
+use crate::registry::{ConnectedAccount, ConnectionForm};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, HashType, KnownHostFileKind, OpenFlags, OpenType, RenameFlags, Session, Sftp};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Upper bound on folders returned by `list_folders`, which walks the tree one level at a time
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Files hashed per `sha256sum` invocation, keeping the command line well below ARG_MAX
+const HASH_BATCH: usize = 200;
+
+/// Blocking operations give up after this long without progress
+const SESSION_TIMEOUT_MS: u32 = 60_000;
+
+fn default_port() -> u16 {
+    22
+}
+
+/// Connection settings for an SFTP server, stored as JSON in `credentials.config`.
+/// The private key passphrase, if any, is stored in `credentials.access_token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    /// Remote directory the account is rooted at; empty means the login directory
+    #[serde(default)]
+    pub path: String,
+    /// Private key file; when unset, keys offered by the running ssh-agent are used
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// Hash files remotely with `sha256sum` over an exec channel. Without it the engine
+    /// compares size and modification time only.
+    #[serde(default)]
+    pub remote_hash: bool,
+    /// Pinned host key fingerprint ("SHA256:..."), recorded when the account is connected.
+    /// Hosts listed in ~/.ssh/known_hosts are checked against that file instead.
+    #[serde(default)]
+    pub host_key: Option<String>,
+}
+
+impl SftpConfig {
+    /// Parse an scp-style target such as "alice@build01:/srv/sync"
+    pub fn parse_target(target: &str) -> Option<Self> {
+        let (username, rest) = target.split_once('@')?;
+        let (host, path) = rest.split_once(':').unwrap_or((rest, ""));
+        if username.is_empty() || host.is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: host.to_string(),
+            port: default_port(),
+            username: username.to_string(),
+            path: path.to_string(),
+            key_path: None,
+            remote_hash: false,
+            host_key: None,
+        })
+    }
+
+    /// Account identifier, e.g. "sftp:alice@build01" (the port is included when it is not 22)
+    pub fn account_id(&self) -> String {
+        if self.port == default_port() {
+            format!("sftp:{}@{}", self.username, self.host)
+        } else {
+            format!("sftp:{}@{}:{}", self.username, self.host, self.port)
+        }
+    }
+
+    /// Absolute or login-relative remote path for a path id
+    fn remote_path(&self, id: &str) -> String {
+        let id = SftpProvider::normalize(id);
+        let root = match self.path.trim_end_matches('/') {
+            "" if self.path.starts_with('/') => "/",
+            "" => ".",
+            root => root,
+        };
+        match (root, id) {
+            (root, "") => root.to_string(),
+            ("/", id) => format!("/{}", id),
+            (root, id) => format!("{}/{}", root, id),
+        }
+    }
+}
+
+/// Sets up SFTP accounts from an `SftpConfig`, pinning the host key presented on
+/// first connection like ssh's accept-new
+pub struct SftpForm;
+
+#[async_trait]
+impl ConnectionForm for SftpForm {
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount> {
+        let mut sftp: SftpConfig = serde_json::from_value(config)?;
+        if sftp.host_key.is_none() {
+            sftp.host_key = Some(SftpProvider::fetch_host_key(&sftp).await?);
+        }
+        Ok(ConnectedAccount {
+            account_id: sftp.account_id(),
+            user_name: sftp.username.clone(),
+            user_email: format!("{}:{}", sftp.host, sftp.path),
+            config: serde_json::to_value(&sftp)?,
+        })
+    }
+}
+
+/// An authenticated session with its SFTP channel
+struct Connection {
+    session: Session,
+    sftp: Sftp,
+}
+
+/// SFTP over SSH (build servers, NAS boxes, anything running OpenSSH).
+/// Like WebDAV, SFTP is path-addressed: ids are paths relative to the configured remote
+/// directory ("builds/web", "builds/web/app.tar"), and the root is "" ("root" and "/" are aliases).
+/// libssh2 is blocking, so every operation runs on the blocking thread pool against a
+/// session that is opened on first use and reopened after a failure.
+pub struct SftpProvider {
+    config: SftpConfig,
+    passphrase: String,
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl SftpProvider {
+    pub fn new(config: SftpConfig, passphrase: String) -> Self {
+        Self {
+            config,
+            passphrase,
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn normalize(id: &str) -> &str {
+        match id.trim_matches('/') {
+            "root" => "",
+            p => p,
+        }
+    }
+
+    fn join(parent: &str, name: &str) -> String {
+        match Self::normalize(parent) {
+            "" => name.to_string(),
+            p => format!("{}/{}", p, name),
+        }
+    }
+
+    /// Connect to the server and return its host key fingerprint without authenticating.
+    /// Fails if the key contradicts ~/.ssh/known_hosts.
+    pub async fn fetch_host_key(config: &SftpConfig) -> CloudResult<String> {
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || {
+            let session = handshake(&config)?;
+            if let CheckResult::Mismatch = check_known_hosts(&session, &config) {
+                return Err(CloudError::ApiError(format!("Host key for {} does not match known_hosts", config.host)));
+            }
+            fingerprint(&session)
+        })
+        .await
+        .map_err(|e| CloudError::Other(anyhow!(e)))?
+    }
+
+    /// Run a blocking operation against the shared connection, opening it if needed
+    async fn with_connection<T, F>(&self, op: F) -> CloudResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, &SftpConfig) -> CloudResult<T> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let passphrase = self.passphrase.clone();
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut guard = connection.lock().unwrap_or_else(|e| e.into_inner());
+            let conn = match guard.take() {
+                Some(conn) => conn,
+                None => connect(&config, &passphrase)?,
+            };
+            let result = op(&conn, &config);
+            // A missing path leaves the session intact; other failures may not, so start over next time
+            let reusable = match &result {
+                Ok(_) => true,
+                Err(CloudError::Io(e)) => e.kind() == std::io::ErrorKind::NotFound,
+                Err(_) => false,
+            };
+            if reusable {
+                *guard = Some(conn);
+            }
+            result
+        })
+        .await
+        .map_err(|e| CloudError::Other(anyhow!(e)))?
+    }
+
+    /// Copy into the part file next to `local_path`, then check it and rename it into place
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        let id = file_id.to_string();
+        let temp = part_path(local_path)?;
+
+        let copied = self.with_connection({
+            let temp = temp.clone();
+            move |conn, config| {
+                let mut source = conn.sftp.open(Path::new(&config.remote_path(&id))).map_err(ssh_err)?;
+                let mut target = std::fs::File::create(&temp)?;
+                std::io::copy(&mut source, &mut target)?;
+                Ok(())
+            }
+        }).await;
+        if let Err(e) = copied {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+        commit_part(local_path, expected).await
+    }
+}
+
+fn ssh_err(e: ssh2::Error) -> CloudError {
+    CloudError::Io(e.into())
+}
+
+fn handshake(config: &SftpConfig) -> CloudResult<Session> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))?;
+    let mut session = Session::new().map_err(ssh_err)?;
+    session.set_timeout(SESSION_TIMEOUT_MS);
+    session.set_tcp_stream(tcp);
+    session.handshake().map_err(ssh_err)?;
+    Ok(session)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn check_known_hosts(session: &Session, config: &SftpConfig) -> CheckResult {
+    let (Some((key, _)), Some(home)) = (session.host_key(), home_dir()) else {
+        return CheckResult::NotFound;
+    };
+    let Ok(mut known_hosts) = session.known_hosts() else {
+        return CheckResult::Failure;
+    };
+    if known_hosts.read_file(&home.join(".ssh").join("known_hosts"), KnownHostFileKind::OpenSSH).is_err() {
+        return CheckResult::NotFound;
+    }
+    known_hosts.check_port(&config.host, config.port, key)
+}
+
+/// Host key fingerprint in the format printed by OpenSSH
+fn fingerprint(session: &Session) -> CloudResult<String> {
+    session.host_key_hash(HashType::Sha256)
+        .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+        .ok_or_else(|| CloudError::ApiError("Server did not present a host key".to_string()))
+}
+
+fn connect(config: &SftpConfig, passphrase: &str) -> CloudResult<Connection> {
+    let session = handshake(config)?;
+
+    match check_known_hosts(&session, config) {
+        CheckResult::Match => {}
+        CheckResult::Mismatch => {
+            return Err(CloudError::ApiError(format!("Host key for {} does not match known_hosts", config.host)));
+        }
+        CheckResult::NotFound | CheckResult::Failure => {
+            let presented = fingerprint(&session)?;
+            if config.host_key.as_deref() != Some(presented.as_str()) {
+                return Err(CloudError::ApiError(format!("Unknown host key {} for {}", presented, config.host)));
+            }
+        }
+    }
+
+    let auth = match &config.key_path {
+        Some(key) => {
+            let passphrase = (!passphrase.is_empty()).then_some(passphrase);
+            session.userauth_pubkey_file(&config.username, None, &expand_home(key), passphrase)
+        }
+        None => session.userauth_agent(&config.username),
+    };
+    if auth.is_err() || !session.authenticated() {
+        return Err(CloudError::Unauthenticated);
+    }
+
+    let sftp = session.sftp().map_err(ssh_err)?;
+    Ok(Connection { session, sftp })
+}
+
+/// Quote an argument for a POSIX shell
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Parse `sha256sum` output into a map from file name to hex digest. Names that
+/// sha256sum had to escape (containing a backslash or newline) are left out.
+pub fn parse_sha256sum(output: &str) -> HashMap<String, String> {
+    output.lines()
+        .filter(|line| !line.starts_with('\\'))
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            // Text mode separates with two spaces, binary mode with " *"
+            let name = name.strip_prefix(' ').or_else(|| name.strip_prefix('*'))?;
+            (hash.len() == 64).then(|| (name.to_string(), hash.to_lowercase()))
+        })
+        .collect()
+}
+
+impl Connection {
+    fn exec(&self, command: &str) -> CloudResult<(i32, String)> {
+        let mut channel = self.session.channel_session().map_err(ssh_err)?;
+        channel.exec(command).map_err(ssh_err)?;
+        let mut output = String::new();
+        channel.read_to_string(&mut output)?;
+        channel.wait_close().map_err(ssh_err)?;
+        Ok((channel.exit_status().map_err(ssh_err)?, output))
+    }
+
+    /// SHA-256 of the given files in `dir`, computed on the server. Files the server
+    /// could not hash are missing from the result.
+    fn remote_hashes(&self, dir: &str, names: &[String]) -> CloudResult<HashMap<String, String>> {
+        let mut hashes = HashMap::new();
+        for batch in names.chunks(HASH_BATCH) {
+            let args: Vec<String> = batch.iter().map(|n| shell_quote(n)).collect();
+            let command = format!("cd {} && sha256sum -- {}", shell_quote(dir), args.join(" "));
+            let (status, output) = self.exec(&command)?;
+            if status == 127 {
+                println!("sha256sum is not available on the server, comparing by size and time");
+                break;
+            }
+            hashes.extend(parse_sha256sum(&output));
+        }
+        Ok(hashes)
+    }
+
+    fn list(&self, config: &SftpConfig, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let folder = SftpProvider::normalize(folder_id);
+        let dir = config.remote_path(folder);
+        let mut files: Vec<RemoteFile> = self.sftp.readdir(Path::new(&dir)).map_err(ssh_err)?
+            .into_iter()
+            // Symlinks and special files are not synced
+            .filter(|(_, stat)| stat.is_dir() || stat.is_file())
+            .filter_map(|(path, stat)| {
+                let name = path.file_name()?.to_str()?.to_string();
+                if name == "." || name == ".." {
+                    return None;
+                }
+                Some(RemoteFile {
+                    id: SftpProvider::join(folder, &name),
+                    name,
+                    is_dir: stat.is_dir(),
+                    size: if stat.is_dir() { None } else { stat.size },
+                    modified_at: stat.mtime.map(|t| t as i64),
+                    hash: None,
+                    revision: None,
+                })
+            })
+            .collect();
+
+        if config.remote_hash {
+            let names: Vec<String> = files.iter().filter(|f| !f.is_dir).map(|f| f.name.clone()).collect();
+            let mut hashes = self.remote_hashes(&dir, &names)?;
+            for file in files.iter_mut() {
+                file.hash = hashes.remove(&file.name);
+            }
+        }
+        Ok(files)
+    }
+
+    fn stat(&self, config: &SftpConfig, id: &str) -> CloudResult<RemoteFile> {
+        let id = SftpProvider::normalize(id);
+        let stat = self.sftp.stat(Path::new(&config.remote_path(id))).map_err(ssh_err)?;
+        let (parent, name) = id.rsplit_once('/').unwrap_or(("", id));
+
+        let hash = if config.remote_hash && stat.is_file() {
+            self.remote_hashes(&config.remote_path(parent), &[name.to_string()])?.remove(name)
+        } else {
+            None
+        };
+
+        Ok(RemoteFile {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_dir: stat.is_dir(),
+            size: if stat.is_dir() { None } else { stat.size },
+            modified_at: stat.mtime.map(|t| t as i64),
+            hash,
+            revision: None,
+        })
+    }
+
+    fn remove_recursive(&self, path: &str) -> CloudResult<()> {
+        let stat = self.sftp.lstat(Path::new(path)).map_err(ssh_err)?;
+        if !stat.is_dir() {
+            return self.sftp.unlink(Path::new(path)).map_err(ssh_err);
+        }
+        for (child, _) in self.sftp.readdir(Path::new(path)).map_err(ssh_err)? {
+            match child.file_name().and_then(|n| n.to_str()) {
+                Some(".") | Some("..") | None => continue,
+                Some(name) => self.remove_recursive(&format!("{}/{}", path, name))?,
+            }
+        }
+        self.sftp.rmdir(Path::new(path)).map_err(ssh_err)
+    }
+}
+
+#[async_trait]
+impl CloudProvider for SftpProvider {
+    fn id(&self) -> &str {
+        "sftp"
+    }
+
+    fn hash_kind(&self) -> HashKind {
+        HashKind::Sha256
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?
+            .to_string();
+        let id = Self::join(cloud_path, &filename);
+        let local_path = local_path.to_path_buf();
+
+        let remote = self.with_connection(move |conn, config| {
+            let dest = config.remote_path(&id);
+            // Write next to the destination and rename, so readers never see a partial file
+            let (parent, _) = id.rsplit_once('/').unwrap_or(("", &id));
+            let temp = config.remote_path(&Self::join(parent, &format!(".{}.omnisync-part", filename)));
+
+            let mut source = std::fs::File::open(&local_path)?;
+            let mut target = conn.sftp
+                .open_mode(Path::new(&temp), OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE, 0o644, OpenType::File)
+                .map_err(ssh_err)?;
+            let mut hasher = Sha256::new();
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                let n = source.read(&mut buffer)?;
+                if n == 0 { break; }
+                hasher.update(&buffer[..n]);
+                target.write_all(&buffer[..n])?;
+            }
+            drop(target);
+
+            let flags = Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE);
+            if conn.sftp.rename(Path::new(&temp), Path::new(&dest), flags).is_err() {
+                // SFTPv3 servers refuse to rename over an existing file
+                let _ = conn.sftp.unlink(Path::new(&dest));
+                conn.sftp.rename(Path::new(&temp), Path::new(&dest), None).map_err(ssh_err)?;
+            }
+
+            // Keep the local modification time, so size and time comparisons stay meaningful
+            if let Some(mtime) = std::fs::metadata(&local_path)?.modified().ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            {
+                let times = ssh2::FileStat {
+                    size: None,
+                    uid: None,
+                    gid: None,
+                    perm: None,
+                    atime: Some(mtime.as_secs()),
+                    mtime: Some(mtime.as_secs()),
+                };
+                conn.sftp.setstat(Path::new(&dest), times).map_err(ssh_err)?;
+            }
+
+            let stat = conn.sftp.stat(Path::new(&dest)).map_err(ssh_err)?;
+            Ok(RemoteFile {
+                name: filename,
+                id,
+                is_dir: false,
+                size: stat.size,
+                modified_at: stat.mtime.map(|t| t as i64),
+                // What sha256sum would report for the bytes just written
+                hash: config.remote_hash.then(|| hex_digest(&hasher.finalize())),
+                revision: None,
+            })
+        }).await?;
+
+        println!("Uploaded {} to SFTP", remote.name);
+        Ok(remote)
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::listed(remote, HashKind::Sha256)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let id = Self::join(cloud_parent, filename);
+
+        let result = self.with_connection(move |conn, config| conn.remove_recursive(&config.remote_path(&id))).await;
+        match result {
+            Err(CloudError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+            Ok(()) => {
+                println!("Deleted {} from SFTP", filename);
+                Ok(())
+            }
+        }
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let id = cloud_path.to_string();
+        let remote = self.with_connection(move |conn, config| conn.stat(config, &id)).await?;
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let folder = folder_id.to_string();
+        self.with_connection(move |conn, config| conn.list(config, &folder)).await
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        self.with_connection(|conn, config| {
+            let mut folders = Vec::new();
+            let mut queue: VecDeque<String> = VecDeque::from([String::new()]);
+
+            while let Some(id) = queue.pop_front() {
+                let dir = config.remote_path(&id);
+                for (path, stat) in conn.sftp.readdir(Path::new(&dir)).map_err(ssh_err)? {
+                    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+                    if !stat.is_dir() || name == "." || name == ".." {
+                        continue;
+                    }
+                    let child = Self::join(&id, name);
+                    folders.push(RemoteFolder { id: child.clone(), name: child.clone() });
+                    if folders.len() >= MAX_LISTED_FOLDERS {
+                        return Ok(folders);
+                    }
+                    queue.push_back(child);
+                }
+            }
+
+            Ok(folders)
+        }).await
+    }
+
+    async fn move_file(&self, file_id: &str, _from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let from = file_id.to_string();
+        let id = Self::join(to_parent, new_name);
+
+        let remote = self.with_connection(move |conn, config| {
+            // No OVERWRITE: fail rather than replace something already at the destination
+            let flags = Some(RenameFlags::ATOMIC | RenameFlags::NATIVE);
+            conn.sftp
+                .rename(Path::new(&config.remote_path(&from)), Path::new(&config.remote_path(&id)), flags)
+                .map_err(ssh_err)?;
+            conn.stat(config, &id)
+        }).await?;
+
+        println!("Moved {} to {} on SFTP", file_id, remote.id);
+        Ok(Some(remote))
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let id = Self::join(parent_id, name);
+        let created = id.clone();
+
+        self.with_connection(move |conn, config| {
+            let path = config.remote_path(&created);
+            if let Err(e) = conn.sftp.mkdir(Path::new(&path), 0o755) {
+                // mkdir fails with a generic error when the directory already exists
+                match conn.sftp.stat(Path::new(&path)) {
+                    Ok(stat) if stat.is_dir() => {}
+                    _ => return Err(ssh_err(e)),
+                }
+            }
+            Ok(())
+        }).await?;
+
+        println!("Created folder {} on SFTP", name);
+        Ok(id)
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,398 @@
+use crate::download::{save_response, Expected};
+use crate::hashing::hash_file;
+use crate::provider::{CloudProvider, FileMetadata, CloudError, CloudResult, HashKind, RemoteFile, RemoteFolder};
+use crate::registry::{ConnectedAccount, ConnectionForm};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Upper bound on folders returned by `list_folders`, which walks the tree one level at a time
+const MAX_LISTED_FOLDERS: usize = 2000;
+
+/// Characters left as-is in a path segment; everything else is percent-encoded
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+    <d:getetag/>
+    <oc:checksums/>
+  </d:prop>
+</d:propfind>"#;
+
+/// Connection settings for a WebDAV server, stored as JSON in `credentials.config`.
+/// The password (or Nextcloud/ownCloud app password) is stored in `credentials.access_token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    /// Collection to sync against, e.g. "https://cloud.example.com/remote.php/dav/files/alice/"
+    pub url: String,
+    pub username: String,
+}
+
+impl WebDavConfig {
+    /// Account identifier, e.g. "webdav:alice@cloud.example.com"
+    pub fn account_id(&self) -> String {
+        let host = url::Url::parse(&self.url).ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| self.url.clone());
+        format!("webdav:{}@{}", self.username, host)
+    }
+}
+
+/// Sets up WebDAV accounts from a `WebDavConfig`
+pub struct WebDavForm;
+
+#[async_trait]
+impl ConnectionForm for WebDavForm {
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount> {
+        let dav: WebDavConfig = serde_json::from_value(config.clone())?;
+        Ok(ConnectedAccount { account_id: dav.account_id(), user_name: dav.username, user_email: dav.url, config })
+    }
+}
+
+/// Generic WebDAV (Nextcloud, ownCloud, Apache mod_dav, ...).
+/// WebDAV is path-addressed: folder and file ids are paths relative to the configured
+/// URL ("Documents/Work", "Documents/a.txt"), and the root is "" ("root" and "/" are aliases).
+/// `RemoteFile.hash` is the MD5 from `oc:checksums` when the server keeps one; uploads send
+/// an `OC-Checksum` so files written by us always have it. The ETag is reported as
+/// `RemoteFile.revision`: it cannot be compared with local content, but tells remote changes
+/// apart when there is no checksum, even ones that keep the size and modification time.
+pub struct WebDavProvider {
+    client: Client,
+    base_url: url::Url,
+    username: String,
+    password: String,
+}
+
+/// One `<d:response>` of a PROPFIND multistatus
+#[derive(Debug, Default)]
+struct DavEntry {
+    href: String,
+    is_dir: bool,
+    size: Option<u64>,
+    modified_at: Option<i64>,
+    checksum: Option<String>,
+    etag: Option<String>,
+}
+
+impl WebDavProvider {
+    pub fn new(config: WebDavConfig, password: String) -> CloudResult<Self> {
+        let mut base = config.url.clone();
+        if !base.ends_with('/') {
+            base.push('/');
+        }
+        let base_url = url::Url::parse(&base).map_err(|e| CloudError::Other(anyhow!("Invalid WebDAV URL: {}", e)))?;
+        Ok(Self {
+            client: Client::new(),
+            base_url,
+            username: config.username,
+            password,
+        })
+    }
+
+    fn normalize(id: &str) -> &str {
+        match id.trim_matches('/') {
+            "root" => "",
+            p => p,
+        }
+    }
+
+    fn join(parent: &str, name: &str) -> String {
+        match Self::normalize(parent) {
+            "" => name.to_string(),
+            p => format!("{}/{}", p, name),
+        }
+    }
+
+    /// URL for a path id; collections get a trailing slash
+    fn url_for(&self, id: &str, collection: bool) -> String {
+        let mut encoded: String = Self::normalize(id)
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| utf8_percent_encode(s, PATH_SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        if collection && !encoded.is_empty() {
+            encoded.push('/');
+        }
+        format!("{}{}", self.base_url, encoded)
+    }
+
+    /// Convert an href from a multistatus response back into a path id
+    fn id_from_href(&self, href: &str) -> String {
+        let path = url::Url::parse(href).map(|u| u.path().to_string()).unwrap_or_else(|_| href.to_string());
+        let path = percent_decode_str(&path).decode_utf8_lossy().to_string();
+        let base = percent_decode_str(self.base_url.path()).decode_utf8_lossy().to_string();
+        path.strip_prefix(&base).unwrap_or(&path).trim_matches('/').to_string()
+    }
+
+    async fn download_to(&self, file_id: &str, local_path: &Path, expected: Expected<'_>) -> CloudResult<()> {
+        let response = self.client
+            .get(self.url_for(file_id, false))
+            .basic_auth(&self.username, Some(&self.password))
+            .send()
+            .await?;
+        save_response(Self::check(response, "Download").await?, local_path, expected).await
+    }
+
+    async fn check(response: Response, action: &str) -> CloudResult<Response> {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(CloudError::Unauthenticated);
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(CloudError::ApiError(format!("{} failed: {} {}", action, status, error_text)));
+        }
+        Ok(response)
+    }
+
+    async fn propfind(&self, id: &str, collection: bool, depth: &str) -> CloudResult<Vec<DavEntry>> {
+        let response = self.client
+            .request(Method::from_bytes(b"PROPFIND").expect("valid method"), self.url_for(id, collection))
+            .basic_auth(&self.username, Some(&self.password))
+            .header("Depth", depth)
+            .header(reqwest::header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+
+        let body = Self::check(response, "PROPFIND").await?.text().await?;
+        parse_multistatus(&body).map_err(|e| CloudError::ApiError(format!("Invalid PROPFIND response: {}", e)))
+    }
+
+    fn to_remote(&self, entry: DavEntry) -> RemoteFile {
+        let id = self.id_from_href(&entry.href);
+        RemoteFile {
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            id,
+            is_dir: entry.is_dir,
+            size: if entry.is_dir { None } else { entry.size },
+            modified_at: entry.modified_at,
+            hash: entry.checksum,
+            revision: entry.etag,
+        }
+    }
+
+    async fn stat(&self, id: &str) -> CloudResult<RemoteFile> {
+        let entry = self.propfind(id, false, "0").await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| CloudError::ApiError(format!("No properties returned for {}", id)))?;
+        Ok(self.to_remote(entry))
+    }
+}
+
+/// Parse a PROPFIND multistatus. Element names are matched by local name, since
+/// servers differ in the namespace prefixes they use ("d:", "D:", or a default namespace).
+fn parse_multistatus(xml: &str) -> Result<Vec<DavEntry>, quick_xml::Error> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut current: Option<DavEntry> = None;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                if e.local_name().as_ref() == b"response" {
+                    current = Some(DavEntry::default());
+                }
+                text.clear();
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"collection" => {
+                if let Some(entry) = current.as_mut() {
+                    entry.is_dir = true;
+                }
+            }
+            Event::Text(t) => text.push_str(&t.decode().map_err(quick_xml::Error::from)?),
+            Event::GeneralRef(r) => {
+                if let Some(c) = r.resolve_char_ref()? {
+                    text.push(c);
+                } else {
+                    text.push_str(match r.decode().map_err(quick_xml::Error::from)?.as_ref() {
+                        "amp" => "&",
+                        "lt" => "<",
+                        "gt" => ">",
+                        "quot" => "\"",
+                        "apos" => "'",
+                        _ => "",
+                    });
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                if let Some(entry) = current.as_mut() {
+                    let value = text.trim();
+                    match name.as_ref() {
+                        b"href" => entry.href = value.to_string(),
+                        b"collection" => entry.is_dir = true,
+                        b"getcontentlength" => entry.size = value.parse().ok(),
+                        b"getlastmodified" => {
+                            entry.modified_at = chrono::DateTime::parse_from_rfc2822(value).ok().map(|dt| dt.timestamp());
+                        }
+                        // Quoted, and "W/"-prefixed when weak; only compared for equality
+                        b"getetag" if !value.is_empty() => {
+                            entry.etag = Some(value.trim_start_matches("W/").trim_matches('"').to_string());
+                        }
+                        b"checksum" => {
+                            // e.g. "SHA1:... MD5:... ADLER32:..."
+                            entry.checksum = value.split_whitespace()
+                                .find_map(|c| c.strip_prefix("MD5:").or_else(|| c.strip_prefix("md5:")))
+                                .map(|h| h.to_lowercase());
+                        }
+                        b"response" => entries.extend(current.take()),
+                        _ => {}
+                    }
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+#[async_trait]
+impl CloudProvider for WebDavProvider {
+    fn id(&self) -> &str {
+        "webdav"
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        let filename = local_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CloudError::Other(anyhow!("Invalid filename")))?;
+        let id = Self::join(cloud_path, filename);
+
+        // Hashed in a first pass so the body can be streamed from disk
+        let meta = tokio::fs::metadata(local_path).await?;
+        let checksum = format!("MD5:{}", hash_file(local_path, HashKind::Md5).await?);
+        let mut request = self.client
+            .put(self.url_for(&id, false))
+            .basic_auth(&self.username, Some(&self.password))
+            .header("OC-Checksum", checksum)
+            // Many servers refuse chunked PUT bodies
+            .header(reqwest::header::CONTENT_LENGTH, meta.len());
+
+        // Nextcloud/ownCloud keep the client's modification time when it is sent along
+        if let Some(mtime) = meta.modified().ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        {
+            request = request.header("X-OC-Mtime", mtime.as_secs().to_string());
+        }
+
+        let file = tokio::fs::File::open(local_path).await?;
+        let response = request.body(file).send().await?;
+        Self::check(response, "Upload").await?;
+
+        println!("Uploaded {} to WebDAV", filename);
+        self.stat(&id).await
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.download_to(file_id, local_path, Expected::default()).await
+    }
+
+    async fn download_remote(&self, remote: &RemoteFile, local_path: &Path) -> CloudResult<()> {
+        self.download_to(&remote.id, local_path, Expected::listed(remote, HashKind::Md5)).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        let response = self.client
+            .delete(self.url_for(&Self::join(cloud_parent, filename), false))
+            .basic_auth(&self.username, Some(&self.password))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check(response, "Delete").await?;
+        println!("Deleted {} from WebDAV", filename);
+        Ok(())
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        let remote = self.stat(cloud_path).await?;
+        Ok(FileMetadata {
+            hash: remote.hash,
+            size: remote.size,
+            modified_at: remote.modified_at,
+        })
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        let folder = Self::normalize(folder_id).to_string();
+        let entries = self.propfind(&folder, true, "1").await?;
+
+        // The folder itself is part of a Depth: 1 response
+        Ok(entries.into_iter()
+            .map(|e| self.to_remote(e))
+            .filter(|f| f.id != folder)
+            .collect())
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        // Depth: infinity is disabled on most servers, so walk one level at a time
+        let mut folders = Vec::new();
+        let mut queue: VecDeque<String> = VecDeque::from([String::new()]);
+
+        while let Some(id) = queue.pop_front() {
+            for child in self.list_files(&id).await?.into_iter().filter(|f| f.is_dir) {
+                folders.push(RemoteFolder { id: child.id.clone(), name: child.id.clone() });
+                if folders.len() >= MAX_LISTED_FOLDERS {
+                    return Ok(folders);
+                }
+                queue.push_back(child.id);
+            }
+        }
+
+        Ok(folders)
+    }
+
+    async fn move_file(&self, file_id: &str, _from_parent: &str, to_parent: &str, new_name: &str) -> CloudResult<Option<RemoteFile>> {
+        let id = Self::join(to_parent, new_name);
+        let response = self.client
+            .request(Method::from_bytes(b"MOVE").expect("valid method"), self.url_for(file_id, false))
+            .basic_auth(&self.username, Some(&self.password))
+            .header("Destination", self.url_for(&id, false))
+            // Never replace something that appeared at the destination meanwhile
+            .header("Overwrite", "F")
+            .send()
+            .await?;
+        Self::check(response, "Move").await?;
+
+        println!("Moved {} to {} on WebDAV", file_id, id);
+        Ok(Some(self.stat(&id).await?))
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        let id = Self::join(parent_id, name);
+        let response = self.client
+            .request(Method::from_bytes(b"MKCOL").expect("valid method"), self.url_for(&id, true))
+            .basic_auth(&self.username, Some(&self.password))
+            .send()
+            .await?;
+
+        // 405 Method Not Allowed: the collection already exists
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(id);
+        }
+        Self::check(response, "Create folder").await?;
+
+        println!("Created folder {} on WebDAV", name);
+        Ok(id)
+    }
+}
@@ -0,0 +1,204 @@
+use crate::models::{FileState, SyncMode};
+use crate::provider::RemoteFile;
+use serde::{Deserialize, Serialize};
+
+/// Current state of a local file, as seen during reconciliation.
+#[derive(Debug, Clone)]
+pub struct LocalState {
+    pub size: u64,
+    pub modified_at: i64,
+    pub hash: String,
+}
+
+/// What to do with a single entry after comparing local, remote and base states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Both sides match; refresh the base record if needed.
+    InSync,
+    /// Local side is new or changed; push it to the cloud.
+    Upload,
+    /// Remote side is new or changed; pull it locally.
+    Download,
+    /// Remote was deleted since the last sync and local is unchanged.
+    DeleteLocal,
+    /// Local was deleted since the last sync and remote is unchanged.
+    DeleteRemote,
+    /// Both sides changed since the last sync.
+    BothChanged,
+    /// Gone on both sides; drop the base record.
+    Forget,
+    /// A change the pair's mode does not propagate; leave both sides alone.
+    Skip,
+}
+
+/// Three-way comparison for a file. `base` is the state recorded at the last sync;
+/// a deletion is only propagated when the entry existed at that point.
+pub fn decide_file(local: Option<&LocalState>, remote: Option<&RemoteFile>, base: Option<&FileState>) -> Decision {
+    match (local, remote, base) {
+        (None, None, _) => Decision::Forget,
+        (Some(_), None, None) => Decision::Upload,
+        (None, Some(_), None) => Decision::Download,
+        (Some(l), None, Some(b)) => {
+            if local_changed(l, b) { Decision::Upload } else { Decision::DeleteLocal }
+        }
+        (None, Some(r), Some(b)) => {
+            if remote_changed(r, b) { Decision::Download } else { Decision::DeleteRemote }
+        }
+        (Some(l), Some(r), base) => {
+            if r.hash.as_deref() == Some(l.hash.as_str()) {
+                return Decision::InSync;
+            }
+            let Some(b) = base else {
+                // Never synced and contents differ, or cannot be compared without a remote hash
+                return Decision::BothChanged;
+            };
+            match (local_changed(l, b), remote_changed(r, b)) {
+                (false, false) => Decision::InSync,
+                (true, false) => Decision::Upload,
+                (false, true) => Decision::Download,
+                (true, true) => Decision::BothChanged,
+            }
+        }
+    }
+}
+
+/// Three-way comparison for a directory. `Upload` means create it remotely,
+/// `Download` means create it locally, `InSync` means recurse into it.
+pub fn decide_dir(local_exists: bool, remote_exists: bool, base: Option<&FileState>) -> Decision {
+    match (local_exists, remote_exists, base.is_some()) {
+        (true, true, _) => Decision::InSync,
+        (false, false, _) => Decision::Forget,
+        (true, false, false) => Decision::Upload,
+        (true, false, true) => Decision::DeleteLocal,
+        (false, true, false) => Decision::Download,
+        (false, true, true) => Decision::DeleteRemote,
+    }
+}
+
+/// Narrow a two-way decision down to what the pair's `mode` allows.
+/// `local_exists` tells the mirror which way to undo a remote change.
+pub fn apply_mode(decision: Decision, mode: SyncMode, local_exists: bool) -> Decision {
+    match mode {
+        SyncMode::TwoWay => decision,
+        SyncMode::UploadOnly => match decision {
+            Decision::Download => Decision::Skip,
+            // Put back what was removed remotely; local edits win
+            Decision::DeleteLocal | Decision::BothChanged => Decision::Upload,
+            // Keep the remote copy, but stop tracking it
+            Decision::DeleteRemote => Decision::Forget,
+            d => d,
+        },
+        SyncMode::DownloadOnly => match decision {
+            Decision::Upload => Decision::Skip,
+            // Restore what was removed locally; remote edits win
+            Decision::DeleteRemote | Decision::BothChanged => Decision::Download,
+            d => d,
+        },
+        SyncMode::Mirror => match decision {
+            Decision::Download | Decision::DeleteLocal | Decision::BothChanged if local_exists => Decision::Upload,
+            Decision::Download => Decision::DeleteRemote,
+            d => d,
+        },
+    }
+}
+
+/// The percentage limit on deletions only applies from this many files on,
+/// so small trees can still lose a few files without asking
+pub const DELETE_GUARD_MIN_FILES: usize = 10;
+
+/// Whether deleting `deletions` of `total` synced files in one pass crosses a pair's limits
+pub fn exceeds_delete_limit(deletions: usize, total: usize, limit: i64, limit_percent: i64) -> bool {
+    let over_count = limit > 0 && deletions as i64 > limit;
+    let over_percent = limit_percent > 0
+        && deletions >= DELETE_GUARD_MIN_FILES
+        && (deletions as i64) * 100 > limit_percent * total as i64;
+    over_count || over_percent
+}
+
+/// A step of a sync pass, as reported by a dry run. Paths are keys relative to the pair root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SyncAction {
+    Upload { path: String, size: Option<u64> },
+    Download { path: String, size: Option<u64> },
+    DeleteLocal { path: String, is_dir: bool },
+    DeleteRemote { path: String, is_dir: bool },
+    MkdirLocal { path: String },
+    MkdirRemote { path: String },
+    /// Changed on both sides; both versions will be kept
+    Conflict { path: String },
+}
+
+impl std::fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let folder = |is_dir: bool| if is_dir { "/" } else { "" };
+        match self {
+            SyncAction::Upload { path, .. } => write!(f, "upload        {}", path),
+            SyncAction::Download { path, .. } => write!(f, "download      {}", path),
+            SyncAction::DeleteLocal { path, is_dir } => write!(f, "delete local  {}{}", path, folder(*is_dir)),
+            SyncAction::DeleteRemote { path, is_dir } => write!(f, "delete remote {}{}", path, folder(*is_dir)),
+            SyncAction::MkdirLocal { path } => write!(f, "mkdir local   {}/", path),
+            SyncAction::MkdirRemote { path } => write!(f, "mkdir remote  {}/", path),
+            SyncAction::Conflict { path } => write!(f, "conflict      {}", path),
+        }
+    }
+}
+
+/// Everything a sync pass over a pair would do, in tree order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPlan {
+    pub pair_id: i64,
+    pub actions: Vec<SyncAction>,
+}
+
+/// The visible step behind a decision, if any. Bookkeeping-only decisions
+/// (in sync, forget, skip) have none.
+pub fn plan_action(decision: Decision, is_dir: bool, path: &str, local_size: Option<u64>, remote_size: Option<u64>) -> Option<SyncAction> {
+    let path = path.to_string();
+    match (decision, is_dir) {
+        (Decision::Upload, true) => Some(SyncAction::MkdirRemote { path }),
+        (Decision::Upload, false) => Some(SyncAction::Upload { path, size: local_size }),
+        (Decision::Download, true) => Some(SyncAction::MkdirLocal { path }),
+        (Decision::Download, false) => Some(SyncAction::Download { path, size: remote_size }),
+        (Decision::DeleteLocal, is_dir) => Some(SyncAction::DeleteLocal { path, is_dir }),
+        (Decision::DeleteRemote, is_dir) => Some(SyncAction::DeleteRemote { path, is_dir }),
+        (Decision::BothChanged, false) => Some(SyncAction::Conflict { path }),
+        _ => None,
+    }
+}
+
+pub fn local_changed(local: &LocalState, base: &FileState) -> bool {
+    match &base.hash {
+        Some(h) => *h != local.hash,
+        None => base.size != Some(local.size as i64) || base.modified_at != Some(local.modified_at),
+    }
+}
+
+pub fn remote_changed(remote: &RemoteFile, base: &FileState) -> bool {
+    if base.remote_id.as_deref() != Some(remote.id.as_str()) {
+        return true;
+    }
+    match (&remote.hash, &base.remote_hash) {
+        (Some(r), Some(b)) => r != b,
+        _ => match (&remote.revision, &base.remote_revision) {
+            (Some(r), Some(b)) => r != b,
+            // Without either: the recorded local size is what the remote copy had at the last sync
+            _ => remote.modified_at != base.remote_modified_at
+                || remote.size.is_some_and(|s| base.size != Some(s as i64)),
+        },
+    }
+}
+
+/// Name for the conflicted copy of `name`, e.g. "report (conflicted copy, laptop, 2026-10-18).pdf".
+/// `n` > 1 disambiguates several copies made on the same day.
+pub fn conflicted_copy_name(name: &str, host: &str, date: &str, n: u32) -> String {
+    let label = if n > 1 {
+        format!("conflicted copy {}, {}, {}", n, host, date)
+    } else {
+        format!("conflicted copy, {}, {}", host, date)
+    };
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, label, ext),
+        _ => format!("{} ({})", name, label),
+    }
+}
@@ -0,0 +1,150 @@
+use crate::auth::AuthProvider;
+use crate::models::Credentials;
+use crate::provider::{CloudError, CloudProvider, CloudResult};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Builds a provider instance from stored credentials
+pub type ProviderFactory = Arc<dyn Fn(&Credentials) -> CloudResult<Box<dyn CloudProvider>> + Send + Sync>;
+
+/// An account set up from a connection form, ready to be stored
+#[derive(Debug, Clone)]
+pub struct ConnectedAccount {
+    /// Starts with the provider type, e.g. "s3:backups@minio.local:9000"
+    pub account_id: String,
+    pub user_name: String,
+    pub user_email: String,
+    /// The settings to store, including anything learned while connecting
+    pub config: serde_json::Value,
+}
+
+/// How accounts of a provider type without OAuth are set up from the JSON settings
+/// of their connection form, registered next to its provider factory
+#[async_trait]
+pub trait ConnectionForm: Send + Sync {
+    /// Check the settings and derive the account they describe
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount>;
+}
+
+/// Provider factories keyed by provider type, the prefix of an account id
+/// ("gdrive" for "gdrive:user@gmail.com"), the sign-in of the OAuth ones and
+/// the connection form of the others.
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    factories: HashMap<String, ProviderFactory>,
+    auth: HashMap<String, Arc<dyn AuthProvider>>,
+    forms: HashMap<String, Arc<dyn ConnectionForm>>,
+}
+
+/// Extract provider type from account_id (e.g., "gdrive:user@gmail.com" -> "gdrive")
+pub fn provider_type(account_id: &str) -> &str {
+    account_id.split(':').next().unwrap_or(account_id)
+}
+
+/// Parse the JSON connection settings of a non-OAuth account
+pub fn connection_config<T: DeserializeOwned>(creds: &Credentials) -> CloudResult<T> {
+    let config = creds.config.as_deref()
+        .ok_or_else(|| CloudError::Other(anyhow!("Missing connection settings for {}", creds.account_id)))?;
+    serde_json::from_str(config)
+        .map_err(|e| CloudError::Other(anyhow!("Invalid connection settings for {}: {}", creds.account_id, e)))
+}
+
+impl ProviderRegistry {
+    /// A registry without any providers
+    pub fn empty() -> Self {
+        Self { factories: HashMap::new(), auth: HashMap::new(), forms: HashMap::new() }
+    }
+
+    /// Register (or replace) the factory for a provider type
+    pub fn register<F>(&mut self, provider_type: &str, factory: F)
+    where
+        F: Fn(&Credentials) -> CloudResult<Box<dyn CloudProvider>> + Send + Sync + 'static,
+    {
+        self.factories.insert(provider_type.to_string(), Arc::new(factory));
+    }
+
+    /// Register (or replace) how accounts of a provider type sign in and refresh tokens
+    pub fn register_auth<A: AuthProvider + 'static>(&mut self, provider_type: &str, auth: A) {
+        self.auth.insert(provider_type.to_string(), Arc::new(auth));
+    }
+
+    /// Sign-in of a provider type; None for types without OAuth
+    pub fn auth(&self, provider_type: &str) -> Option<Arc<dyn AuthProvider>> {
+        self.auth.get(provider_type).cloned()
+    }
+
+    /// Register (or replace) how accounts of a provider type are set up from a connection form
+    pub fn register_form<C: ConnectionForm + 'static>(&mut self, provider_type: &str, form: C) {
+        self.forms.insert(provider_type.to_string(), Arc::new(form));
+    }
+
+    /// Connection form of a provider type; None for types that sign in with OAuth
+    pub fn form(&self, provider_type: &str) -> Option<Arc<dyn ConnectionForm>> {
+        self.forms.get(provider_type).cloned()
+    }
+
+    pub fn contains(&self, provider_type: &str) -> bool {
+        self.factories.contains_key(provider_type)
+    }
+
+    /// Registered provider types, sorted
+    pub fn provider_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self.factories.keys().cloned().collect();
+        types.sort();
+        types
+    }
+
+    /// Build a provider for an account, picking the factory by the account id's provider type
+    pub fn build(&self, creds: &Credentials) -> CloudResult<Box<dyn CloudProvider>> {
+        let ptype = provider_type(&creds.account_id);
+        let factory = self.factories.get(ptype)
+            .ok_or_else(|| CloudError::Other(anyhow!("No provider registered for {}", ptype)))?;
+        factory(creds)
+    }
+}
+
+impl Default for ProviderRegistry {
+    /// A registry with every provider shipped in `crate::providers`
+    fn default() -> Self {
+        use crate::providers::*;
+
+        let mut registry = Self::empty();
+        registry.register("gdrive", |creds| {
+            Ok(Box::new(gdrive::GoogleDriveProvider::new(creds.access_token.clone())))
+        });
+        registry.register("onedrive", |creds| {
+            Ok(Box::new(onedrive::OneDriveProvider::new(creds.access_token.clone())))
+        });
+        registry.register("dropbox", |creds| {
+            Ok(Box::new(dropbox::DropboxProvider::new(creds.access_token.clone())))
+        });
+        registry.register("s3", |creds| {
+            Ok(Box::new(s3::S3Provider::new(connection_config(creds)?, creds.access_token.clone())))
+        });
+        registry.register("webdav", |creds| {
+            Ok(Box::new(webdav::WebDavProvider::new(connection_config(creds)?, creds.access_token.clone())?))
+        });
+        registry.register("sftp", |creds| {
+            Ok(Box::new(sftp::SftpProvider::new(connection_config(creds)?, creds.access_token.clone())))
+        });
+        registry.register("local", |creds| {
+            Ok(Box::new(local::LocalProvider::new(connection_config(creds)?)))
+        });
+
+        registry.register_auth("gdrive", gdrive::GoogleAuth::new(
+            crate::config::get_google_client_id(),
+            crate::config::get_google_client_secret(),
+        ));
+        registry.register_auth("onedrive", onedrive::OneDriveAuth::new(crate::config::get_onedrive_client_id()));
+        registry.register_auth("dropbox", dropbox::DropboxAuth::new(crate::config::get_dropbox_client_id()));
+
+        registry.register_form("s3", s3::S3Form);
+        registry.register_form("webdav", webdav::WebDavForm);
+        registry.register_form("sftp", sftp::SftpForm);
+        registry.register_form("local", local::LocalForm);
+        registry
+    }
+}
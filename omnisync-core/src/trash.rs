@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+/// Where displaced files are kept, relative to the pair root (ignored by every matcher)
+pub const TRASH_DIR: &str = ".omnisync/trash";
+
+/// Location of trash item `id`, which was called `name`, in the pair rooted at `root`
+pub fn item_path(root: &Path, id: i64, name: &str) -> PathBuf {
+    root.join(TRASH_DIR).join(id.to_string()).join(name)
+}
+
+/// Move a file or folder, creating the destination's parents
+pub async fn move_entry(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(from, to).await
+}
+
+/// Copy a file, creating the destination's parents
+pub async fn copy_entry(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::copy(from, to).await.map(|_| ())
+}
+
+/// Delete an item's folder in the trash with whatever it holds
+pub async fn remove_item(root: &Path, id: i64) -> std::io::Result<()> {
+    match tokio::fs::remove_dir_all(root.join(TRASH_DIR).join(id.to_string())).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
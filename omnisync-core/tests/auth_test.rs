@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use omnisync_core::auth::{AuthProvider, TokenResponse, UserInfo};
+use omnisync_core::SyncEngine;
+use sqlx::sqlite::SqlitePoolOptions;
+use std::sync::{Arc, Mutex};
+
+/// Sign-in that hands out numbered tokens and records what it was asked to do
+#[derive(Clone, Default)]
+struct MockAuth {
+    calls: Arc<Mutex<Vec<String>>>,
+    fail_refresh: bool,
+}
+
+impl MockAuth {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl AuthProvider for MockAuth {
+    fn authorize_url(&self, redirect_uri: &str, code_challenge: &str) -> anyhow::Result<String> {
+        Ok(format!("https://auth.example.com/?redirect_uri={}&challenge={}", redirect_uri, code_challenge))
+    }
+
+    async fn exchange_code(&self, code: &str, _redirect_uri: &str, code_verifier: &str) -> anyhow::Result<TokenResponse> {
+        self.calls.lock().unwrap().push(format!("exchange {} {}", code, code_verifier));
+        Ok(TokenResponse { access_token: "access-1".into(), refresh_token: Some("refresh-1".into()), expires_in: Some(3600) })
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> anyhow::Result<TokenResponse> {
+        self.calls.lock().unwrap().push(format!("refresh {}", refresh_token));
+        if self.fail_refresh {
+            anyhow::bail!("invalid_grant");
+        }
+        Ok(TokenResponse { access_token: "access-2".into(), refresh_token: Some("refresh-2".into()), expires_in: Some(3600) })
+    }
+
+    async fn user_info(&self, _access_token: &str) -> anyhow::Result<UserInfo> {
+        Ok(UserInfo { name: Some("Mock User".into()), email: Some("user@example.com".into()), avatar: None })
+    }
+
+    async fn revoke(&self, _access_token: &str, refresh_token: Option<&str>) -> anyhow::Result<()> {
+        self.calls.lock().unwrap().push(format!("revoke {}", refresh_token.unwrap_or_default()));
+        Ok(())
+    }
+}
+
+async fn engine_with(auth: &MockAuth) -> SyncEngine {
+    let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    let engine = SyncEngine::new(pool);
+    engine.register_auth("mock", auth.clone());
+    engine
+}
+
+#[tokio::test]
+async fn test_sign_in_stores_account() {
+    let auth = MockAuth::default();
+    let engine = engine_with(&auth).await;
+
+    let url = engine.oauth_authorize_url("mock", "challenge").unwrap();
+    assert!(url.contains("challenge=challenge"));
+    assert!(engine.oauth_authorize_url("local", "challenge").is_err());
+
+    let account_id = engine.complete_oauth("mock", "http://127.0.0.1:4420/?code=abc", "verifier").await.unwrap();
+    assert_eq!(account_id, "mock:user@example.com");
+    assert_eq!(auth.calls(), ["exchange abc verifier"]);
+
+    let creds = engine.get_credentials(&account_id).await.unwrap().unwrap();
+    assert_eq!(creds.access_token, "access-1");
+    assert_eq!(creds.refresh_token.as_deref(), Some("refresh-1"));
+    assert!(creds.expires_at.is_some());
+    assert_eq!(creds.user_name.as_deref(), Some("Mock User"));
+}
+
+#[tokio::test]
+async fn test_expiring_token_is_refreshed() {
+    let auth = MockAuth::default();
+    let engine = engine_with(&auth).await;
+    engine.set_credentials("mock:a", "mock", "access-1", Some("refresh-1"), Some(60), None, None, None).await.unwrap();
+
+    let creds = engine.get_valid_credentials("mock:a").await.unwrap().unwrap();
+    assert_eq!(creds.access_token, "access-2");
+    // Rotated refresh token and the missing user info are stored
+    let stored = engine.get_credentials("mock:a").await.unwrap().unwrap();
+    assert_eq!(stored.refresh_token.as_deref(), Some("refresh-2"));
+    assert_eq!(stored.user_email.as_deref(), Some("user@example.com"));
+
+    // Good for an hour now
+    engine.get_valid_credentials("mock:a").await.unwrap().unwrap();
+    assert_eq!(auth.calls(), ["refresh refresh-1"]);
+
+    // Types without a sign-in are left alone
+    engine.set_credentials("other:a", "other", "token", Some("refresh"), Some(60), None, None, None).await.unwrap();
+    assert_eq!(engine.get_valid_credentials("other:a").await.unwrap().unwrap().access_token, "token");
+}
+
+#[tokio::test]
+async fn test_failed_refresh() {
+    let auth = MockAuth { fail_refresh: true, ..Default::default() };
+    let engine = engine_with(&auth).await;
+
+    // Still valid for a while: the current token is used
+    engine.set_credentials("mock:a", "mock", "access-1", Some("refresh-1"), Some(300), None, None, None).await.unwrap();
+    assert_eq!(engine.get_valid_credentials("mock:a").await.unwrap().unwrap().access_token, "access-1");
+
+    // Expired: the account is disconnected
+    engine.set_credentials("mock:a", "mock", "access-1", Some("refresh-1"), Some(-10), None, None, None).await.unwrap();
+    assert!(engine.get_valid_credentials("mock:a").await.unwrap().is_none());
+    assert!(engine.get_credentials("mock:a").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sign_out_revokes() {
+    let auth = MockAuth::default();
+    let engine = engine_with(&auth).await;
+    engine.set_credentials("mock:a", "mock", "access-1", Some("refresh-1"), Some(3600), None, None, None).await.unwrap();
+
+    engine.sign_out("mock:a").await.unwrap();
+    assert_eq!(auth.calls(), ["revoke refresh-1"]);
+    assert!(engine.get_credentials("mock:a").await.unwrap().is_none());
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use common::{add_local_pair, ignore_status, memory_pool};
+use async_trait::async_trait;
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::models::Credentials;
+use omnisync_core::provider::{
+    ChangeSet, CloudError, CloudProvider, CloudResult, FileMetadata, RemoteChange, RemoteFile, RemoteFolder,
+};
+use omnisync_core::providers::local::LocalProvider;
+use omnisync_core::registry::connection_config;
+use omnisync_core::{SyncEngine, SyncPair};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// What the next `changes_since` call with a cursor answers
+#[derive(Clone)]
+enum Feed {
+    Changes(Vec<RemoteChange>),
+    Expired,
+}
+
+type Script = Arc<Mutex<Feed>>;
+
+/// A local target directory with a scripted change feed
+struct FeedProvider {
+    inner: LocalProvider,
+    script: Script,
+    cursors: Arc<Mutex<Vec<Option<String>>>>,
+    /// Listings fail while set, like a dropped connection would
+    offline: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl CloudProvider for FeedProvider {
+    fn id(&self) -> &str {
+        "local"
+    }
+
+    async fn upload_file(&self, local_path: &Path, cloud_path: &str) -> CloudResult<RemoteFile> {
+        self.inner.upload_file(local_path, cloud_path).await
+    }
+
+    async fn download_file(&self, file_id: &str, local_path: &Path) -> CloudResult<()> {
+        self.inner.download_file(file_id, local_path).await
+    }
+
+    async fn delete_file(&self, filename: &str, cloud_parent: &str) -> CloudResult<()> {
+        self.inner.delete_file(filename, cloud_parent).await
+    }
+
+    async fn get_metadata(&self, cloud_path: &str) -> CloudResult<FileMetadata> {
+        self.inner.get_metadata(cloud_path).await
+    }
+
+    async fn list_files(&self, folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        if self.offline.load(Ordering::Relaxed) {
+            return Err(CloudError::ApiError("connection reset".to_string()));
+        }
+        self.inner.list_files(folder_id).await
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        self.inner.list_folders().await
+    }
+
+    async fn create_folder(&self, name: &str, parent_id: &str) -> CloudResult<String> {
+        self.inner.create_folder(name, parent_id).await
+    }
+
+    async fn changes_since(&self, cursor: Option<&str>) -> CloudResult<Option<ChangeSet>> {
+        self.cursors.lock().unwrap().push(cursor.map(|c| c.to_string()));
+        let next = format!("c{}", self.cursors.lock().unwrap().len());
+        match (cursor, self.script.lock().unwrap().clone()) {
+            (None, _) => Ok(Some(ChangeSet { changes: Vec::new(), cursor: next })),
+            (Some(_), Feed::Changes(changes)) => Ok(Some(ChangeSet { changes, cursor: next })),
+            (Some(_), Feed::Expired) => Err(CloudError::InvalidCursor),
+        }
+    }
+}
+
+struct Setup {
+    engine: SyncEngine,
+    pair: SyncPair,
+    script: Script,
+    cursors: Arc<Mutex<Vec<Option<String>>>>,
+    offline: Arc<AtomicBool>,
+}
+
+async fn setup(local: &Path, remote: &Path) -> Setup {
+    let engine = SyncEngine::new(memory_pool().await);
+
+    let script: Script = Arc::new(Mutex::new(Feed::Changes(Vec::new())));
+    let cursors = Arc::new(Mutex::new(Vec::new()));
+    let offline = Arc::new(AtomicBool::new(false));
+    let (script_c, cursors_c, offline_c) = (script.clone(), cursors.clone(), offline.clone());
+    engine.register_provider("local", move |creds: &Credentials| {
+        Ok(Box::new(FeedProvider {
+            inner: LocalProvider::new(connection_config(creds)?),
+            script: script_c.clone(),
+            cursors: cursors_c.clone(),
+            offline: offline_c.clone(),
+        }) as Box<dyn CloudProvider>)
+    });
+
+    let pair = add_local_pair(&engine, local, remote).await;
+    Setup { engine, pair, script, cursors, offline }
+}
+
+fn changed(file_id: &str, parent: &str) -> RemoteChange {
+    RemoteChange {
+        file_id: file_id.to_string(),
+        removed: false,
+        name: file_id.rsplit('/').next().map(|n| n.to_string()),
+        parents: vec![parent.to_string()],
+    }
+}
+
+#[tokio::test]
+async fn test_poll_applies_only_reported_changes() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir(remote.path().join("docs")).unwrap();
+    fs::write(remote.path().join("docs/a.txt"), b"a").unwrap();
+    let s = setup(local.path(), remote.path()).await;
+    let account = s.pair.account_id.clone();
+
+    // No cursor yet: one full walk, starting from a fresh cursor
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+    assert!(local.path().join("docs/a.txt").exists());
+    assert_eq!(*s.cursors.lock().unwrap(), [None]);
+
+    fs::write(remote.path().join("docs/b.txt"), b"b").unwrap();
+    fs::remove_file(remote.path().join("docs/a.txt")).unwrap();
+    // Not reported by the feed, so not picked up by this poll
+    fs::write(remote.path().join("unreported.txt"), b"u").unwrap();
+    *s.script.lock().unwrap() = Feed::Changes(vec![
+        changed("docs/b.txt", "docs"),
+        RemoteChange { file_id: "docs/a.txt".to_string(), removed: true, name: None, parents: Vec::new() },
+        // Outside the pair
+        changed("elsewhere.txt", "some-other-folder"),
+    ]);
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+
+    assert_eq!(fs::read(local.path().join("docs/b.txt")).unwrap(), b"b");
+    assert!(!local.path().join("docs/a.txt").exists());
+    assert!(!local.path().join("unreported.txt").exists());
+    assert_eq!(*s.cursors.lock().unwrap(), [None, Some("c1".to_string())]);
+}
+
+#[tokio::test]
+async fn test_expired_cursor_falls_back_to_full_walk() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let s = setup(local.path(), remote.path()).await;
+    let account = s.pair.account_id.clone();
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+
+    fs::write(remote.path().join("unreported.txt"), b"u").unwrap();
+    *s.script.lock().unwrap() = Feed::Expired;
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+
+    assert!(local.path().join("unreported.txt").exists());
+    // A new cursor was taken for the next poll
+    assert_eq!(*s.cursors.lock().unwrap(), [None, Some("c1".to_string()), None]);
+}
+
+#[tokio::test]
+async fn test_failed_pass_keeps_cursor() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir(remote.path().join("docs")).unwrap();
+    let s = setup(local.path(), remote.path()).await;
+    let account = s.pair.account_id.clone();
+
+    // A failed full walk stores no cursor, so the next poll walks again
+    s.offline.store(true, Ordering::Relaxed);
+    assert!(s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.is_err());
+    s.offline.store(false, Ordering::Relaxed);
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+    assert_eq!(*s.cursors.lock().unwrap(), [None, None]);
+
+    // A failed delta pass keeps the old cursor, so the same changes are handed over again
+    fs::write(remote.path().join("docs/b.txt"), b"b").unwrap();
+    *s.script.lock().unwrap() = Feed::Changes(vec![changed("docs/b.txt", "docs")]);
+    s.offline.store(true, Ordering::Relaxed);
+    assert!(s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.is_err());
+    assert!(!local.path().join("docs/b.txt").exists());
+
+    s.offline.store(false, Ordering::Relaxed);
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+    assert_eq!(fs::read(local.path().join("docs/b.txt")).unwrap(), b"b");
+    assert_eq!(
+        *s.cursors.lock().unwrap(),
+        [None, None, Some("c2".to_string()), Some("c2".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_remote_move_renames_local_copy() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir(remote.path().join("docs")).unwrap();
+    fs::create_dir(remote.path().join("archive")).unwrap();
+    fs::write(remote.path().join("docs/report.txt"), b"report").unwrap();
+    let s = setup(local.path(), remote.path()).await;
+    let account = s.pair.account_id.clone();
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), ignore_status()).await.unwrap();
+
+    // A feed that reports the entry under its old id with a new name and parent, like Drive does
+    fs::rename(remote.path().join("docs/report.txt"), remote.path().join("archive/2024.txt")).unwrap();
+    *s.script.lock().unwrap() = Feed::Changes(vec![RemoteChange {
+        file_id: "docs/report.txt".to_string(),
+        removed: false,
+        name: Some("2024.txt".to_string()),
+        parents: vec!["archive".to_string()],
+    }]);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let on_status = Arc::new(move |status| sink.lock().unwrap().push(status));
+    s.engine.poll_remote_changes(&account, std::slice::from_ref(&s.pair), on_status).await.unwrap();
+
+    assert!(!local.path().join("docs/report.txt").exists());
+    assert_eq!(fs::read(local.path().join("archive/2024.txt")).unwrap(), b"report");
+    let keys: Vec<String> = s.engine.get_file_states(s.pair.id).await.unwrap().into_iter().map(|s| s.path).collect();
+    assert!(keys.contains(&"archive/2024.txt".to_string()));
+    assert!(!keys.contains(&"docs/report.txt".to_string()));
+    assert!(events.lock().unwrap().iter().any(|e| matches!(e, SyncStatus::Moved { .. })));
+}
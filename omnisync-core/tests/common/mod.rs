@@ -0,0 +1,50 @@
+// Shared by the engine tests; each test binary only uses part of it
+#![allow(dead_code)]
+
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::{SyncEngine, SyncPair};
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A fresh in-memory database with the migrations applied
+pub async fn memory_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
+
+/// Add a pair syncing `local` against a local-folder account on the `remote` directory
+pub async fn add_local_pair(engine: &SyncEngine, local: &Path, remote: &Path) -> SyncPair {
+    let account_id = engine
+        .connect_account("local", json!({ "path": remote.to_str().unwrap() }), "")
+        .await
+        .unwrap();
+    let id = engine
+        .add_sync_pair(local.to_str().unwrap(), "root", "Backup", "local", &account_id)
+        .await
+        .unwrap();
+    engine.get_sync_pair(id).await.unwrap().unwrap()
+}
+
+/// An engine with a pair syncing `local` against the `remote` directory
+pub async fn setup(local: &Path, remote: &Path) -> (SyncEngine, SyncPair) {
+    let engine = SyncEngine::new(memory_pool().await);
+    let pair = add_local_pair(&engine, local, remote).await;
+    (engine, pair)
+}
+
+pub fn ignore_status() -> Arc<impl Fn(SyncStatus) + Send + Sync + 'static> {
+    Arc::new(|_| {})
+}
+
+pub type Events = Arc<Mutex<Vec<SyncStatus>>>;
+
+/// Status callback that records every event
+pub fn recorder() -> (Arc<impl Fn(SyncStatus) + Send + Sync + 'static>, Events) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    (Arc::new(move |status| sink.lock().unwrap().push(status)), events)
+}
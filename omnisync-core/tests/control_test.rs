@@ -0,0 +1,169 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::add_local_pair;
+use omnisync_core::control::{ControlClient, ControlServer};
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::models::{SyncMode, TrashItem};
+use omnisync_core::reconcile::{SyncAction, SyncPlan};
+use omnisync_core::{SyncEngine, SyncPair};
+use serde_json::{json, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::fs;
+use std::path::Path;
+
+/// An engine with one pair between `local` and a local-folder account on `remote`
+async fn engine_with_pair(dir: &Path, local: &Path, remote: &Path) -> (SyncEngine, i64) {
+    let options = SqliteConnectOptions::new().filename(dir.join("omnisync.db")).create_if_missing(true);
+    let pool = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    let engine = SyncEngine::new(pool);
+
+    let pair = add_local_pair(&engine, local, remote).await;
+    (engine, pair.id)
+}
+
+async fn serve(engine: SyncEngine, socket: &Path) -> ControlClient {
+    let server = ControlServer::bind(engine, socket).await.unwrap();
+    tokio::spawn(server.run());
+    ControlClient::connect(socket).await.unwrap()
+}
+
+async fn pair_status(client: &mut ControlClient, id: i64) -> String {
+    let pairs: Vec<SyncPair> = serde_json::from_value(client.call("list_pairs", Value::Null).await.unwrap()).unwrap();
+    pairs.into_iter().find(|p| p.id == id).unwrap().status
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_over_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let (engine, id) = engine_with_pair(dir.path(), local.path(), remote.path()).await;
+    let mut client = serve(engine, &dir.path().join("daemon.sock")).await;
+
+    assert_eq!(pair_status(&mut client, id).await, "active");
+    client.call("pause", json!({ "pair_id": id })).await.unwrap();
+    assert_eq!(pair_status(&mut client, id).await, "paused");
+    client.call("resume", json!({ "pair_id": id })).await.unwrap();
+    assert_eq!(pair_status(&mut client, id).await, "active");
+
+    let err = client.call("pause", json!({ "pair_id": id + 100 })).await.unwrap_err();
+    assert!(err.to_string().contains("No sync pair with id"));
+    assert!(client.call("pause", Value::Null).await.is_err());
+    assert!(client.call("reboot", Value::Null).await.is_err());
+}
+
+#[tokio::test]
+async fn test_sync_streams_events_to_subscribers() {
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    fs::write(local.path().join("notes.txt"), b"notes").unwrap();
+    let (engine, id) = engine_with_pair(dir.path(), local.path(), remote.path()).await;
+    let socket = dir.path().join("daemon.sock");
+    let mut client = serve(engine, &socket).await;
+
+    // A second client only listening
+    let mut listener = ControlClient::connect(&socket).await.unwrap();
+    listener.subscribe().await.unwrap();
+
+    client.subscribe().await.unwrap();
+    let mut events = Vec::new();
+    client.call_with_events("sync", json!({ "pair_id": id }), |status| events.push(status)).await.unwrap();
+    assert_eq!(fs::read(remote.path().join("notes.txt")).unwrap(), b"notes");
+    assert!(events.iter().any(|e| matches!(e, SyncStatus::Uploaded { path, .. } if path.ends_with("notes.txt"))));
+
+    let heard = listener.next_event().await.unwrap().unwrap();
+    assert_eq!(heard.pair_id(), Some(id));
+
+    let status = client.call("status", Value::Null).await.unwrap();
+    let pair = &status["pairs"][0];
+    assert_eq!(pair["pair_id"], id);
+    assert_eq!(pair["status"], "active");
+    // The latest event of the pair: the upload, or Idle a moment later
+    assert_eq!(pair["activity"]["data"]["pair_id"], id);
+}
+
+#[tokio::test]
+async fn test_pairs_are_managed_over_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    fs::write(local.path().join("notes.txt"), b"notes").unwrap();
+    let (engine, id) = engine_with_pair(dir.path(), local.path(), remote.path()).await;
+    let account = engine.get_sync_pair(id).await.unwrap().unwrap().account_id;
+    let mut client = serve(engine, &dir.path().join("daemon.sock")).await;
+
+    let other = tempfile::tempdir().unwrap();
+    let params = json!({
+        "local": other.path(),
+        "remote": "root",
+        "account": account,
+        "mode": "upload_only",
+        "paused": true,
+    });
+    let added: SyncPair = serde_json::from_value(client.call("add_pair", params).await.unwrap()).unwrap();
+    assert_eq!(added.status, "paused");
+    assert_eq!(added.sync_mode(), SyncMode::UploadOnly);
+    assert_eq!(added.remote_name, "root");
+
+    client.call("set_mode", json!({ "pair_id": added.id, "mode": "mirror" })).await.unwrap();
+    let pairs: Vec<SyncPair> = serde_json::from_value(client.call("list_pairs", Value::Null).await.unwrap()).unwrap();
+    assert_eq!(pairs.iter().find(|p| p.id == added.id).unwrap().sync_mode(), SyncMode::Mirror);
+
+    client.call("remove_pair", json!({ "pair_id": added.id })).await.unwrap();
+    let err = client.call("remove_pair", json!({ "pair_id": added.id })).await.unwrap_err();
+    assert!(err.to_string().contains("No sync pair with id"));
+    let err = client.call("add_pair", json!({ "local": other.path(), "remote": "root", "account": "local:nobody" })).await.unwrap_err();
+    assert!(err.to_string().contains("No account"));
+    assert!(client.call("add_pair", json!({ "remote": "root", "account": account })).await.is_err());
+    assert!(client.call("set_mode", json!({ "pair_id": id, "mode": "sideways" })).await.is_err());
+
+    // A dry run changes nothing
+    let plan: SyncPlan = serde_json::from_value(client.call("plan", json!({ "pair_id": id })).await.unwrap()).unwrap();
+    assert_eq!(plan.actions, [SyncAction::Upload { path: "notes.txt".to_string(), size: Some(5) }]);
+    assert!(!remote.path().join("notes.txt").exists());
+}
+
+#[tokio::test]
+async fn test_trash_and_deletions_over_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    fs::write(remote.path().join("report.txt"), b"report").unwrap();
+    let (engine, id) = engine_with_pair(dir.path(), local.path(), remote.path()).await;
+    let mut client = serve(engine, &dir.path().join("daemon.sock")).await;
+
+    client.call("sync", json!({ "pair_id": id })).await.unwrap();
+    fs::remove_file(remote.path().join("report.txt")).unwrap();
+    client.call("sync", json!({ "pair_id": id })).await.unwrap();
+    assert!(!local.path().join("report.txt").exists());
+    let pending: Vec<String> = serde_json::from_value(client.call("deletions", json!({ "pair_id": id })).await.unwrap()).unwrap();
+    assert!(pending.is_empty());
+
+    let items: Vec<TrashItem> = serde_json::from_value(client.call("trash", json!({ "pair_id": id })).await.unwrap()).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].path, "report.txt");
+    client.call("restore_trash", json!({ "item_id": items[0].id })).await.unwrap();
+    assert_eq!(fs::read(local.path().join("report.txt")).unwrap(), b"report");
+
+    client.call("set_trash_retention", json!({ "pair_id": id, "days": 7 })).await.unwrap();
+    client.call("set_permanent_delete", json!({ "pair_id": id, "permanent": true })).await.unwrap();
+    assert!(client.call("set_permanent_delete", json!({ "pair_id": id })).await.is_err());
+    let remote_items = client.call("trash", json!({ "pair_id": id, "remote": true })).await.unwrap();
+    assert_eq!(remote_items, json!([]));
+}
+
+#[tokio::test]
+async fn test_one_daemon_per_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let (engine, _) = engine_with_pair(dir.path(), local.path(), remote.path()).await;
+    let socket = dir.path().join("daemon.sock");
+
+    // Left behind by a daemon that is gone
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    assert!(socket.exists());
+    let _client = serve(engine.clone(), &socket).await;
+
+    let err = ControlServer::bind(engine, &socket).await.err().unwrap();
+    assert!(err.to_string().contains("already running"));
+}
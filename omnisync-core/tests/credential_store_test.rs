@@ -0,0 +1,80 @@
+use omnisync_core::credential_store::{CredentialStore, MemoryCredentialStore};
+use omnisync_core::SyncEngine;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+
+async fn setup() -> (SyncEngine, SqlitePool) {
+    let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    (SyncEngine::new(pool.clone()), pool)
+}
+
+/// (access_token, refresh_token) as they sit in the database
+async fn stored_tokens(pool: &SqlitePool, account_id: &str) -> (String, Option<String>) {
+    sqlx::query_as("SELECT access_token, refresh_token FROM credentials WHERE account_id = ?")
+        .bind(account_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_tokens_stay_out_of_database() {
+    let (engine, pool) = setup().await;
+    let store = Arc::new(MemoryCredentialStore::default());
+    assert_eq!(engine.use_credential_store(store.clone()).await.unwrap(), 0);
+
+    engine
+        .set_credentials("gdrive:a@example.com", "gdrive", "access", Some("refresh"), Some(3600), None, Some("a@example.com".into()), None)
+        .await
+        .unwrap();
+    assert_eq!(stored_tokens(&pool, "gdrive:a@example.com").await, (String::new(), None));
+    assert!(store.get("gdrive:a@example.com").unwrap().unwrap().contains("refresh"));
+
+    let creds = engine.get_credentials("gdrive:a@example.com").await.unwrap().unwrap();
+    assert_eq!(creds.access_token, "access");
+    assert_eq!(creds.refresh_token.as_deref(), Some("refresh"));
+    assert_eq!(creds.user_email.as_deref(), Some("a@example.com"));
+
+    engine.disconnect_account("gdrive:a@example.com").await.unwrap();
+    assert!(store.get("gdrive:a@example.com").unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_existing_tokens_move_to_store() {
+    let (engine, pool) = setup().await;
+    engine
+        .set_credentials("gdrive:a@example.com", "gdrive", "access", Some("refresh"), Some(3600), None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(stored_tokens(&pool, "gdrive:a@example.com").await.0, "access");
+
+    let store = Arc::new(MemoryCredentialStore::default());
+    assert_eq!(engine.use_credential_store(store.clone()).await.unwrap(), 1);
+    assert_eq!(stored_tokens(&pool, "gdrive:a@example.com").await, (String::new(), None));
+    let creds = engine.get_credentials("gdrive:a@example.com").await.unwrap().unwrap();
+    assert_eq!((creds.access_token.as_str(), creds.refresh_token.as_deref()), ("access", Some("refresh")));
+
+    // Moved once
+    assert_eq!(engine.use_credential_store(store).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_stored_tokens_need_the_store() {
+    let (engine, pool) = setup().await;
+    engine.use_credential_store(Arc::new(MemoryCredentialStore::default())).await.unwrap();
+    engine
+        .set_credentials("gdrive:a@example.com", "gdrive", "access", None, None, Some("A".into()), None, None)
+        .await
+        .unwrap();
+
+    // Another engine on the same database, without the keyring
+    let other = SyncEngine::new(pool);
+    assert!(other.get_credentials("gdrive:a@example.com").await.is_err());
+    // Listing needs no tokens
+    let accounts = other.get_all_accounts().await.unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].user_name.as_deref(), Some("A"));
+    assert!(accounts[0].access_token.is_empty());
+}
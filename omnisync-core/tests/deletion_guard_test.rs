@@ -0,0 +1,127 @@
+mod common;
+
+use common::{ignore_status, recorder, setup};
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::{SyncEngine, SyncPair};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A synced pair with twelve files in "photos"
+async fn synced_pair(local: &Path, remote: &Path) -> (SyncEngine, SyncPair) {
+    let (engine, pair) = setup(local, remote).await;
+
+    fs::create_dir(remote.join("photos")).unwrap();
+    for i in 0..12 {
+        fs::write(remote.join(format!("photos/{}.jpg", i)), format!("photo {}", i)).unwrap();
+    }
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(local.join("photos/11.jpg").exists());
+    (engine, pair)
+}
+
+#[tokio::test]
+async fn test_emptied_folder_pauses_pair_until_restored() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+
+    fs::remove_dir_all(local.path().join("photos")).unwrap();
+    let (on_status, events) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+
+    assert!(remote.path().join("photos/0.jpg").exists());
+    assert_eq!(engine.get_sync_pair(pair.id).await.unwrap().unwrap().status, "paused");
+    assert!(events.lock().unwrap().iter().any(|e| matches!(e, SyncStatus::DeletionsPending { count: 12, .. })));
+    let pending = engine.pending_deletions(pair.id).await.unwrap();
+    assert_eq!(pending.len(), 13);
+    assert!(pending.contains(&"photos".to_string()));
+    assert!(pending.contains(&"photos/7.jpg".to_string()));
+
+    engine.restore_deletions(pair.id, on_status).await.unwrap();
+    assert_eq!(fs::read(local.path().join("photos/7.jpg")).unwrap(), b"photo 7");
+    assert!(remote.path().join("photos/7.jpg").exists());
+    assert_eq!(engine.get_sync_pair(pair.id).await.unwrap().unwrap().status, "active");
+    assert!(engine.pending_deletions(pair.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_confirmed_deletions_go_through_once() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+
+    fs::remove_dir_all(local.path().join("photos")).unwrap();
+    let (on_status, _) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+    assert!(remote.path().join("photos").exists());
+
+    engine.confirm_deletions(pair.id, on_status).await.unwrap();
+    assert!(!remote.path().join("photos").exists());
+    assert_eq!(engine.get_sync_pair(pair.id).await.unwrap().unwrap().status, "active");
+}
+
+#[tokio::test]
+async fn test_deletions_within_limits_are_synced() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+
+    for i in 0..2 {
+        fs::remove_file(local.path().join(format!("photos/{}.jpg", i))).unwrap();
+    }
+    engine.perform_initial_sync(&pair, Arc::new(|_| {})).await.unwrap();
+    assert!(!remote.path().join("photos/0.jpg").exists());
+    assert!(remote.path().join("photos/2.jpg").exists());
+
+    // A lower limit trips on the same kind of change
+    engine.set_delete_limits(pair.id, 1, 0).await.unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    for i in 2..4 {
+        fs::remove_file(local.path().join(format!("photos/{}.jpg", i))).unwrap();
+    }
+    engine.perform_initial_sync(&pair, Arc::new(|_| {})).await.unwrap();
+    assert!(remote.path().join("photos/2.jpg").exists());
+    assert_eq!(engine.get_sync_pair(pair.id).await.unwrap().unwrap().status, "paused");
+}
+
+/// Wait up to five seconds for `done`
+async fn eventually(done: impl Fn() -> bool) -> bool {
+    for _ in 0..50 {
+        if done() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_watcher_deletions_count_across_batches() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+    engine.set_delete_limits(pair.id, 3, 0).await.unwrap();
+    let (on_status, events) = recorder();
+    let watching = engine.clone();
+    let daemon = tokio::spawn(async move { watching.start(move |e| on_status(e)).await });
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    // Within the limit on its own
+    for i in 0..2 {
+        fs::remove_file(local.path().join(format!("photos/{}.jpg", i))).unwrap();
+    }
+    assert!(eventually(|| !remote.path().join("photos/1.jpg").exists()).await);
+
+    // Two more in a later batch make four deletions in a row
+    for i in 2..4 {
+        fs::remove_file(local.path().join(format!("photos/{}.jpg", i))).unwrap();
+    }
+    assert!(eventually(|| events.lock().unwrap().iter().any(|e| matches!(e, SyncStatus::DeletionsPending { count: 4, .. }))).await);
+    daemon.abort();
+
+    assert!(remote.path().join("photos/2.jpg").exists());
+    assert!(remote.path().join("photos/3.jpg").exists());
+    assert_eq!(engine.get_sync_pair(pair.id).await.unwrap().unwrap().status, "paused");
+}
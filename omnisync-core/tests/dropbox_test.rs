@@ -0,0 +1,200 @@
+use omnisync_core::hashing::{hash_file, DropboxContentHasher};
+use omnisync_core::provider::{CloudProvider, HashKind};
+use omnisync_core::providers::dropbox::DropboxProvider;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use wiremock::matchers::{body_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn provider(server: &MockServer) -> DropboxProvider {
+    DropboxProvider::with_base_urls("token".to_string(), &server.uri(), &server.uri())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[tokio::test]
+async fn test_list_files_follows_cursor() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/files/list_folder"))
+        .and(header("authorization", "Bearer token"))
+        .and(body_json(json!({ "path": "", "recursive": false, "include_deleted": false, "limit": 2000 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "entries": [{
+                ".tag": "file",
+                "name": "a.txt",
+                "path_display": "/a.txt",
+                "size": 5,
+                "server_modified": "2024-01-01T00:00:00Z",
+                "content_hash": "abc"
+            }],
+            "cursor": "c1",
+            "has_more": true
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/files/list_folder/continue"))
+        .and(body_json(json!({ "cursor": "c1" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "entries": [
+                { ".tag": "folder", "name": "Docs", "path_display": "/Docs" },
+                { ".tag": "deleted", "name": "old.txt", "path_display": "/old.txt" }
+            ],
+            "cursor": "c2",
+            "has_more": false
+        })))
+        .mount(&server)
+        .await;
+
+    let files = provider(&server).list_files("root").await.unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].id, "/a.txt");
+    assert_eq!(files[0].hash.as_deref(), Some("abc"));
+    assert_eq!(files[0].modified_at, Some(1704067200));
+    assert!(files[1].is_dir);
+    // Folder ids are paths, usable directly as a parent for further calls
+    assert_eq!(files[1].id, "/Docs");
+}
+
+#[tokio::test]
+async fn test_upload_sends_path_in_api_arg() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/files/upload"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "résumé.txt",
+            "path_display": "/Docs/résumé.txt",
+            "size": 5,
+            "content_hash": "def"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("résumé.txt");
+    std::fs::write(&file, b"hello").unwrap();
+
+    let remote = provider(&server).upload_file(&file, "/Docs").await.unwrap();
+    assert_eq!(remote.id, "/Docs/résumé.txt");
+    assert!(!remote.is_dir);
+
+    let requests = server.received_requests().await.unwrap();
+    let arg = requests[0].headers.get("dropbox-api-arg").unwrap().to_str().unwrap();
+    // Header values must be ASCII; non-ASCII characters are JSON-escaped
+    let parsed: serde_json::Value = serde_json::from_str(arg).unwrap();
+    assert_eq!(parsed["path"], "/Docs/résumé.txt");
+    assert_eq!(parsed["mode"], "overwrite");
+    assert!(arg.is_ascii());
+    assert_eq!(requests[0].body, b"hello");
+}
+
+#[tokio::test]
+async fn test_large_upload_uses_session() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/files/upload_session/start"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "session_id": "s1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/files/upload_session/append_v2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/files/upload_session/finish"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "big.bin",
+            "path_display": "/big.bin",
+            "content_hash": "big"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("big.bin");
+    std::fs::write(&file, vec![1u8; 8 * 1024 * 1024 + 10]).unwrap();
+
+    let remote = provider(&server).upload_file(&file, "").await.unwrap();
+    assert_eq!(remote.id, "/big.bin");
+
+    let requests = server.received_requests().await.unwrap();
+    let finish = requests.iter().find(|r| r.url.path() == "/files/upload_session/finish").unwrap();
+    let arg: serde_json::Value = serde_json::from_str(finish.headers.get("dropbox-api-arg").unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(arg["cursor"]["offset"], 8 * 1024 * 1024 + 10);
+    assert_eq!(arg["commit"]["path"], "/big.bin");
+}
+
+#[tokio::test]
+async fn test_delete_and_create_folder_conflicts() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/files/delete_v2"))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "error_summary": "path_lookup/not_found/.."
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/files/create_folder_v2"))
+        .and(body_json(json!({ "path": "/Photos", "autorename": false })))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "error_summary": "path/conflict/folder/.."
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = provider(&server);
+    // Already gone is not an error
+    provider.delete_file("gone.txt", "/").await.unwrap();
+    assert_eq!(provider.create_folder("Photos", "root").await.unwrap(), "/Photos");
+}
+
+#[tokio::test]
+async fn test_download() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/files/download"))
+        .and(header("dropbox-api-arg", r#"{"path":"/a.txt"}"#))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"contents".to_vec()))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("a.txt");
+    provider(&server).download_file("/a.txt", &dest).await.unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), b"contents");
+}
+
+#[tokio::test]
+async fn test_content_hash() {
+    // Empty content: SHA-256 of no block digests
+    assert_eq!(DropboxContentHasher::new().finalize(), hex(&Sha256::digest(b"")));
+
+    // Two blocks: 4 MiB + 1 byte
+    let block = 4 * 1024 * 1024;
+    let data: Vec<u8> = (0..block + 1).map(|i| (i % 253) as u8).collect();
+    let mut concat = Vec::new();
+    concat.extend_from_slice(&Sha256::digest(&data[..block]));
+    concat.extend_from_slice(&Sha256::digest(&data[block..]));
+    let expected = hex(&Sha256::digest(&concat));
+
+    let mut chunked = DropboxContentHasher::new();
+    for chunk in data.chunks(1_000_003) {
+        chunked.update(chunk);
+    }
+    assert_eq!(chunked.finalize(), expected);
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.bin");
+    std::fs::write(&file, &data).unwrap();
+    assert_eq!(hash_file(&file, HashKind::Dropbox).await.unwrap(), expected);
+}
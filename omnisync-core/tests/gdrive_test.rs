@@ -0,0 +1,471 @@
+use async_trait::async_trait;
+use omnisync_core::provider::{CloudError, CloudProvider, RemoteFile, Transfer, UploadKey, UploadSessionStore};
+use omnisync_core::providers::gdrive::{GoogleDriveProvider, UPLOAD_CHUNK_SIZE};
+use serde_json::json;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Larger than the resumable threshold and spanning two chunks
+const LARGE: u64 = UPLOAD_CHUNK_SIZE + 1024 * 1024;
+
+fn provider(server: &MockServer) -> GoogleDriveProvider {
+    GoogleDriveProvider::with_base_urls("token".to_string(), &server.uri(), &server.uri())
+}
+
+/// Session store kept in memory, holding at most one session
+#[derive(Default)]
+struct MemorySessions {
+    uri: Mutex<Option<String>>,
+}
+
+#[async_trait]
+impl UploadSessionStore for MemorySessions {
+    async fn load(&self, _key: &UploadKey) -> Option<String> {
+        self.uri.lock().unwrap().clone()
+    }
+
+    async fn save(&self, _key: &UploadKey, session_uri: &str) {
+        *self.uri.lock().unwrap() = Some(session_uri.to_string());
+    }
+
+    async fn clear(&self, _key: &UploadKey) {
+        *self.uri.lock().unwrap() = None;
+    }
+}
+
+type Reports = Arc<Mutex<Vec<(u64, u64)>>>;
+
+fn transfer(sessions: Arc<MemorySessions>) -> (Transfer, Reports) {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let sink = reports.clone();
+    let transfer = Transfer {
+        sessions: Some(sessions),
+        progress: Some(Arc::new(move |sent, total| sink.lock().unwrap().push((sent, total)))),
+    };
+    (transfer, reports)
+}
+
+async fn mock_empty_search(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": [] })))
+        .mount(server)
+        .await;
+}
+
+fn uploaded(size: u64) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "file-1",
+        "name": "big.bin",
+        "mimeType": "application/octet-stream",
+        "size": size.to_string(),
+        "modifiedTime": "2024-01-01T00:00:00Z",
+        "md5Checksum": "abc"
+    }))
+}
+
+fn write_file(dir: &Path, name: &str, size: u64) -> std::path::PathBuf {
+    let file = dir.join(name);
+    std::fs::write(&file, vec![7u8; size as usize]).unwrap();
+    file
+}
+
+#[tokio::test]
+async fn test_small_file_uses_multipart() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/files"))
+        .and(query_param("uploadType", "multipart"))
+        .respond_with(uploaded(5))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "small.txt", 5);
+    let (transfer, reports) = transfer(Arc::default());
+
+    let remote = provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap();
+    assert_eq!(remote.id, "file-1");
+    assert_eq!(*reports.lock().unwrap(), [(5, 5)]);
+}
+
+#[tokio::test]
+async fn test_large_file_uploads_in_chunks() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    let session = format!("{}/session/1", server.uri());
+    Mock::given(method("POST"))
+        .and(path("/files"))
+        .and(query_param("uploadType", "resumable"))
+        .and(header("x-upload-content-length", LARGE.to_string().as_str()))
+        .respond_with(ResponseTemplate::new(200).insert_header("location", session.as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/1"))
+        .and(header("content-range", format!("bytes 0-{}/{}", UPLOAD_CHUNK_SIZE - 1, LARGE).as_str()))
+        .respond_with(ResponseTemplate::new(308).insert_header("range", format!("bytes=0-{}", UPLOAD_CHUNK_SIZE - 1).as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/1"))
+        .and(header("content-range", format!("bytes {}-{}/{}", UPLOAD_CHUNK_SIZE, LARGE - 1, LARGE).as_str()))
+        .respond_with(uploaded(LARGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "big.bin", LARGE);
+    let sessions = Arc::new(MemorySessions::default());
+    let (transfer, reports) = transfer(sessions.clone());
+
+    let remote = provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap();
+    assert_eq!(remote.size, Some(LARGE));
+    assert_eq!(*reports.lock().unwrap(), [(0, LARGE), (UPLOAD_CHUNK_SIZE, LARGE), (LARGE, LARGE)]);
+    // A finished session is forgotten
+    assert_eq!(*sessions.uri.lock().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_stored_session_resumes_at_server_offset() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    let session = format!("{}/session/2", server.uri());
+    // No new session is started
+    Mock::given(method("POST"))
+        .and(path("/files"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/2"))
+        .and(header("content-range", format!("bytes */{}", LARGE).as_str()))
+        .respond_with(ResponseTemplate::new(308).insert_header("range", format!("bytes=0-{}", UPLOAD_CHUNK_SIZE - 1).as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // Only the remainder is sent
+    Mock::given(method("PUT"))
+        .and(path("/session/2"))
+        .and(header("content-range", format!("bytes {}-{}/{}", UPLOAD_CHUNK_SIZE, LARGE - 1, LARGE).as_str()))
+        .respond_with(uploaded(LARGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "big.bin", LARGE);
+    let sessions = Arc::new(MemorySessions { uri: Mutex::new(Some(session)) });
+    let (transfer, reports) = transfer(sessions.clone());
+
+    provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap();
+    assert_eq!(*reports.lock().unwrap(), [(UPLOAD_CHUNK_SIZE, LARGE), (LARGE, LARGE)]);
+    assert_eq!(*sessions.uri.lock().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_expired_session_starts_over() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    let fresh = format!("{}/session/new", server.uri());
+    Mock::given(method("PUT"))
+        .and(path("/session/old"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/files"))
+        .and(query_param("uploadType", "resumable"))
+        .respond_with(ResponseTemplate::new(200).insert_header("location", fresh.as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/new"))
+        .and(header("content-range", format!("bytes 0-{}/{}", UPLOAD_CHUNK_SIZE - 1, LARGE).as_str()))
+        .respond_with(ResponseTemplate::new(308).insert_header("range", format!("bytes=0-{}", UPLOAD_CHUNK_SIZE - 1).as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/new"))
+        .and(header("content-range", format!("bytes {}-{}/{}", UPLOAD_CHUNK_SIZE, LARGE - 1, LARGE).as_str()))
+        .respond_with(uploaded(LARGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "big.bin", LARGE);
+    let sessions = Arc::new(MemorySessions { uri: Mutex::new(Some(format!("{}/session/old", server.uri()))) });
+    let (transfer, _) = transfer(sessions.clone());
+
+    let remote = provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap();
+    assert_eq!(remote.id, "file-1");
+}
+
+#[tokio::test]
+async fn test_session_with_every_byte_is_finished_without_sending() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    let query = format!("bytes */{}", LARGE);
+    // The stored session already has the whole file but was never finalized
+    Mock::given(method("PUT"))
+        .and(path("/session/3"))
+        .and(header("content-range", query.as_str()))
+        .respond_with(ResponseTemplate::new(308).insert_header("range", format!("bytes=0-{}", LARGE - 1).as_str()))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/session/3"))
+        .and(header("content-range", query.as_str()))
+        .respond_with(uploaded(LARGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "big.bin", LARGE);
+    let sessions = Arc::new(MemorySessions { uri: Mutex::new(Some(format!("{}/session/3", server.uri()))) });
+    let (transfer, _) = transfer(sessions.clone());
+
+    let remote = provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap();
+    assert_eq!(remote.id, "file-1");
+    assert_eq!(*sessions.uri.lock().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_session_with_more_bytes_than_the_file_is_rejected() {
+    let server = MockServer::start().await;
+    mock_empty_search(&server).await;
+    Mock::given(method("PUT"))
+        .and(path("/session/4"))
+        .respond_with(ResponseTemplate::new(308).insert_header("range", format!("bytes=0-{}", LARGE + 9).as_str()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_file(dir.path(), "big.bin", LARGE);
+    let sessions = Arc::new(MemorySessions { uri: Mutex::new(Some(format!("{}/session/4", server.uri()))) });
+    let (transfer, _) = transfer(sessions.clone());
+
+    let err = provider(&server).upload_file_with(&file, "root", &transfer).await.unwrap_err();
+    assert!(matches!(err, CloudError::ApiError(_)));
+    assert_eq!(*sessions.uri.lock().unwrap(), None);
+}
+
+fn listed(hash: &str) -> RemoteFile {
+    RemoteFile {
+        id: "file-1".to_string(),
+        name: "notes.txt".to_string(),
+        is_dir: false,
+        size: Some(5),
+        modified_at: None,
+        hash: Some(hash.to_string()),
+        revision: None,
+    }
+}
+
+#[tokio::test]
+async fn test_download_is_verified_and_renamed_into_place() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/files/file-1"))
+        .and(query_param("alt", "media"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"hello".to_vec()))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("notes.txt");
+    // md5("hello")
+    provider(&server)
+        .download_remote(&listed("5d41402abc4b2a76b9719d911017c592"), &dest)
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&dest).unwrap(), b"hello");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_corrupt_download_keeps_existing_file() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/files/file-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"jello".to_vec()))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("notes.txt");
+    std::fs::write(&dest, b"previous").unwrap();
+
+    let err = provider(&server)
+        .download_remote(&listed("5d41402abc4b2a76b9719d911017c592"), &dest)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Checksum mismatch"));
+    assert_eq!(std::fs::read(&dest).unwrap(), b"previous");
+    // The partial download is cleaned up
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_changes_follow_pages_and_alias_root() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/files/root"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "0AROOT" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/changes"))
+        .and(query_param("pageToken", "100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "nextPageToken": "101",
+            "changes": [{ "fileId": "f1", "removed": false, "file": { "parents": ["0AROOT"], "trashed": false } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/changes"))
+        .and(query_param("pageToken", "101"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "newStartPageToken": "102",
+            "changes": [
+                { "fileId": "f2", "removed": true },
+                { "fileId": "f3", "removed": false, "file": { "parents": ["folder-1"], "trashed": true } }
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let set = provider(&server).changes_since(Some("100")).await.unwrap().unwrap();
+    assert_eq!(set.cursor, "102");
+    assert_eq!(set.changes.len(), 3);
+    assert_eq!(set.changes[0].parents, ["root"]);
+    assert!(set.changes[1].removed);
+    // Trashed counts as removed
+    assert!(set.changes[2].removed);
+    assert!(set.changes[2].parents.is_empty());
+}
+
+#[tokio::test]
+async fn test_rejected_page_token_is_invalid_cursor() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/changes/startPageToken"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "startPageToken": "7" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/files/root"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "0AROOT" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/changes"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({ "error": { "message": "Invalid Value" } })))
+        .mount(&server)
+        .await;
+
+    let drive = provider(&server);
+    assert_eq!(drive.changes_since(None).await.unwrap().unwrap().cursor, "7");
+    assert!(matches!(drive.changes_since(Some("stale")).await, Err(CloudError::InvalidCursor)));
+}
+
+#[tokio::test]
+async fn test_move_updates_parents_and_name() {
+    let server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/files/file-1"))
+        .and(query_param("addParents", "folder-b"))
+        .and(query_param("removeParents", "folder-a"))
+        .and(wiremock::matchers::body_json(json!({ "name": "renamed.txt" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "file-1", "name": "renamed.txt" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let moved = provider(&server).move_file("file-1", "folder-a", "folder-b", "renamed.txt").await.unwrap().unwrap();
+    // Same file, so revisions and sharing stay with it
+    assert_eq!(moved.id, "file-1");
+    assert_eq!(moved.name, "renamed.txt");
+}
+
+#[tokio::test]
+async fn test_trash_sets_trashed_flag() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": [{ "id": "file-1" }] })))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/files/file-1"))
+        .and(wiremock::matchers::body_json(json!({ "trashed": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "file-1",
+            "name": "old.txt",
+            "mimeType": "text/plain",
+            "parents": ["folder-a"],
+            "trashedTime": "2024-05-01T00:00:00Z"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let drive = provider(&server);
+    assert!(drive.supports_trash());
+    let trashed = drive.trash_file("old.txt", "folder-a").await.unwrap().unwrap();
+    assert_eq!(trashed.id, "file-1");
+    assert_eq!(trashed.parent.as_deref(), Some("folder-a"));
+    assert_eq!(trashed.trashed_at, Some(1714521600));
+}
+
+#[tokio::test]
+async fn test_trash_listing_and_restore() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/files"))
+        .and(query_param("q", "trashed = true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "files": [
+            { "id": "folder-1", "name": "old", "mimeType": "application/vnd.google-apps.folder", "explicitlyTrashed": true },
+            // Trashed along with its folder
+            { "id": "file-2", "name": "inner.txt", "mimeType": "text/plain", "explicitlyTrashed": false }
+        ] })))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/files/folder-1"))
+        .and(wiremock::matchers::body_json(json!({ "trashed": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "folder-1",
+            "name": "old",
+            "mimeType": "application/vnd.google-apps.folder"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let drive = provider(&server);
+    let trashed = drive.list_trashed().await.unwrap();
+    assert_eq!(trashed.len(), 1);
+    assert!(trashed[0].is_dir);
+    let restored = drive.restore_file("folder-1").await.unwrap();
+    assert!(restored.is_dir);
+    assert_eq!(restored.name, "old");
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{ignore_status, setup};
+use omnisync_core::ignore_rules::IgnoreMatcher;
+use std::fs;
+
+#[tokio::test]
+async fn test_default_rules_skip_noise() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir(local.path().join(".git")).unwrap();
+    fs::write(local.path().join(".git/HEAD"), b"ref").unwrap();
+    fs::create_dir_all(local.path().join("app/node_modules/left-pad")).unwrap();
+    fs::write(local.path().join("app/node_modules/left-pad/index.js"), b"pad").unwrap();
+    fs::write(local.path().join("build.tmp"), b"tmp").unwrap();
+    fs::write(local.path().join("~$report.docx"), b"lock").unwrap();
+    fs::create_dir(local.path().join("dist")).unwrap();
+    fs::write(local.path().join("dist/app.js"), b"app").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(!remote.path().join(".git").exists());
+    assert!(!remote.path().join("app/node_modules").exists());
+    assert!(!remote.path().join("build.tmp").exists());
+    assert!(!remote.path().join("~$report.docx").exists());
+    assert_eq!(fs::read(remote.path().join("dist/app.js")).unwrap(), b"app");
+}
+
+#[tokio::test]
+async fn test_ignore_files_nest_and_override() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join(".omnisyncignore"), "*.log\nbuild/\n").unwrap();
+    fs::create_dir_all(root.path().join("keep/build")).unwrap();
+    fs::write(root.path().join("keep/.omnisyncignore"), "!important.log\n!build/\n").unwrap();
+
+    let matcher = IgnoreMatcher::new(root.path(), "*.tmp");
+    assert!(matcher.is_ignored("debug.log", false));
+    assert!(matcher.is_ignored("build/out.bin", false));
+    assert!(matcher.is_ignored("keep/other.log", false));
+    assert!(!matcher.is_ignored("keep/important.log", false));
+    assert!(!matcher.is_ignored("keep/build/out.bin", false));
+    assert!(matcher.is_ignored("keep/scratch.tmp", false));
+    // The rule file itself is synced so other devices apply the same rules
+    assert!(!matcher.is_ignored(".omnisyncignore", false));
+    // Engine bookkeeping can't be re-included
+    assert!(matcher.is_ignored(".omnisync/trash/a.txt", false));
+}
+
+#[tokio::test]
+async fn test_pair_rules_can_be_changed() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("data.csv"), b"1,2").unwrap();
+    fs::write(local.path().join("notes.txt"), b"notes").unwrap();
+    fs::write(local.path().join("scratch.tmp"), b"tmp").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    engine.set_ignore_rules(pair.id, "*.csv\n").await.unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    assert_eq!(pair.ignore_rules, "*.csv\n");
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(!remote.path().join("data.csv").exists());
+    assert!(remote.path().join("notes.txt").exists());
+    // The defaults were replaced, not extended
+    assert!(remote.path().join("scratch.tmp").exists());
+
+    assert!(engine.set_ignore_rules(pair.id + 100, "").await.is_err());
+}
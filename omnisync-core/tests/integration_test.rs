@@ -21,7 +21,7 @@ async fn test_engine_initialization() {
     
     // Run start in a background task with a timeout to verify it starts and runs
     // Since start() loops indefinitely, a timeout expiration is actually a success (it didn't crash)
-    let result = tokio::time::timeout(Duration::from_millis(500), engine.start()).await;
+    let result = tokio::time::timeout(Duration::from_millis(500), engine.start(|_| {})).await;
     
     // Check that it didn't return an error (it should have timed out)
     if let Ok(config_res) = result {
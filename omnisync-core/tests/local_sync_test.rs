@@ -0,0 +1,251 @@
+mod common;
+
+use common::{add_local_pair, memory_pool, recorder, setup};
+use omnisync_core::engine::SyncStatus;
+use omnisync_core::provider::CloudProvider;
+use omnisync_core::providers::local::{LocalConfig, LocalProvider};
+use omnisync_core::SyncEngine;
+use std::fs;
+use std::path::Path;
+
+#[tokio::test]
+async fn test_initial_sync_merges_both_sides() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("notes.txt"), b"local notes").unwrap();
+    fs::create_dir(local.path().join("docs")).unwrap();
+    fs::write(local.path().join("docs/report.md"), b"# report").unwrap();
+    fs::create_dir(remote.path().join("photos")).unwrap();
+    fs::write(remote.path().join("photos/cat.jpg"), b"meow").unwrap();
+    // Same content on both sides before the first sync
+    fs::write(local.path().join("same.txt"), b"same").unwrap();
+    fs::write(remote.path().join("same.txt"), b"same").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, events) = recorder();
+    engine.perform_initial_sync(&pair, on_status).await.unwrap();
+
+    assert_eq!(fs::read(remote.path().join("notes.txt")).unwrap(), b"local notes");
+    assert_eq!(fs::read(remote.path().join("docs/report.md")).unwrap(), b"# report");
+    assert_eq!(fs::read(local.path().join("photos/cat.jpg")).unwrap(), b"meow");
+    assert!(engine.list_conflicts(Some(pair.id)).await.unwrap().is_empty());
+
+    let mut keys: Vec<String> = engine.get_file_states(pair.id).await.unwrap().into_iter().map(|s| s.path).collect();
+    keys.sort();
+    assert_eq!(keys, ["docs", "docs/report.md", "notes.txt", "photos", "photos/cat.jpg", "same.txt"]);
+
+    let events = events.lock().unwrap();
+    assert!(events.iter().any(|e| matches!(e, SyncStatus::Uploaded { path, .. } if path.ends_with("notes.txt"))));
+    assert!(events.iter().any(|e| matches!(e, SyncStatus::Downloading { path, .. } if path.ends_with("cat.jpg"))));
+}
+
+#[tokio::test]
+async fn test_deletions_propagate_on_next_pass() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("keep.txt"), b"keep").unwrap();
+    fs::write(local.path().join("gone-locally.txt"), b"a").unwrap();
+    fs::write(remote.path().join("gone-remotely.txt"), b"b").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, _) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+    assert!(local.path().join("gone-remotely.txt").exists());
+
+    fs::remove_file(local.path().join("gone-locally.txt")).unwrap();
+    fs::remove_file(remote.path().join("gone-remotely.txt")).unwrap();
+    engine.perform_initial_sync(&pair, on_status).await.unwrap();
+
+    assert!(!remote.path().join("gone-locally.txt").exists());
+    assert!(!local.path().join("gone-remotely.txt").exists());
+    assert!(remote.path().join("keep.txt").exists());
+    let keys: Vec<String> = engine.get_file_states(pair.id).await.unwrap().into_iter().map(|s| s.path).collect();
+    assert_eq!(keys, ["keep.txt"]);
+}
+
+#[tokio::test]
+async fn test_both_changed_keeps_a_conflicted_copy() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("plan.txt"), b"v1").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, events) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+
+    fs::write(local.path().join("plan.txt"), b"local v2").unwrap();
+    fs::write(remote.path().join("plan.txt"), b"remote v2").unwrap();
+    engine.perform_initial_sync(&pair, on_status).await.unwrap();
+
+    // The remote version wins the original name; the local one is kept alongside
+    assert_eq!(fs::read(local.path().join("plan.txt")).unwrap(), b"remote v2");
+    let conflicts = engine.list_conflicts(Some(pair.id)).await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].path, "plan.txt");
+    assert_eq!(fs::read(local.path().join(&conflicts[0].conflict_path)).unwrap(), b"local v2");
+    assert_eq!(fs::read(remote.path().join(&conflicts[0].conflict_path)).unwrap(), b"local v2");
+    assert!(events.lock().unwrap().iter().any(|e| matches!(e, SyncStatus::Conflict { .. })));
+}
+
+#[tokio::test]
+async fn test_sync_file_uploads_into_nested_folder() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+
+    let nested = Path::new(&pair.local_path).join("a/b");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("c.txt"), b"deep").unwrap();
+
+    let (on_status, _) = recorder();
+    engine.sync_file(&nested.join("c.txt"), &pair, on_status, None).await.unwrap();
+
+    assert_eq!(fs::read(remote.path().join("a/b/c.txt")).unwrap(), b"deep");
+    let states = engine.get_file_states(pair.id).await.unwrap();
+    let state = states.iter().find(|s| s.path == "a/b/c.txt").unwrap();
+    assert_eq!(state.remote_id.as_deref(), Some("a/b/c.txt"));
+    // The target is compared by size and modification time, never hashed
+    assert!(state.hash.is_some());
+    assert_eq!(state.remote_hash, None);
+    assert_eq!(state.remote_modified_at, Some(state.modified_at.unwrap()));
+}
+
+#[tokio::test]
+async fn test_sync_file_uploads_same_size_edit() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(remote.path().join("todo.txt"), b"milk").unwrap();
+    let pool = memory_pool().await;
+    let engine = SyncEngine::new(pool.clone());
+    let pair = add_local_pair(&engine, local.path(), remote.path()).await;
+    let (on_status, _) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+
+    // The target reports no hash, so only the recorded state tells this edit apart.
+    // A second engine on the same database skips the debounce of the first one.
+    let path = Path::new(&pair.local_path).join("todo.txt");
+    fs::write(&path, b"eggs").unwrap();
+    let engine = SyncEngine::new(pool);
+    engine.sync_file(&path, &pair, on_status, None).await.unwrap();
+
+    assert_eq!(fs::read(remote.path().join("todo.txt")).unwrap(), b"eggs");
+    assert!(engine.list_conflicts(Some(pair.id)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_delete_remote_file_and_download() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(remote.path().join("old.txt"), b"old").unwrap();
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, events) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+    fs::write(remote.path().join("new.txt"), b"new").unwrap();
+
+    // A local removal the watcher reported
+    let old = Path::new(&pair.local_path).join("old.txt");
+    fs::remove_file(&old).unwrap();
+    engine.delete_remote_file(&old, &pair, on_status.clone(), None).await.unwrap();
+    assert!(!remote.path().join("old.txt").exists());
+    assert!(events.lock().unwrap().iter().any(|e| matches!(e, SyncStatus::Deleted { .. })));
+
+    let provider = LocalProvider::new(LocalConfig { path: remote.path().to_str().unwrap().to_string() });
+    let listed = provider.list_files("root").await.unwrap();
+    assert_eq!(listed.len(), 1);
+
+    let dest = Path::new(&pair.local_path).join("new.txt");
+    engine.sync_remote_to_local(&listed[0], &dest, &pair, on_status, None).await.unwrap();
+    assert_eq!(fs::read(&dest).unwrap(), b"new");
+    let states = engine.get_file_states(pair.id).await.unwrap();
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0].path, "new.txt");
+    // The download keeps the modification time of the source
+    assert_eq!(states[0].modified_at, listed[0].modified_at);
+    assert_eq!(states[0].remote_modified_at, listed[0].modified_at);
+}
+
+#[tokio::test]
+async fn test_delete_remote_file_keeps_unsynced_or_changed_remote() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(remote.path().join("edited.txt"), b"v1").unwrap();
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, _) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+
+    // Edited remotely after the last sync, then removed locally
+    fs::write(remote.path().join("edited.txt"), b"v2 from elsewhere").unwrap();
+    let edited = Path::new(&pair.local_path).join("edited.txt");
+    fs::remove_file(&edited).unwrap();
+    engine.delete_remote_file(&edited, &pair, on_status.clone(), None).await.unwrap();
+    assert_eq!(fs::read(remote.path().join("edited.txt")).unwrap(), b"v2 from elsewhere");
+
+    // Never synced: not ours to remove
+    fs::write(remote.path().join("unknown.txt"), b"?").unwrap();
+    let unknown = Path::new(&pair.local_path).join("unknown.txt");
+    engine.delete_remote_file(&unknown, &pair, on_status.clone(), None).await.unwrap();
+    assert!(remote.path().join("unknown.txt").exists());
+
+    // The next pass brings the edited copy back
+    engine.perform_initial_sync(&pair, on_status).await.unwrap();
+    assert_eq!(fs::read(&edited).unwrap(), b"v2 from elsewhere");
+}
+
+#[tokio::test]
+async fn test_provider_paths_stay_inside_root() {
+    let remote = tempfile::tempdir().unwrap();
+    let provider = LocalProvider::new(LocalConfig { path: remote.path().to_str().unwrap().to_string() });
+
+    assert_eq!(provider.create_folder("x", "/").await.unwrap(), "x");
+    assert_eq!(provider.create_folder("y", "x").await.unwrap(), "x/y");
+    assert!(remote.path().join("x/y").is_dir());
+    assert!(provider.list_files("../..").await.is_err());
+    // Already gone is not an error
+    provider.delete_file("missing.txt", "x").await.unwrap();
+
+    let folders: Vec<String> = provider.list_folders().await.unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(folders, ["x", "x/y"]);
+}
+
+#[tokio::test]
+async fn test_local_move_is_applied_remotely() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir_all(local.path().join("photos/2024")).unwrap();
+    fs::write(local.path().join("photos/2024/cat.jpg"), b"meow").unwrap();
+    fs::write(local.path().join("draft.txt"), b"draft").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (on_status, events) = recorder();
+    engine.perform_initial_sync(&pair, on_status.clone()).await.unwrap();
+
+    // A file renamed into another folder
+    fs::create_dir(local.path().join("docs")).unwrap();
+    fs::rename(local.path().join("draft.txt"), local.path().join("docs/final.txt")).unwrap();
+    engine
+        .move_remote_entry(&local.path().join("draft.txt"), &local.path().join("docs/final.txt"), &pair, on_status.clone(), None)
+        .await
+        .unwrap();
+    assert!(!remote.path().join("draft.txt").exists());
+    assert_eq!(fs::read(remote.path().join("docs/final.txt")).unwrap(), b"draft");
+
+    // A folder rename carries the entries below it
+    fs::rename(local.path().join("photos"), local.path().join("pictures")).unwrap();
+    engine
+        .move_remote_entry(&local.path().join("photos"), &local.path().join("pictures"), &pair, on_status.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(fs::read(remote.path().join("pictures/2024/cat.jpg")).unwrap(), b"meow");
+
+    let states = engine.get_file_states(pair.id).await.unwrap();
+    let cat = states.iter().find(|s| s.path == "pictures/2024/cat.jpg").unwrap();
+    // Path ids below the moved folder follow it
+    assert_eq!(cat.remote_id.as_deref(), Some("pictures/2024/cat.jpg"));
+    assert!(!states.iter().any(|s| s.path.starts_with("photos")));
+    assert_eq!(events.lock().unwrap().iter().filter(|e| matches!(e, SyncStatus::Moved { .. })).count(), 2);
+
+    // Nothing left to transfer
+    events.lock().unwrap().clear();
+    engine.perform_initial_sync(&pair, on_status).await.unwrap();
+    assert!(events.lock().unwrap().is_empty());
+}
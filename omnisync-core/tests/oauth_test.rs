@@ -0,0 +1,31 @@
+use omnisync_core::SyncEngine;
+
+#[test]
+fn test_parse_oauth_code_from_redirect() {
+    let code = SyncEngine::parse_oauth_code("http://127.0.0.1:4420/?state=x&code=4%2F0Ab-cd&scope=drive\n").unwrap();
+    assert_eq!(code, "4/0Ab-cd");
+
+    // A bare code is taken as is
+    assert_eq!(SyncEngine::parse_oauth_code("  abc123 ").unwrap(), "abc123");
+}
+
+#[test]
+fn test_parse_oauth_code_rejects_errors() {
+    let err = SyncEngine::parse_oauth_code("http://127.0.0.1:4420/?error=access_denied&error_description=User+declined")
+        .unwrap_err();
+    assert!(err.to_string().contains("User declined"));
+
+    assert!(SyncEngine::parse_oauth_code("http://127.0.0.1:4420/").is_err());
+    assert!(SyncEngine::parse_oauth_code("").is_err());
+    assert!(SyncEngine::parse_oauth_code("not a code").is_err());
+}
+
+#[test]
+fn test_google_authorize_url_uses_pkce() {
+    let url = url::Url::parse(&omnisync_core::providers::gdrive::authorize_url("client", "http://127.0.0.1:4420", "challenge")).unwrap();
+    let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    assert_eq!(query["redirect_uri"], "http://127.0.0.1:4420");
+    assert_eq!(query["code_challenge"], "challenge");
+    assert_eq!(query["code_challenge_method"], "S256");
+    assert_eq!(query["access_type"], "offline");
+}
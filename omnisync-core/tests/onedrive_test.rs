@@ -0,0 +1,205 @@
+use omnisync_core::hashing::{hash_file, QuickXorHash};
+use omnisync_core::provider::{CloudProvider, HashKind};
+use omnisync_core::providers::onedrive::OneDriveProvider;
+use serde_json::json;
+use wiremock::matchers::{body_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn item(id: &str, name: &str, is_dir: bool) -> serde_json::Value {
+    if is_dir {
+        json!({ "id": id, "name": name, "folder": { "childCount": 0 } })
+    } else {
+        json!({
+            "id": id,
+            "name": name,
+            "size": 5,
+            "lastModifiedDateTime": "2024-01-01T00:00:00Z",
+            "file": { "hashes": { "quickXorHash": "hash-of-" } }
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_list_files_follows_next_link() {
+    let server = MockServer::start().await;
+    let next = format!("{}/page2", server.uri());
+
+    Mock::given(method("GET"))
+        .and(path("/me/drive/items/folder1/children"))
+        .and(header("authorization", "Bearer token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "value": [item("a", "a.txt", false)],
+            "@odata.nextLink": next,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/page2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "value": [item("b", "docs", true)],
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = OneDriveProvider::with_base_url("token".to_string(), &server.uri());
+    let files = provider.list_files("folder1").await.unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].name, "a.txt");
+    assert_eq!(files[0].hash.as_deref(), Some("hash-of-"));
+    assert_eq!(files[0].modified_at, Some(1704067200));
+    assert!(!files[0].is_dir);
+    assert!(files[1].is_dir);
+}
+
+#[tokio::test]
+async fn test_small_upload_uses_single_put() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/me/drive/items/parent:/a.txt:/content"))
+        .and(header("authorization", "Bearer token"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(item("new-id", "a.txt", false)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, b"hello").unwrap();
+
+    let provider = OneDriveProvider::with_base_url("token".to_string(), &server.uri());
+    let remote = provider.upload_file(&file, "parent").await.unwrap();
+    assert_eq!(remote.id, "new-id");
+}
+
+#[tokio::test]
+async fn test_large_upload_uses_upload_session() {
+    let server = MockServer::start().await;
+    let upload_url = format!("{}/upload/session-1", server.uri());
+
+    Mock::given(method("POST"))
+        .and(path("/me/drive/root:/big.bin:/createUploadSession"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "uploadUrl": upload_url })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let size = 4 * 1024 * 1024 + 10;
+    Mock::given(method("PUT"))
+        .and(path("/upload/session-1"))
+        .and(header("content-range", format!("bytes 0-{}/{}", size - 1, size).as_str()))
+        .respond_with(ResponseTemplate::new(201).set_body_json(item("big-id", "big.bin", false)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("big.bin");
+    std::fs::write(&file, vec![7u8; size]).unwrap();
+
+    let provider = OneDriveProvider::with_base_url("token".to_string(), &server.uri());
+    let remote = provider.upload_file(&file, "root").await.unwrap();
+    assert_eq!(remote.id, "big-id");
+
+    // The session URL is pre-authenticated and must not carry the bearer token
+    let requests = server.received_requests().await.unwrap();
+    let fragment = requests.iter().find(|r| r.url.path() == "/upload/session-1").unwrap();
+    assert!(!fragment.headers.contains_key("authorization"));
+}
+
+#[tokio::test]
+async fn test_download_and_delete() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/me/drive/items/file-1/content"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"contents".to_vec()))
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/me/drive/items/parent:/gone.txt"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/me/drive/items/parent:/a.txt"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("out.txt");
+    let provider = OneDriveProvider::with_base_url("token".to_string(), &server.uri());
+
+    provider.download_file("file-1", &dest).await.unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), b"contents");
+
+    provider.delete_file("a.txt", "parent").await.unwrap();
+    // Already deleted remotely is not an error
+    provider.delete_file("gone.txt", "parent").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_create_folder_returns_existing_on_conflict() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/me/drive/root/children"))
+        .and(body_json(json!({
+            "name": "Photos",
+            "folder": {},
+            "@microsoft.graph.conflictBehavior": "fail"
+        })))
+        .respond_with(ResponseTemplate::new(409))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/me/drive/root:/Photos"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(item("existing-id", "Photos", true)))
+        .mount(&server)
+        .await;
+
+    let provider = OneDriveProvider::with_base_url("token".to_string(), &server.uri());
+    assert_eq!(provider.create_folder("Photos", "root").await.unwrap(), "existing-id");
+}
+
+#[tokio::test]
+async fn test_unauthorized_maps_to_unauthenticated() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let provider = OneDriveProvider::with_base_url("expired".to_string(), &server.uri());
+    let err = provider.list_files("root").await.unwrap_err();
+    assert!(matches!(err, omnisync_core::provider::CloudError::Unauthenticated));
+}
+
+#[tokio::test]
+async fn test_quick_xor_hash() {
+    assert_eq!(QuickXorHash::new().finalize(), "AAAAAAAAAAAAAAAAAAAAAAAAAAA=");
+
+    // Known answers: the value OneDrive reports for "hello world", and one longer
+    // than 160 bytes so the shift wraps around the whole width
+    let mut hello = QuickXorHash::new();
+    hello.update(b"hello world");
+    assert_eq!(hello.finalize(), "aCgDG9jwBhDc4Q1yawMZAAAAAAA=");
+    let mut wrapped = QuickXorHash::new();
+    wrapped.update(&(0..=255u8).cycle().take(512).collect::<Vec<_>>());
+    assert_eq!(wrapped.finalize(), "edJlP68QDhntUYpkxf/vpP5uDuY=");
+
+    // Feeding the data in uneven chunks must give the same digest as one call
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let mut whole = QuickXorHash::new();
+    whole.update(&data);
+    let mut chunked = QuickXorHash::new();
+    for chunk in data.chunks(997) {
+        chunked.update(chunk);
+    }
+    assert_eq!(whole.finalize(), chunked.finalize());
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.bin");
+    std::fs::write(&file, &data).unwrap();
+    assert_eq!(hash_file(&file, HashKind::QuickXor).await.unwrap(), whole.finalize());
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{ignore_status, setup};
+use omnisync_core::reconcile::SyncAction;
+use std::fs;
+use std::path::Path;
+
+fn listing(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = walkdir(dir);
+    names.sort();
+    names
+}
+
+fn walkdir(dir: &Path) -> Vec<String> {
+    let mut out = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        out.push(path.to_string_lossy().to_string());
+        if path.is_dir() {
+            out.extend(walkdir(&path));
+        }
+    }
+    out
+}
+
+#[tokio::test]
+async fn test_plan_lists_actions_without_touching_anything() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("notes.txt"), b"notes").unwrap();
+    fs::create_dir(local.path().join("docs")).unwrap();
+    fs::write(local.path().join("docs/report.md"), b"# report").unwrap();
+    fs::create_dir(remote.path().join("photos")).unwrap();
+    fs::write(remote.path().join("photos/cat.jpg"), b"meow").unwrap();
+    fs::write(local.path().join("both.txt"), b"mine").unwrap();
+    fs::write(remote.path().join("both.txt"), b"theirs").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    let (local_before, remote_before) = (listing(local.path()), listing(remote.path()));
+
+    let plan = engine.plan_sync(pair.id).await.unwrap();
+    assert_eq!(plan.pair_id, pair.id);
+    assert_eq!(plan.actions, [
+        SyncAction::Conflict { path: "both.txt".to_string() },
+        SyncAction::MkdirRemote { path: "docs".to_string() },
+        SyncAction::Upload { path: "docs/report.md".to_string(), size: Some(8) },
+        SyncAction::Upload { path: "notes.txt".to_string(), size: Some(5) },
+        SyncAction::MkdirLocal { path: "photos".to_string() },
+        SyncAction::Download { path: "photos/cat.jpg".to_string(), size: Some(4) },
+    ]);
+
+    assert_eq!(listing(local.path()), local_before);
+    assert_eq!(listing(remote.path()), remote_before);
+    assert!(engine.get_file_states(pair.id).await.unwrap().is_empty());
+    assert!(engine.list_conflicts(Some(pair.id)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_plan_after_sync_shows_deletions() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::write(local.path().join("a.txt"), b"a").unwrap();
+    fs::create_dir(local.path().join("old")).unwrap();
+    fs::write(local.path().join("old/b.txt"), b"b").unwrap();
+
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(engine.plan_sync(pair.id).await.unwrap().actions.is_empty());
+
+    fs::remove_file(remote.path().join("a.txt")).unwrap();
+    fs::remove_dir_all(local.path().join("old")).unwrap();
+    let plan = engine.plan_sync(pair.id).await.unwrap();
+    assert_eq!(plan.actions, [
+        SyncAction::DeleteLocal { path: "a.txt".to_string(), is_dir: false },
+        SyncAction::DeleteRemote { path: "old".to_string(), is_dir: true },
+    ]);
+    assert_eq!(plan.actions[1].to_string(), "delete remote old/");
+    assert!(local.path().join("a.txt").exists());
+    assert!(remote.path().join("old/b.txt").exists());
+}
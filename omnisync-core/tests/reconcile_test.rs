@@ -0,0 +1,147 @@
+use omnisync_core::models::{FileState, SyncMode};
+use omnisync_core::provider::RemoteFile;
+use omnisync_core::reconcile::{apply_mode, conflicted_copy_name, decide_dir, decide_file, exceeds_delete_limit, Decision, LocalState};
+
+fn local(hash: &str, modified_at: i64) -> LocalState {
+    LocalState { size: 10, modified_at, hash: hash.to_string() }
+}
+
+fn remote(hash: &str, modified_at: i64) -> RemoteFile {
+    RemoteFile {
+        id: "remote-1".to_string(),
+        name: "a.txt".to_string(),
+        is_dir: false,
+        size: Some(10),
+        modified_at: Some(modified_at),
+        hash: Some(hash.to_string()),
+        revision: None,
+    }
+}
+
+fn base(hash: &str) -> FileState {
+    FileState {
+        pair_id: 1,
+        path: "a.txt".to_string(),
+        is_dir: false,
+        hash: Some(hash.to_string()),
+        size: Some(10),
+        modified_at: Some(100),
+        remote_id: Some("remote-1".to_string()),
+        remote_hash: Some(hash.to_string()),
+        remote_revision: None,
+        remote_modified_at: Some(100),
+        status: "synced".to_string(),
+        synced_at: 100,
+    }
+}
+
+#[test]
+fn test_new_local_file_with_old_mtime_is_uploaded() {
+    // e.g. extracted from a tarball: mtime predates any previous sync
+    let l = local("aaa", 1);
+    assert_eq!(decide_file(Some(&l), None, None), Decision::Upload);
+}
+
+#[test]
+fn test_deletions_only_propagate_for_previously_synced_files() {
+    let b = base("aaa");
+    assert_eq!(decide_file(Some(&local("aaa", 100)), None, Some(&b)), Decision::DeleteLocal);
+    assert_eq!(decide_file(None, Some(&remote("aaa", 100)), Some(&b)), Decision::DeleteRemote);
+    assert_eq!(decide_file(None, Some(&remote("aaa", 100)), None), Decision::Download);
+    assert_eq!(decide_file(None, None, Some(&b)), Decision::Forget);
+}
+
+#[test]
+fn test_edit_wins_over_delete() {
+    let b = base("aaa");
+    assert_eq!(decide_file(Some(&local("bbb", 200)), None, Some(&b)), Decision::Upload);
+    assert_eq!(decide_file(None, Some(&remote("bbb", 200)), Some(&b)), Decision::Download);
+}
+
+#[test]
+fn test_changes_are_detected_against_base() {
+    let b = base("aaa");
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&remote("aaa", 100)), Some(&b)), Decision::InSync);
+    assert_eq!(decide_file(Some(&local("bbb", 200)), Some(&remote("aaa", 100)), Some(&b)), Decision::Upload);
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&remote("ccc", 200)), Some(&b)), Decision::Download);
+    assert_eq!(decide_file(Some(&local("bbb", 200)), Some(&remote("ccc", 200)), Some(&b)), Decision::BothChanged);
+}
+
+#[test]
+fn test_revision_detects_changes_without_hashes() {
+    // A WebDAV file without a checksum, overwritten within the same second at the same size
+    let mut b = base("aaa");
+    b.remote_hash = None;
+    b.remote_revision = Some("e1".to_string());
+    let mut r = remote("aaa", 100);
+    r.hash = None;
+    r.revision = Some("e1".to_string());
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&r), Some(&b)), Decision::InSync);
+    r.revision = Some("e2".to_string());
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&r), Some(&b)), Decision::Download);
+}
+
+#[test]
+fn test_directories() {
+    let mut b = base("aaa");
+    b.is_dir = true;
+    assert_eq!(decide_dir(true, false, None), Decision::Upload);
+    assert_eq!(decide_dir(true, false, Some(&b)), Decision::DeleteLocal);
+    assert_eq!(decide_dir(false, true, None), Decision::Download);
+    assert_eq!(decide_dir(false, true, Some(&b)), Decision::DeleteRemote);
+    assert_eq!(decide_dir(true, true, Some(&b)), Decision::InSync);
+}
+
+#[test]
+fn test_both_sides_changed_without_base_is_a_conflict() {
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&remote("bbb", 100)), None), Decision::BothChanged);
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&remote("aaa", 300)), None), Decision::InSync);
+
+    // Same size but no remote hash: the contents may still differ
+    let unhashed = RemoteFile { hash: None, ..remote("aaa", 100) };
+    assert_eq!(decide_file(Some(&local("aaa", 100)), Some(&unhashed), None), Decision::BothChanged);
+}
+
+#[test]
+fn test_modes_narrow_decisions() {
+    use Decision::*;
+    for d in [InSync, Upload, Download, DeleteLocal, DeleteRemote, BothChanged, Forget] {
+        assert_eq!(apply_mode(d, SyncMode::TwoWay, true), d);
+    }
+
+    assert_eq!(apply_mode(Download, SyncMode::UploadOnly, false), Skip);
+    assert_eq!(apply_mode(DeleteLocal, SyncMode::UploadOnly, true), Upload);
+    assert_eq!(apply_mode(DeleteRemote, SyncMode::UploadOnly, false), Forget);
+    assert_eq!(apply_mode(BothChanged, SyncMode::UploadOnly, true), Upload);
+
+    assert_eq!(apply_mode(Upload, SyncMode::DownloadOnly, true), Skip);
+    assert_eq!(apply_mode(DeleteRemote, SyncMode::DownloadOnly, false), Download);
+    assert_eq!(apply_mode(DeleteLocal, SyncMode::DownloadOnly, true), DeleteLocal);
+    assert_eq!(apply_mode(BothChanged, SyncMode::DownloadOnly, true), Download);
+
+    assert_eq!(apply_mode(Download, SyncMode::Mirror, true), Upload);
+    assert_eq!(apply_mode(Download, SyncMode::Mirror, false), DeleteRemote);
+    assert_eq!(apply_mode(DeleteLocal, SyncMode::Mirror, true), Upload);
+    assert_eq!(apply_mode(DeleteRemote, SyncMode::Mirror, false), DeleteRemote);
+    assert_eq!(apply_mode(BothChanged, SyncMode::Mirror, true), Upload);
+}
+
+#[test]
+fn test_delete_limits() {
+    assert!(!exceeds_delete_limit(50, 10_000, 50, 20));
+    assert!(exceeds_delete_limit(51, 10_000, 50, 20));
+    // 20% of a small tree, but too few files to ask about
+    assert!(!exceeds_delete_limit(3, 5, 50, 20));
+    assert!(exceeds_delete_limit(10, 40, 50, 20));
+    assert!(!exceeds_delete_limit(10, 50, 50, 20));
+    // Limits turned off
+    assert!(!exceeds_delete_limit(1000, 1000, 0, 0));
+}
+
+#[test]
+fn test_conflicted_copy_name() {
+    assert_eq!(conflicted_copy_name("report.pdf", "laptop", "2026-10-18", 1), "report (conflicted copy, laptop, 2026-10-18).pdf");
+    assert_eq!(conflicted_copy_name("archive.tar.gz", "laptop", "2026-10-18", 2), "archive.tar (conflicted copy 2, laptop, 2026-10-18).gz");
+    assert_eq!(conflicted_copy_name("Makefile", "laptop", "2026-10-18", 1), "Makefile (conflicted copy, laptop, 2026-10-18)");
+    assert_eq!(conflicted_copy_name(".env", "laptop", "2026-10-18", 1), ".env (conflicted copy, laptop, 2026-10-18)");
+}
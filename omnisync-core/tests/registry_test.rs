@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use omnisync_core::models::Credentials;
+use omnisync_core::provider::{CloudProvider, CloudResult, FileMetadata, RemoteFile, RemoteFolder};
+use omnisync_core::registry::{ConnectedAccount, ConnectionForm, ProviderRegistry};
+use omnisync_core::SyncEngine;
+use sqlx::sqlite::SqlitePoolOptions;
+use std::path::Path;
+
+/// A backend that only knows the folders it was created with
+struct FixedFolders {
+    folders: Vec<String>,
+}
+
+#[async_trait]
+impl CloudProvider for FixedFolders {
+    fn id(&self) -> &str {
+        "fixed"
+    }
+
+    async fn upload_file(&self, _local_path: &Path, _cloud_path: &str) -> CloudResult<RemoteFile> {
+        unimplemented!()
+    }
+
+    async fn download_file(&self, _file_id: &str, _local_path: &Path) -> CloudResult<()> {
+        unimplemented!()
+    }
+
+    async fn delete_file(&self, _filename: &str, _cloud_parent: &str) -> CloudResult<()> {
+        unimplemented!()
+    }
+
+    async fn get_metadata(&self, _cloud_path: &str) -> CloudResult<FileMetadata> {
+        unimplemented!()
+    }
+
+    async fn list_files(&self, _folder_id: &str) -> CloudResult<Vec<RemoteFile>> {
+        Ok(Vec::new())
+    }
+
+    async fn list_folders(&self) -> CloudResult<Vec<RemoteFolder>> {
+        Ok(self.folders.iter().map(|f| RemoteFolder { id: f.clone(), name: f.clone() }).collect())
+    }
+
+    async fn create_folder(&self, name: &str, _parent_id: &str) -> CloudResult<String> {
+        Ok(name.to_string())
+    }
+}
+
+async fn engine() -> SyncEngine {
+    let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    SyncEngine::new(pool)
+}
+
+fn credentials(account_id: &str, token: &str) -> Credentials {
+    Credentials {
+        account_id: account_id.to_string(),
+        provider_id: account_id.split(':').next().unwrap().to_string(),
+        access_token: token.to_string(),
+        refresh_token: None,
+        expires_at: None,
+        user_name: None,
+        user_email: None,
+        user_avatar: None,
+        config: None,
+    }
+}
+
+#[test]
+fn test_default_registry_has_builtin_providers() {
+    let registry = ProviderRegistry::default();
+    assert_eq!(
+        registry.provider_types(),
+        ["dropbox", "gdrive", "local", "onedrive", "s3", "sftp", "webdav"]
+    );
+
+    let provider = registry.build(&credentials("dropbox:a@example.com", "token")).unwrap();
+    assert_eq!(provider.id(), "dropbox");
+    // Connection-form providers need their settings
+    assert!(registry.build(&credentials("s3:bucket@host", "secret")).is_err());
+    assert!(registry.build(&credentials("carrier-pigeon:coop", "")).is_err());
+}
+
+#[tokio::test]
+async fn test_engine_uses_registered_factory() {
+    let engine = engine().await;
+    engine.register_provider("fixed", |creds: &Credentials| {
+        // The factory sees the stored credentials
+        Ok(Box::new(FixedFolders { folders: vec![creds.access_token.clone()] }) as Box<dyn CloudProvider>)
+    });
+    engine
+        .set_credentials("fixed:test", "fixed", "Projects", None, None, None, None, None)
+        .await
+        .unwrap();
+
+    let folders = engine.get_remote_folders("fixed:test").await.unwrap();
+    assert_eq!(folders.len(), 1);
+    assert_eq!(folders[0].id, "Projects");
+}
+
+#[tokio::test]
+async fn test_registering_replaces_builtin() {
+    let engine = engine().await;
+    engine.register_provider("gdrive", |_: &Credentials| {
+        Ok(Box::new(FixedFolders { folders: vec!["stub".to_string()] }) as Box<dyn CloudProvider>)
+    });
+    engine
+        .set_credentials("gdrive:a@example.com", "gdrive", "token", None, None, None, None, None)
+        .await
+        .unwrap();
+
+    let folders = engine.get_remote_folders("gdrive:a@example.com").await.unwrap();
+    assert_eq!(folders[0].name, "stub");
+}
+
+#[tokio::test]
+async fn test_unregistered_provider_is_an_error() {
+    let engine = engine().await;
+    engine
+        .set_credentials("mock_provider:test", "mock_provider", "token", None, None, None, None, None)
+        .await
+        .unwrap();
+
+    let err = engine.get_remote_folders("mock_provider:test").await.unwrap_err();
+    assert!(err.to_string().contains("No provider registered for mock_provider"));
+}
+
+/// Accounts named after the "project" setting
+struct ProjectForm;
+
+#[async_trait]
+impl ConnectionForm for ProjectForm {
+    async fn connect(&self, config: serde_json::Value) -> anyhow::Result<ConnectedAccount> {
+        let project = config["project"].as_str().ok_or_else(|| anyhow::anyhow!("No project"))?.to_string();
+        Ok(ConnectedAccount { account_id: format!("fixed:{}", project), user_name: project.clone(), user_email: String::new(), config })
+    }
+}
+
+#[tokio::test]
+async fn test_connect_account_uses_registered_form() {
+    let engine = engine().await;
+    engine.register_provider("fixed", |_: &Credentials| {
+        Ok(Box::new(FixedFolders { folders: Vec::new() }) as Box<dyn CloudProvider>)
+    });
+    let err = engine.connect_account("fixed", serde_json::json!({ "project": "apollo" }), "").await.unwrap_err();
+    assert!(err.to_string().contains("does not use a connection form"));
+
+    engine.register_form("fixed", ProjectForm);
+    assert!(engine.connect_account("fixed", serde_json::json!({}), "").await.is_err());
+    let account_id = engine.connect_account("fixed", serde_json::json!({ "project": "apollo" }), "").await.unwrap();
+    assert_eq!(account_id, "fixed:apollo");
+    let creds = engine.get_credentials(&account_id).await.unwrap().unwrap();
+    assert_eq!(creds.user_name.as_deref(), Some("apollo"));
+    assert_eq!(creds.config.as_deref(), Some(r#"{"project":"apollo"}"#));
+}
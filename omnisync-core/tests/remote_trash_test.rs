@@ -0,0 +1,103 @@
+mod common;
+
+use common::{ignore_status, setup};
+use omnisync_core::provider::CloudProvider;
+use omnisync_core::providers::local::{LocalConfig, LocalProvider};
+use omnisync_core::{SyncEngine, SyncPair};
+use std::fs;
+use std::path::Path;
+
+/// A pair that has already been synced once, with "shared.txt" and "docs/a.txt" on both sides
+async fn synced_pair(local: &Path, remote: &Path) -> (SyncEngine, SyncPair) {
+    let (engine, pair) = setup(local, remote).await;
+
+    // Present on both sides already, so the first pass transfers nothing
+    for root in [local, remote] {
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/a.txt"), b"a").unwrap();
+        fs::write(root.join("shared.txt"), b"shared").unwrap();
+    }
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    (engine, pair)
+}
+
+#[tokio::test]
+async fn test_local_deletions_go_to_remote_trash() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+    assert!(!pair.permanent_delete);
+
+    fs::remove_file(local.path().join("shared.txt")).unwrap();
+    fs::remove_dir_all(local.path().join("docs")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(!remote.path().join("shared.txt").exists());
+    assert!(!remote.path().join("docs").exists());
+
+    let items = engine.list_remote_trash(pair.id).await.unwrap();
+    let mut paths: Vec<(&str, bool)> = items.iter().map(|i| (i.path.as_str(), i.is_dir)).collect();
+    paths.sort();
+    assert_eq!(paths, [("docs", true), ("shared.txt", false)]);
+
+    // Restored on the cloud, then brought back down by the next pass
+    let docs = items.iter().find(|i| i.path == "docs").unwrap();
+    engine.restore_remote_trash_item(docs.id).await.unwrap();
+    assert_eq!(fs::read(remote.path().join("docs/a.txt")).unwrap(), b"a");
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert_eq!(fs::read(local.path().join("docs/a.txt")).unwrap(), b"a");
+    assert_eq!(engine.list_remote_trash(pair.id).await.unwrap().len(), 1);
+    assert!(engine.restore_remote_trash_item(docs.id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_trash_emptied_elsewhere_is_forgotten() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+
+    fs::remove_file(local.path().join("shared.txt")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert_eq!(engine.list_remote_trash(pair.id).await.unwrap().len(), 1);
+
+    fs::remove_dir_all(remote.path().join(".omnisync/trash")).unwrap();
+    assert!(engine.list_remote_trash(pair.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_permanent_delete_is_opt_in() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path()).await;
+    engine.set_permanent_delete(pair.id, true).await.unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+
+    fs::remove_file(local.path().join("shared.txt")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(!remote.path().join("shared.txt").exists());
+    assert!(engine.list_remote_trash(pair.id).await.unwrap().is_empty());
+    assert!(!remote.path().join(".omnisync").exists());
+
+    assert!(engine.set_permanent_delete(pair.id + 100, true).await.is_err());
+}
+
+#[tokio::test]
+async fn test_local_provider_trash_round_trip() {
+    let target = tempfile::tempdir().unwrap();
+    fs::create_dir(target.path().join("docs")).unwrap();
+    fs::write(target.path().join("docs/a.txt"), b"a").unwrap();
+    let provider = LocalProvider::new(LocalConfig { path: target.path().to_str().unwrap().to_string() });
+
+    let trashed = provider.trash_file("a.txt", "docs").await.unwrap().unwrap();
+    assert_eq!(trashed.name, "a.txt");
+    assert_eq!(trashed.parent.as_deref(), Some("docs"));
+    assert!(provider.trash_file("a.txt", "docs").await.unwrap().is_none());
+    // The trash is not part of the listed tree
+    assert!(provider.list_files("root").await.unwrap().iter().all(|f| f.name == "docs"));
+    assert!(provider.list_folders().await.unwrap().iter().all(|f| f.id == "docs"));
+
+    assert_eq!(provider.list_trashed().await.unwrap().len(), 1);
+    let restored = provider.restore_file(&trashed.id).await.unwrap();
+    assert_eq!(restored.id, "docs/a.txt");
+    assert_eq!(fs::read(target.path().join("docs/a.txt")).unwrap(), b"a");
+    assert!(provider.list_trashed().await.unwrap().is_empty());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::{ignore_status, setup};
+use omnisync_core::models::FolderCheckState;
+use std::fs;
+use std::path::Path;
+
+/// A remote tree with a small folder and a large one with a subfolder
+fn remote_tree(remote: &Path) {
+    fs::create_dir(remote.join("docs")).unwrap();
+    fs::write(remote.join("docs/a.txt"), b"a").unwrap();
+    fs::create_dir_all(remote.join("media/raw")).unwrap();
+    fs::write(remote.join("media/clip.mp4"), b"clip").unwrap();
+    fs::write(remote.join("media/raw/take1.mov"), b"take1").unwrap();
+}
+
+#[tokio::test]
+async fn test_excluded_folder_is_not_downloaded() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    remote_tree(remote.path());
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+
+    engine.set_folder_excluded(pair.id, "media/raw", true).await.unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(local.path().join("docs/a.txt").exists());
+    assert!(local.path().join("media/clip.mp4").exists());
+    assert!(!local.path().join("media/raw").exists());
+    // Missing locally, but not deleted remotely
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(remote.path().join("media/raw/take1.mov").exists());
+}
+
+#[tokio::test]
+async fn test_excluding_a_synced_folder_keeps_remote_copy() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    remote_tree(remote.path());
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(local.path().join("media/raw/take1.mov").exists());
+
+    engine.set_folder_excluded(pair.id, "media", true).await.unwrap();
+    // Freeing up space locally must not reach the cloud
+    fs::remove_dir_all(local.path().join("media")).unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(remote.path().join("media/raw/take1.mov").exists());
+    assert!(!local.path().join("media").exists());
+
+    // Included again: downloaded, not treated as deleted locally
+    engine.set_folder_excluded(pair.id, "media", false).await.unwrap();
+    let pair = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert_eq!(fs::read(local.path().join("media/raw/take1.mov")).unwrap(), b"take1");
+    assert!(remote.path().join("media/raw/take1.mov").exists());
+}
+
+#[tokio::test]
+async fn test_folder_tree_check_states() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    remote_tree(remote.path());
+    let (engine, pair) = setup(local.path(), remote.path()).await;
+    engine.set_folder_excluded(pair.id, "media/raw", true).await.unwrap();
+
+    let root = engine.list_folder_tree(pair.id, "", None).await.unwrap();
+    let states: Vec<(&str, FolderCheckState)> = root.iter().map(|f| (f.path.as_str(), f.state)).collect();
+    assert_eq!(states, [("docs", FolderCheckState::Included), ("media", FolderCheckState::Partial)]);
+
+    let media = root.iter().find(|f| f.name == "media").unwrap();
+    let children = engine.list_folder_tree(pair.id, &media.path, Some(&media.id)).await.unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].path, "media/raw");
+    assert_eq!(children[0].state, FolderCheckState::Excluded);
+
+    // Excluding the parent covers the child; the child can't be included on its own
+    engine.set_folder_excluded(pair.id, "media", true).await.unwrap();
+    let pair_row = engine.get_sync_pair(pair.id).await.unwrap().unwrap();
+    assert_eq!(pair_row.excluded_keys().collect::<Vec<_>>(), ["media"]);
+    assert!(engine.set_folder_excluded(pair.id, "media/raw", false).await.is_err());
+    assert!(engine.set_folder_excluded(pair.id, "", true).await.is_err());
+}
@@ -0,0 +1,282 @@
+//! A small in-process SSH server for the SFTP tests. The SFTP subsystem serves the local
+//! filesystem as-is (point the account at a temporary directory) and exec requests run
+//! through `sh -c`, which is enough for the provider's `sha256sum` calls.
+
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{HashAlg, PrivateKey, PublicKey};
+use russh::server::{Auth, ChannelOpenHandle, Msg, Session};
+use russh::{Channel, ChannelId};
+use russh_sftp::protocol::{Attrs, Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode};
+use std::collections::HashMap;
+use std::os::unix::fs::{FileExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::net::TcpListener;
+
+pub struct TestServer {
+    pub port: u16,
+    /// Host key fingerprint, as `SftpProvider::fetch_host_key` reports it
+    pub host_key: String,
+    /// Private key the server accepts, in OpenSSH format
+    pub key_path: PathBuf,
+    _keys: tempfile::TempDir,
+}
+
+/// Listen on a free local port until the test ends
+pub async fn start() -> TestServer {
+    let host_key = key(7);
+    let keys = tempfile::tempdir().unwrap();
+    let key_path = keys.path().join("id_ed25519");
+    let client_key = write_key(&key_path, 42);
+
+    let config = Arc::new(russh::server::Config {
+        keys: vec![host_key.clone()],
+        auth_rejection_time: Duration::ZERO,
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let authorized = client_key.public_key().clone();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = SshSession { authorized: authorized.clone(), channels: HashMap::new() };
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Ok(session) = russh::server::run_stream(config, stream, handler).await {
+                    let _ = session.await;
+                }
+            });
+        }
+    });
+
+    TestServer {
+        port,
+        host_key: host_key.public_key().fingerprint(HashAlg::Sha256).to_string(),
+        key_path,
+        _keys: keys,
+    }
+}
+
+/// Ed25519 key derived from `seed`, so every run uses the same keys
+fn key(seed: u8) -> PrivateKey {
+    PrivateKey::from(Ed25519Keypair::from_seed(&[seed; 32]))
+}
+
+/// Write the private key for `seed` to `path` the way ssh-keygen would
+pub fn write_key(path: &Path, seed: u8) -> PrivateKey {
+    let key = key(seed);
+    std::fs::write(path, key.to_openssh(LineEnding::LF).unwrap().as_bytes()).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    key
+}
+
+struct SshSession {
+    authorized: PublicKey,
+    /// Session channels waiting for their exec or subsystem request
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl russh::server::Handler for SshSession {
+    type Error = anyhow::Error;
+
+    async fn auth_publickey(&mut self, _user: &str, public_key: &PublicKey) -> Result<Auth, Self::Error> {
+        Ok(if public_key.key_data() == self.authorized.key_data() { Auth::Accept } else { Auth::reject() })
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        reply: ChannelOpenHandle,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        reply.accept().await;
+        Ok(())
+    }
+
+    async fn exec_request(&mut self, channel: ChannelId, data: &[u8], session: &mut Session) -> Result<(), Self::Error> {
+        self.channels.remove(&channel);
+        let command = String::from_utf8_lossy(data).to_string();
+        let output = tokio::process::Command::new("sh").arg("-c").arg(&command).output().await?;
+
+        session.channel_success(channel)?;
+        session.data(channel, output.stdout)?;
+        session.exit_status_request(channel, output.status.code().unwrap_or(255) as u32)?;
+        session.eof(channel)?;
+        session.close(channel)?;
+        Ok(())
+    }
+
+    async fn channel_eof(&mut self, channel: ChannelId, session: &mut Session) -> Result<(), Self::Error> {
+        // The client is done with the channel; libssh2 waits for it to be closed
+        session.close(channel)?;
+        Ok(())
+    }
+
+    async fn subsystem_request(&mut self, channel: ChannelId, name: &str, session: &mut Session) -> Result<(), Self::Error> {
+        match self.channels.remove(&channel) {
+            Some(stream) if name == "sftp" => {
+                session.channel_success(channel)?;
+                russh_sftp::server::run(stream.into_stream(), SftpSession::default()).await;
+            }
+            _ => session.channel_failure(channel)?,
+        }
+        Ok(())
+    }
+}
+
+enum OpenHandle {
+    File(std::fs::File),
+    /// Directory entries not handed out yet
+    Dir(Vec<File>),
+}
+
+#[derive(Default)]
+struct SftpSession {
+    handles: HashMap<String, OpenHandle>,
+    next_handle: u64,
+}
+
+impl SftpSession {
+    fn add(&mut self, id: u32, handle: OpenHandle) -> Handle {
+        self.next_handle += 1;
+        let name = self.next_handle.to_string();
+        self.handles.insert(name.clone(), handle);
+        Handle { id, handle: name }
+    }
+
+    fn file(&self, handle: &str) -> Result<&std::fs::File, StatusCode> {
+        match self.handles.get(handle) {
+            Some(OpenHandle::File(file)) => Ok(file),
+            _ => Err(StatusCode::Failure),
+        }
+    }
+}
+
+fn ok(id: u32) -> Status {
+    Status { id, status_code: StatusCode::Ok, error_message: "Ok".to_string(), language_tag: "en-US".to_string() }
+}
+
+fn status(e: std::io::Error) -> StatusCode {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NoSuchFile,
+        std::io::ErrorKind::PermissionDenied => StatusCode::PermissionDenied,
+        _ => StatusCode::Failure,
+    }
+}
+
+fn attrs(id: u32, meta: std::io::Result<std::fs::Metadata>) -> Result<Attrs, StatusCode> {
+    Ok(Attrs { id, attrs: FileAttributes::from(&meta.map_err(status)?) })
+}
+
+impl russh_sftp::server::Handler for SftpSession {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn open(&mut self, id: u32, filename: String, pflags: OpenFlags, attrs: FileAttributes) -> Result<Handle, Self::Error> {
+        let file = std::fs::OpenOptions::from(pflags).open(&filename).map_err(status)?;
+        if let (true, Some(mode)) = (pflags.contains(OpenFlags::CREATE), attrs.permissions) {
+            file.set_permissions(std::fs::Permissions::from_mode(mode & 0o7777)).map_err(status)?;
+        }
+        Ok(self.add(id, OpenHandle::File(file)))
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        self.handles.remove(&handle).ok_or(StatusCode::Failure)?;
+        Ok(ok(id))
+    }
+
+    async fn read(&mut self, id: u32, handle: String, offset: u64, len: u32) -> Result<Data, Self::Error> {
+        let mut data = vec![0; len as usize];
+        let n = self.file(&handle)?.read_at(&mut data, offset).map_err(status)?;
+        if n == 0 {
+            return Err(StatusCode::Eof);
+        }
+        data.truncate(n);
+        Ok(Data { id, data })
+    }
+
+    async fn write(&mut self, id: u32, handle: String, offset: u64, data: Vec<u8>) -> Result<Status, Self::Error> {
+        self.file(&handle)?.write_all_at(&data, offset).map_err(status)?;
+        Ok(ok(id))
+    }
+
+    async fn lstat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        attrs(id, std::fs::symlink_metadata(path))
+    }
+
+    async fn fstat(&mut self, id: u32, handle: String) -> Result<Attrs, Self::Error> {
+        attrs(id, self.file(&handle)?.metadata())
+    }
+
+    async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        attrs(id, std::fs::metadata(path))
+    }
+
+    async fn setstat(&mut self, id: u32, path: String, attrs: FileAttributes) -> Result<Status, Self::Error> {
+        if let Some(mode) = attrs.permissions {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o7777)).map_err(status)?;
+        }
+        if let Some(mtime) = attrs.mtime {
+            let mtime = UNIX_EPOCH + Duration::from_secs(mtime as u64);
+            let atime = attrs.atime.map_or(mtime, |t| UNIX_EPOCH + Duration::from_secs(t as u64));
+            let file = std::fs::File::options().write(true).open(&path).map_err(status)?;
+            file.set_times(std::fs::FileTimes::new().set_accessed(atime).set_modified(mtime)).map_err(status)?;
+        }
+        Ok(ok(id))
+    }
+
+    async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&path).map_err(status)? {
+            let entry = entry.map_err(status)?;
+            let meta = entry.metadata().map_err(status)?;
+            entries.push(File::new(entry.file_name().to_string_lossy(), FileAttributes::from(&meta)));
+        }
+        Ok(self.add(id, OpenHandle::Dir(entries)))
+    }
+
+    async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+        match self.handles.get_mut(&handle) {
+            Some(OpenHandle::Dir(entries)) if !entries.is_empty() => Ok(Name { id, files: std::mem::take(entries) }),
+            Some(OpenHandle::Dir(_)) => Err(StatusCode::Eof),
+            _ => Err(StatusCode::Failure),
+        }
+    }
+
+    async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+        std::fs::remove_file(filename).map_err(status)?;
+        Ok(ok(id))
+    }
+
+    async fn mkdir(&mut self, id: u32, path: String, _attrs: FileAttributes) -> Result<Status, Self::Error> {
+        std::fs::create_dir(path).map_err(status)?;
+        Ok(ok(id))
+    }
+
+    async fn rmdir(&mut self, id: u32, path: String) -> Result<Status, Self::Error> {
+        std::fs::remove_dir(path).map_err(status)?;
+        Ok(ok(id))
+    }
+
+    async fn realpath(&mut self, id: u32, path: String) -> Result<Name, Self::Error> {
+        let path = std::fs::canonicalize(path).map_err(status)?;
+        Ok(Name { id, files: vec![File::dummy(path.to_string_lossy())] })
+    }
+
+    async fn rename(&mut self, id: u32, oldpath: String, newpath: String) -> Result<Status, Self::Error> {
+        // Like OpenSSH's SFTPv3 rename, never replace an existing file
+        if Path::new(&newpath).exists() {
+            return Err(StatusCode::Failure);
+        }
+        std::fs::rename(oldpath, newpath).map_err(status)?;
+        Ok(ok(id))
+    }
+}
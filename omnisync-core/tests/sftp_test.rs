@@ -0,0 +1,150 @@
+#[cfg(unix)]
+mod sftp_server;
+
+use omnisync_core::hashing::hash_file;
+use omnisync_core::provider::{CloudError, CloudProvider, HashKind};
+use omnisync_core::providers::sftp::{parse_sha256sum, SftpConfig, SftpProvider};
+
+#[test]
+fn test_parse_target() {
+    let config = SftpConfig::parse_target("alice@build01:/srv/sync").unwrap();
+    assert_eq!(config.username, "alice");
+    assert_eq!(config.host, "build01");
+    assert_eq!(config.path, "/srv/sync");
+    assert_eq!(config.port, 22);
+    assert_eq!(config.account_id(), "sftp:alice@build01");
+
+    // No path: the login directory
+    assert_eq!(SftpConfig::parse_target("bob@nas").unwrap().path, "");
+    assert!(SftpConfig::parse_target("build01:/srv").is_none());
+    assert!(SftpConfig::parse_target("@build01:/srv").is_none());
+}
+
+#[test]
+fn test_config_defaults() {
+    let config: SftpConfig = serde_json::from_value(serde_json::json!({
+        "host": "build01",
+        "port": 2222,
+        "username": "alice"
+    }))
+    .unwrap();
+    assert_eq!(config.account_id(), "sftp:alice@build01:2222");
+    assert_eq!(config.key_path, None);
+    assert!(!config.remote_hash);
+    assert_eq!(config.host_key, None);
+}
+
+#[test]
+fn test_parse_sha256sum() {
+    let output = "\
+2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  hello.txt
+e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 *empty file.bin
+\\9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  back\\\\slash
+sha256sum: missing.txt: No such file or directory
+";
+    let hashes = parse_sha256sum(output);
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(hashes["hello.txt"], "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+    assert_eq!(hashes["empty file.bin"], "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+}
+
+#[tokio::test]
+async fn test_sha256_matches_sha256sum() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("hello.txt");
+    std::fs::write(&file, b"hello").unwrap();
+    assert_eq!(
+        hash_file(&file, HashKind::Sha256).await.unwrap(),
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+}
+
+/// Upload, list with server-side hashes, download and delete against `config`
+async fn round_trip(mut config: SftpConfig) {
+    config.remote_hash = true;
+    config.host_key = Some(SftpProvider::fetch_host_key(&config).await.unwrap());
+    let provider = SftpProvider::new(config, String::new());
+
+    let folder = provider.create_folder("omnisync-test", "root").await.unwrap();
+    // Creating it again reuses the existing directory
+    assert_eq!(provider.create_folder("omnisync-test", "root").await.unwrap(), folder);
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("it's here.txt");
+    std::fs::write(&file, b"hello").unwrap();
+
+    let uploaded = provider.upload_file(&file, &folder).await.unwrap();
+    assert_eq!(uploaded.id, "omnisync-test/it's here.txt");
+    assert_eq!(uploaded.size, Some(5));
+    // Uploading again replaces the file, also on servers that refuse to rename over it
+    std::fs::write(&file, b"hello").unwrap();
+    provider.upload_file(&file, &folder).await.unwrap();
+
+    let listed = provider.list_files(&folder).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    // The server-side sha256sum agrees with the local hash
+    assert_eq!(listed[0].hash, Some(hash_file(&file, HashKind::Sha256).await.unwrap()));
+    // The local modification time was kept
+    let mtime = std::fs::metadata(&file).unwrap().modified().unwrap();
+    assert_eq!(listed[0].modified_at, Some(mtime.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64));
+
+    let copy = dir.path().join("copy.txt");
+    provider.download_remote(&listed[0], &copy).await.unwrap();
+    assert_eq!(std::fs::read(&copy).unwrap(), b"hello");
+
+    provider.delete_file("omnisync-test", "root").await.unwrap();
+    // Already gone is not an error
+    provider.delete_file("omnisync-test", "root").await.unwrap();
+}
+
+// Dropping the provider closes the libssh2 session with blocking calls, which the
+// server can only answer from another worker thread
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_round_trip_against_in_process_server() {
+    let server = sftp_server::start().await;
+    let root = tempfile::tempdir().unwrap();
+    let mut config = SftpConfig::parse_target(&format!("alice@127.0.0.1:{}", root.path().display())).unwrap();
+    config.port = server.port;
+    config.key_path = Some(server.key_path.to_str().unwrap().to_string());
+
+    assert_eq!(SftpProvider::fetch_host_key(&config).await.unwrap(), server.host_key);
+    round_trip(config).await;
+    assert!(!root.path().join("omnisync-test").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wrong_host_or_client_key_is_refused() {
+    let server = sftp_server::start().await;
+    let mut config = SftpConfig::parse_target("alice@127.0.0.1:/").unwrap();
+    config.port = server.port;
+    config.key_path = Some(server.key_path.to_str().unwrap().to_string());
+
+    // A host key other than the pinned one is refused before authenticating
+    config.host_key = Some("SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string());
+    let provider = SftpProvider::new(config.clone(), String::new());
+    assert!(matches!(provider.list_files("root").await, Err(CloudError::ApiError(e)) if e.starts_with("Unknown host key")));
+
+    // The server only accepts its own client key
+    let keys = tempfile::tempdir().unwrap();
+    let other = keys.path().join("id_other");
+    sftp_server::write_key(&other, 1);
+    config.host_key = Some(server.host_key.clone());
+    config.key_path = Some(other.to_str().unwrap().to_string());
+    let provider = SftpProvider::new(config, String::new());
+    assert!(matches!(provider.list_files("root").await, Err(CloudError::Unauthenticated)));
+}
+
+/// Round trip against a real server, e.g. an OpenSSH container:
+/// OMNISYNC_SFTP_TEST=user@localhost:/tmp/omnisync with the key loaded in ssh-agent.
+#[tokio::test]
+#[ignore = "needs an SSH server; set OMNISYNC_SFTP_TEST=user@host:/path"]
+async fn test_round_trip_against_server() {
+    let target = std::env::var("OMNISYNC_SFTP_TEST").expect("OMNISYNC_SFTP_TEST=user@host:/path");
+    let mut config = SftpConfig::parse_target(&target).expect("user@host:/path");
+    if let Ok(port) = std::env::var("OMNISYNC_SFTP_TEST_PORT") {
+        config.port = port.parse().unwrap();
+    }
+    round_trip(config).await;
+}
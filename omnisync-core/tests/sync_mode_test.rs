@@ -0,0 +1,79 @@
+mod common;
+
+use common::{ignore_status, setup};
+use omnisync_core::models::SyncMode;
+use omnisync_core::{SyncEngine, SyncPair};
+use std::fs;
+use std::path::Path;
+
+/// A pair in `mode` that has already been synced once, with "shared.txt" on both sides
+async fn synced_pair(local: &Path, remote: &Path, mode: SyncMode) -> (SyncEngine, SyncPair) {
+    let (engine, pair) = setup(local, remote).await;
+    let id = pair.id;
+
+    // Present on both sides already, so the first pass transfers nothing
+    fs::write(local.join("shared.txt"), b"shared").unwrap();
+    fs::write(remote.join("shared.txt"), b"shared").unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    engine.set_sync_mode(id, mode).await.unwrap();
+    let pair = engine.get_sync_pair(id).await.unwrap().unwrap();
+    assert_eq!(pair.sync_mode(), mode);
+    (engine, pair)
+}
+
+#[tokio::test]
+async fn test_upload_only_never_deletes_or_downloads() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path(), SyncMode::UploadOnly).await;
+
+    fs::remove_file(local.path().join("shared.txt")).unwrap();
+    fs::write(local.path().join("new.txt"), b"new").unwrap();
+    fs::write(remote.path().join("theirs.txt"), b"theirs").unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(remote.path().join("shared.txt").exists());
+    assert_eq!(fs::read(remote.path().join("new.txt")).unwrap(), b"new");
+    assert!(!local.path().join("theirs.txt").exists());
+    assert!(!local.path().join("shared.txt").exists());
+}
+
+#[tokio::test]
+async fn test_download_only_keeps_local_changes_local() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path(), SyncMode::DownloadOnly).await;
+
+    fs::write(local.path().join("mine.txt"), b"mine").unwrap();
+    fs::write(remote.path().join("theirs.txt"), b"theirs").unwrap();
+    fs::remove_file(local.path().join("shared.txt")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(!remote.path().join("mine.txt").exists());
+    assert!(local.path().join("mine.txt").exists());
+    assert_eq!(fs::read(local.path().join("theirs.txt")).unwrap(), b"theirs");
+    // Removed locally: restored rather than deleted remotely
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"shared");
+    assert!(remote.path().join("shared.txt").exists());
+}
+
+#[tokio::test]
+async fn test_mirror_makes_remote_identical() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair) = synced_pair(local.path(), remote.path(), SyncMode::Mirror).await;
+
+    fs::create_dir(remote.path().join("extra")).unwrap();
+    fs::write(remote.path().join("extra/stray.txt"), b"stray").unwrap();
+    fs::write(remote.path().join("shared.txt"), b"edited remotely").unwrap();
+    fs::write(local.path().join("mine.txt"), b"mine").unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+
+    assert!(!remote.path().join("extra").exists());
+    assert!(!local.path().join("extra").exists());
+    assert_eq!(fs::read(remote.path().join("shared.txt")).unwrap(), b"shared");
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"shared");
+    assert_eq!(fs::read(remote.path().join("mine.txt")).unwrap(), b"mine");
+    assert!(engine.list_conflicts(Some(pair.id)).await.unwrap().is_empty());
+}
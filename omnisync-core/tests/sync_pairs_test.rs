@@ -27,7 +27,7 @@ async fn test_sync_pairs_management() {
 
     // Test adding a sync pair
     let id = engine
-        .add_sync_pair(abs_path_str, "/remote/path", "mock_provider")
+        .add_sync_pair(abs_path_str, "/remote/path", "Remote", "mock_provider", "mock_provider:test")
         .await
         .expect("Failed to add sync pair");
 
@@ -39,6 +39,13 @@ async fn test_sync_pairs_management() {
     assert_eq!(pairs[0].local_path, abs_path_str);
     assert_eq!(pairs[0].status, "active");
 
+    // Test pausing and resuming
+    engine.set_pair_active(id, false).await.unwrap();
+    assert_eq!(engine.get_sync_pair(id).await.unwrap().unwrap().status, "paused");
+    engine.set_pair_active(id, true).await.unwrap();
+    assert_eq!(engine.get_sync_pair(id).await.unwrap().unwrap().status, "active");
+    assert!(engine.set_pair_active(id + 1, true).await.is_err());
+
     // Clean up
     fs::remove_dir(test_dir).unwrap();
 }
@@ -0,0 +1,132 @@
+mod common;
+
+use common::{add_local_pair, ignore_status, memory_pool};
+use omnisync_core::provider::RemoteFile;
+use omnisync_core::{SyncEngine, SyncPair};
+use sqlx::SqlitePool;
+use std::fs;
+use std::path::Path;
+
+/// A pair that has already been synced once, with "shared.txt" on both sides
+async fn synced_pair(local: &Path, remote: &Path) -> (SyncEngine, SyncPair, SqlitePool) {
+    let pool = memory_pool().await;
+    let engine = SyncEngine::new(pool.clone());
+    let pair = add_local_pair(&engine, local, remote).await;
+
+    // Present on both sides already, so the first pass transfers nothing
+    fs::write(local.join("shared.txt"), b"shared").unwrap();
+    fs::write(remote.join("shared.txt"), b"shared").unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    (engine, pair, pool)
+}
+
+#[tokio::test]
+async fn test_deleted_on_cloud_goes_to_trash_and_restores() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    fs::create_dir(remote.path().join("docs")).unwrap();
+    fs::write(remote.path().join("docs/a.txt"), b"a").unwrap();
+    let (engine, pair, _) = synced_pair(local.path(), remote.path()).await;
+    assert!(local.path().join("docs/a.txt").exists());
+
+    fs::remove_file(remote.path().join("shared.txt")).unwrap();
+    fs::remove_dir_all(remote.path().join("docs")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert!(!local.path().join("shared.txt").exists());
+    assert!(!local.path().join("docs").exists());
+
+    let items = engine.list_trash(Some(pair.id)).await.unwrap();
+    let mut paths: Vec<(&str, bool, &str)> = items.iter().map(|i| (i.path.as_str(), i.is_dir, i.reason.as_str())).collect();
+    paths.sort();
+    assert_eq!(paths, [("docs", true, "deleted"), ("shared.txt", false, "deleted")]);
+
+    let shared = items.iter().find(|i| i.path == "shared.txt").unwrap();
+    assert_eq!(shared.size, Some(6));
+    engine.restore_trash_item(shared.id).await.unwrap();
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"shared");
+    assert_eq!(engine.list_trash(Some(pair.id)).await.unwrap().len(), 1);
+    assert!(engine.restore_trash_item(shared.id).await.is_err());
+
+    // A restored file is new locally, so it goes back to the cloud
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert_eq!(fs::read(remote.path().join("shared.txt")).unwrap(), b"shared");
+    // The trash itself is never synced
+    assert!(!remote.path().join(".omnisync").exists());
+}
+
+#[tokio::test]
+async fn test_overwritten_version_is_kept() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair, _) = synced_pair(local.path(), remote.path()).await;
+
+    fs::write(remote.path().join("shared.txt"), b"edited in the cloud").unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"edited in the cloud");
+
+    let items = engine.list_trash(Some(pair.id)).await.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].reason, "overwritten");
+
+    // Restoring swaps the versions, so it can be undone
+    engine.restore_trash_item(items[0].id).await.unwrap();
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"shared");
+    let items = engine.list_trash(Some(pair.id)).await.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].reason, "replaced");
+    engine.restore_trash_item(items[0].id).await.unwrap();
+    assert_eq!(fs::read(local.path().join("shared.txt")).unwrap(), b"edited in the cloud");
+}
+
+#[tokio::test]
+async fn test_failed_download_leaves_local_file_and_trash_alone() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair, _) = synced_pair(local.path(), remote.path()).await;
+
+    // Listed larger than it is, so the download fails its size check
+    fs::write(remote.path().join("shared.txt"), b"edited in the cloud").unwrap();
+    let listed = RemoteFile {
+        id: "shared.txt".to_string(),
+        name: "shared.txt".to_string(),
+        is_dir: false,
+        size: Some(100),
+        modified_at: None,
+        hash: None,
+        revision: None,
+    };
+    let dest = local.path().join("shared.txt");
+    let err = engine.sync_remote_to_local(&listed, &dest, &pair, ignore_status(), None).await.unwrap_err();
+    assert!(err.to_string().contains("Size mismatch"));
+
+    assert_eq!(fs::read(&dest).unwrap(), b"shared");
+    assert!(engine.list_trash(Some(pair.id)).await.unwrap().is_empty());
+    assert_eq!(fs::read_dir(local.path().join(".omnisync/incoming")).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_retention_purges_old_items() {
+    let local = tempfile::tempdir().unwrap();
+    let remote = tempfile::tempdir().unwrap();
+    let (engine, pair, pool) = synced_pair(local.path(), remote.path()).await;
+    assert_eq!(pair.trash_retention_days, 30);
+
+    fs::remove_file(remote.path().join("shared.txt")).unwrap();
+    engine.perform_initial_sync(&pair, ignore_status()).await.unwrap();
+    let item = engine.list_trash(Some(pair.id)).await.unwrap().remove(0);
+    let stored = local.path().join(".omnisync/trash").join(item.id.to_string()).join("shared.txt");
+    assert!(stored.exists());
+
+    // Ten days old: kept for 30 days, and forever with retention off
+    sqlx::query("UPDATE trash_items SET trashed_at = trashed_at - 10 * 86400").execute(&pool).await.unwrap();
+    assert_eq!(engine.purge_trash().await.unwrap(), 0);
+    engine.set_trash_retention(pair.id, 0).await.unwrap();
+    assert_eq!(engine.purge_trash().await.unwrap(), 0);
+
+    engine.set_trash_retention(pair.id, 7).await.unwrap();
+    assert_eq!(engine.purge_trash().await.unwrap(), 1);
+    assert!(engine.list_trash(None).await.unwrap().is_empty());
+    assert!(!stored.exists());
+
+    assert!(engine.set_trash_retention(pair.id + 100, 7).await.is_err());
+}
@@ -14,22 +14,32 @@ let currentPair = null;
 let currentViewPath = null;
 let pairSyncStatuses = {}; // { pair_id: { type, path, message } }
 let connectedAccounts = []; // [{ account_id, provider_id, name, email, avatar }]
+let pairConflicts = []; // unresolved conflicts of the open pair
 
 const mainContent = document.getElementById('main-content');
 const detailView = document.getElementById('detail-view');
 const fileListBody = document.getElementById('file-list-body');
 const btnBack = document.getElementById('btn-back');
 const btnAddFile = document.getElementById('btn-add-file');
+const btnIgnoreRules = document.getElementById('btn-ignore-rules');
+const btnSelectiveSync = document.getElementById('btn-selective-sync');
+const detailSyncMode = document.getElementById('detail-sync-mode');
+const selectiveModalOverlay = document.getElementById('selective-modal-overlay');
+const folderTreeRoot = document.getElementById('folder-tree-root');
+const ignoreModalOverlay = document.getElementById('ignore-modal-overlay');
+const ignoreForm = document.getElementById('ignore-form');
+const inputIgnoreRules = document.getElementById('input-ignore-rules');
 
 // ---- Listen for Sync Status ----
 listen('sync-status', (event) => {
     const status = event.payload;
     const type = status.type;
-    const { pair_id, path, message, account_id } = status.data || {};
+    const { pair_id, path, message, account_id, bytes, total } = status.data || {};
+    const percent = type === 'Progress' && total ? Math.floor(bytes * 100 / total) : null;
 
     // Store status for specific pair
     if (pair_id) {
-        pairSyncStatuses[pair_id] = { type, path, message };
+        pairSyncStatuses[pair_id] = { type, path, message, percent };
         updateCardStatus(pair_id);
     }
 
@@ -52,11 +62,21 @@ listen('sync-status', (event) => {
         indicator.style.display = 'flex';
         if (type === 'Syncing' || type === 'Downloading') {
             statusText.textContent = `${type === 'Syncing' ? 'Syncing' : 'Downloading'} ${path ? path.split(/[\\/]/).pop() : ''}...`;
+        } else if (type === 'Progress') {
+            statusText.textContent = `Uploading ${path ? path.split(/[\\/]/).pop() : ''} ${percent}%`;
         } else if (type === 'Uploaded') {
             statusText.textContent = `File Synced!`;
+        } else if (type === 'Moved') {
+            statusText.textContent = `Moved ${path ? path.split(/[\\/]/).pop() : ''}`;
         } else if (type === 'Error') {
             statusText.textContent = `Sync Error`;
             showToast(`Sync Failed: ${message}`, 'error');
+        } else if (type === 'DeletionsPending') {
+            statusText.textContent = window.t('deletions_pending');
+            showDeletionReview(pair_id, status.data.count);
+        } else if (type === 'Conflict') {
+            statusText.textContent = window.t('conflict');
+            showToast(`${window.t('conflict_detected')} ${path ? path.split(/[\\/]/).pop() : ''}`, 'error');
         }
     }
 
@@ -68,7 +88,7 @@ listen('sync-status', (event) => {
             if (rowPath && path && (path === rowPath || path.startsWith(rowPath + '/') || path.startsWith(rowPath + '\\'))) {
                 const statusCell = row.querySelector('.file-status-cell');
                 if (statusCell) {
-                    statusCell.innerHTML = renderFileStatus(type, message);
+                    statusCell.innerHTML = renderFileStatus(type, message, percent);
                 }
             }
         });
@@ -86,7 +106,12 @@ function updateCardStatus(pairId) {
         statusEl.className = 'folder-status active';
         statusEl.innerHTML = `<span class="status-dot"></span>${window.t('active')}`;
     } else {
-        statusEl.className = `folder-status ${statusObj.type.toLowerCase()}`;
+        // Upload progress looks like any other transfer in flight, a move like a finished sync
+        const cssType = statusObj.type === 'Progress' ? 'syncing'
+            : statusObj.type === 'Moved' ? 'uploaded'
+            : statusObj.type === 'DeletionsPending' ? 'error'
+            : statusObj.type.toLowerCase();
+        statusEl.className = `folder-status ${cssType}`;
         let label = statusObj.type;
         let icon = '<span class="status-dot"></span>';
 
@@ -96,9 +121,14 @@ function updateCardStatus(pairId) {
         } else if (statusObj.type === 'Downloading') {
             label = 'Downloading...';
             icon = '<div class="sync-spinner-mini"></div>';
-        } else if (statusObj.type === 'Uploaded') {
+        } else if (statusObj.type === 'Progress') {
+            label = `Uploading ${statusObj.percent}%`;
+            icon = '<div class="sync-spinner-mini"></div>';
+        } else if (statusObj.type === 'Uploaded' || statusObj.type === 'Moved') {
             label = 'Synced';
             icon = '<svg width="10" height="10" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="3" stroke-linecap="round" stroke-linejoin="round" style="margin-right:4px;"><polyline points="20 6 9 17 4 12"></polyline></svg>';
+        } else if (statusObj.type === 'DeletionsPending') {
+            label = window.t('deletions_pending');
         } else if (statusObj.type === 'Error') {
             label = 'Error';
             icon = '<svg width="10" height="10" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="3" stroke-linecap="round" stroke-linejoin="round" style="margin-right:4px;"><circle cx="12" cy="12" r="10"></circle><line x1="12" y1="8" x2="12" y2="12"></line><line x1="12" y1="16" x2="12.01" y2="16"></line></svg>';
@@ -123,8 +153,12 @@ const subtitle = document.getElementById('subtitle');
 const authSection = document.getElementById('auth-section');
 const accountsContainer = document.getElementById('accounts-container');
 const btnAddAccount = document.getElementById('btn-add-account');
+const connectionForm = document.getElementById('connection-form');
+const connectionFieldsEl = document.getElementById('connection-fields');
+const btnConnect = document.getElementById('btn-connect');
 const accountSelectorGroup = document.getElementById('account-selector-group');
 const selectAccount = document.getElementById('select-account');
+const selectMode = document.getElementById('select-mode');
 const syncFields = document.getElementById('sync-fields');
 const btnAddSubmit = document.getElementById('btn-add-submit');
 
@@ -177,6 +211,42 @@ const providerLabels = {
     gdrive: 'Google Drive',
     icloud: 'iCloud',
     onedrive: 'OneDrive',
+    dropbox: 'Dropbox',
+    s3: 'S3',
+    webdav: 'WebDAV',
+    sftp: 'SFTP',
+    local: 'Local Folder',
+};
+
+// Providers connected with a settings form instead of OAuth.
+// Fields marked `secret` are sent separately from the stored connection config;
+// `optional` fields are left out of it when empty.
+const connectionFields = {
+    s3: [
+        { key: 'endpoint', label: 'endpoint', placeholder: 'https://s3.us-east-1.amazonaws.com' },
+        { key: 'region', label: 'region', placeholder: 'us-east-1' },
+        { key: 'bucket', label: 'bucket', placeholder: 'my-bucket' },
+        { key: 'access_key_id', label: 'access_key_id' },
+        { key: 'secret_access_key', label: 'secret_access_key', type: 'password', secret: true },
+        { key: 'path_style', label: 'path_style', type: 'checkbox', checked: true },
+    ],
+    webdav: [
+        { key: 'url', label: 'webdav_url', placeholder: 'https://cloud.example.com/remote.php/dav/files/alice/' },
+        { key: 'username', label: 'username' },
+        { key: 'password', label: 'app_password', type: 'password', secret: true },
+    ],
+    sftp: [
+        { key: 'host', label: 'host', placeholder: 'build01.example.com' },
+        { key: 'port', label: 'port', type: 'number', placeholder: '22', optional: true },
+        { key: 'username', label: 'username' },
+        { key: 'path', label: 'remote_path', placeholder: '/srv/sync', optional: true },
+        { key: 'key_path', label: 'key_path', placeholder: '~/.ssh/id_ed25519', optional: true },
+        { key: 'passphrase', label: 'key_passphrase', type: 'password', secret: true, optional: true },
+        { key: 'remote_hash', label: 'remote_hash', type: 'checkbox', checked: false },
+    ],
+    local: [
+        { key: 'path', label: 'target_folder', placeholder: '/mnt/nas/backup' },
+    ],
 };
 
 function providerIcon(id) {
@@ -187,6 +257,16 @@ function providerIcon(id) {
             return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M17.5 19c-3.6 0-6.5-2.9-6.5-6.5s2.9-6.5,6.5-6.5c0.3 0 0.7 0 1 0.1C17.7 3.6 15.1 2 12 2C7.6 2 4 5.6 4 10c0 4.4 3.6 8 8 8 1.9 0 3.7-0.7 5.1-1.8 0.1 0.6 0.3 1.1 0.6 1.6C16.8 18.8 15.2 19 17.5 19z" fill="#5AC8FA"/></svg>`;
         case 'onedrive':
             return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M22 6.5C22 4.01 19.99 2 17.5 2C16.14 2 14.93 2.6 14.11 3.55C13.4 3.2 12.6 3 11.75 3C9.4 3 7.5 4.9 7.5 7.25C7.5 7.42 7.51 7.58 7.53 7.74C5.55 8.1 4 9.8 4 11.9C4 14.2 5.8 16 8 16H18C20.2 16 22 14.2 22 11.9C22 10.95 21.68 10.08 21.14 9.38C21.68 8.58 22 7.58 22 6.5Z" fill="#0078D4"/></svg>`;
+        case 's3':
+            return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M12 2C7 2 3 3.3 3 5v14c0 1.7 4 3 9 3s9-1.3 9-3V5c0-1.7-4-3-9-3zm0 2c4.4 0 7 1.1 7 1s-2.6 1-7 1-7-.9-7-1 2.6-1 7-1zM5 7.4C6.7 8.1 9.2 8.5 12 8.5s5.3-.4 7-1.1V12c0 .4-2.6 1.5-7 1.5S5 12.4 5 12V7.4zm0 7c1.7.7 4.2 1.1 7 1.1s5.3-.4 7-1.1V19c0 .4-2.6 1.5-7 1.5S5 19.4 5 19v-4.6z" fill="#E25444"/></svg>`;
+        case 'webdav':
+            return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M3 12a9 9 0 1 0 18 0 9 9 0 0 0-18 0zm9-7c.9 0 2.2 1.8 2.7 5H9.3c.5-3.2 1.8-5 2.7-5zm-2.9.5C8.4 7 8 8.6 7.8 10H5.2a7 7 0 0 1 3.9-4.5zm5.8 0A7 7 0 0 1 18.8 10h-2.6c-.2-1.4-.6-3-1.3-4.5zM5.2 14h2.6c.2 1.4.6 3 1.3 4.5A7 7 0 0 1 5.2 14zm4.1 0h5.4c-.5 3.2-1.8 5-2.7 5s-2.2-1.8-2.7-5zm6.9 0h2.6a7 7 0 0 1-3.9 4.5c.7-1.5 1.1-3.1 1.3-4.5z" fill="#0082C9"/></svg>`;
+        case 'sftp':
+            return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M3 4h18a1 1 0 0 1 1 1v14a1 1 0 0 1-1 1H3a1 1 0 0 1-1-1V5a1 1 0 0 1 1-1zm1 2v12h16V6H4zm2.3 2.3 3.7 3.7-3.7 3.7-1.4-1.4L7.2 12 4.9 9.7l1.4-1.4zM11 15h6v2h-6v-2z" fill="#4D4D4D"/></svg>`;
+        case 'local':
+            return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M4 3h16a2 2 0 0 1 2 2v10a2 2 0 0 1-2 2H4a2 2 0 0 1-2-2V5a2 2 0 0 1 2-2zm0 2v10h16V5H4zm13 7a1.5 1.5 0 1 1 0-3 1.5 1.5 0 0 1 0 3zM6 19h12v2H6v-2z" fill="#6B7280"/></svg>`;
+        case 'dropbox':
+            return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none"><path d="M6 1.5L0 5.3l4.2 3.4L10.2 5 6 1.5zM0 12.1l6 3.9 4.2-3.5-6-3.8L0 12.1zm10.2.4l4.2 3.5 6-3.9-4.2-3.4-6 3.8zM24 5.3L18 1.5 13.8 5l6 3.7L24 5.3zM10.2 13.3L6 16.8l-1.8-1.2v1.3l6 3.6 6-3.6v-1.3L18 16.8l-4.2-3.5h-3.6z" fill="#0061FF"/></svg>`;
         default:
             return `<svg width="22" height="22" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><circle cx="12" cy="12" r="10"/><path d="M12 8v4l3 3"/></svg>`;
     }
@@ -203,6 +283,11 @@ function render() {
     document.getElementById('badge-gdrive').textContent = syncPairs.filter(p => p.provider_id === 'gdrive').length;
     document.getElementById('badge-icloud').textContent = syncPairs.filter(p => p.provider_id === 'icloud').length;
     document.getElementById('badge-onedrive').textContent = syncPairs.filter(p => p.provider_id === 'onedrive').length;
+    document.getElementById('badge-dropbox').textContent = syncPairs.filter(p => p.provider_id === 'dropbox').length;
+    document.getElementById('badge-s3').textContent = syncPairs.filter(p => p.provider_id === 's3').length;
+    document.getElementById('badge-webdav').textContent = syncPairs.filter(p => p.provider_id === 'webdav').length;
+    document.getElementById('badge-sftp').textContent = syncPairs.filter(p => p.provider_id === 'sftp').length;
+    document.getElementById('badge-local').textContent = syncPairs.filter(p => p.provider_id === 'local').length;
 
     // Update subtitle
     subtitle.textContent = syncPairs.length === 0
@@ -396,8 +481,71 @@ async function disconnectAccount(accountId) {
 }
 window.disconnectAccount = disconnectAccount;
 
+// ---- Add Account (connection form) ----
+function renderConnectionForm() {
+    const fields = connectionFields[currentProvider] || [];
+    connectionFieldsEl.innerHTML = fields.map(f => f.type === 'checkbox' ? `
+        <label style="display: flex; align-items: center; gap: 8px; font-size: 12px;">
+            <input type="checkbox" data-key="${f.key}" ${f.checked ? 'checked' : ''} />
+            ${window.t(f.label) || f.label}
+        </label>
+    ` : `
+        <input type="${f.type || 'text'}" class="form-input" data-key="${f.key}"
+            placeholder="${f.placeholder || window.t(f.label) || f.label}" aria-label="${window.t(f.label) || f.label}" autocomplete="off" />
+    `).join('');
+}
+
+function hideConnectionForm() {
+    connectionForm.style.display = 'none';
+    connectionFieldsEl.innerHTML = '';
+}
+
+btnConnect.addEventListener('click', async () => {
+    const fields = connectionFields[currentProvider] || [];
+    const config = {};
+    let secret = '';
+    for (const f of fields) {
+        const input = connectionFieldsEl.querySelector(`[data-key="${f.key}"]`);
+        if (f.type === 'checkbox') {
+            config[f.key] = input.checked;
+            continue;
+        }
+        const value = input.value.trim();
+        if (!value && f.optional) continue;
+        if (!value) {
+            showToast(`${window.t(f.label) || f.label}: ${window.t('field_required')}`, 'error');
+            input.focus();
+            return;
+        }
+        if (f.secret) secret = value;
+        else config[f.key] = f.type === 'number' ? Number(value) : value;
+    }
+
+    btnConnect.disabled = true;
+    try {
+        await invoke('connect_account', { providerId: currentProvider, config, secret });
+        showToast(window.t('account_connected_success'), 'success');
+        hideConnectionForm();
+        await loadAccounts();
+    } catch (err) {
+        showToast(err, 'error');
+    } finally {
+        btnConnect.disabled = false;
+    }
+});
+
 // ---- Add Account (OAuth) ----
 btnAddAccount.addEventListener('click', async () => {
+    if (connectionFields[currentProvider]) {
+        if (connectionForm.style.display === 'none') {
+            renderConnectionForm();
+            connectionForm.style.display = 'flex';
+        } else {
+            hideConnectionForm();
+        }
+        return;
+    }
+
     btnAddAccount.disabled = true;
     const originalContent = btnAddAccount.innerHTML;
     const svg = btnAddAccount.querySelector('svg');
@@ -506,18 +654,21 @@ async function loadPairs() {
     }
 }
 
-async function addPair(local, remote, remoteName, provider, accountId) {
+async function addPair(local, remote, remoteName, provider, accountId, mode) {
     try {
-        await invoke('add_sync_pair', {
+        // Added paused, so nothing moves until the preview is confirmed
+        const id = await invoke('add_sync_pair', {
             localPath: local,
             remotePath: remote,
             remoteName: remoteName,
             providerId: provider,
             accountId: accountId,
+            mode: mode,
+            paused: true,
         });
-        showToast(window.t('folder_synced'), 'success');
         await loadPairs();
         closeModal();
+        await showPlanPreview(id);
     } catch (err) {
         showToast(window.t('failed_connect') + ' ' + err, 'error');
     }
@@ -546,6 +697,116 @@ async function removePair(e, id) {
 }
 window.removePair = removePair;
 
+// ---- Mass-deletion safeguard ----
+const deletionsModalOverlay = document.getElementById('deletions-modal-overlay');
+let deletionsPairId = null;
+
+async function showDeletionReview(pairId, count) {
+    deletionsPairId = pairId;
+    const pair = syncPairs.find(p => p.id === pairId);
+    document.getElementById('deletions-summary').textContent =
+        `${count} ${window.t('deletions_pending_hint')} ${pair ? pair.local_path : ''}`;
+    const list = document.getElementById('deletions-list');
+    list.innerHTML = '';
+    deletionsModalOverlay.classList.add('open');
+
+    try {
+        const paths = await invoke('pending_deletions', { pairId });
+        paths.slice(0, PLAN_PREVIEW_LIMIT).forEach(path => {
+            const row = document.createElement('div');
+            row.className = 'folder-tree-row';
+            row.textContent = path;
+            list.appendChild(row);
+        });
+        if (paths.length > PLAN_PREVIEW_LIMIT) {
+            list.insertAdjacentHTML('beforeend', `<div class="folder-tree-empty">+${paths.length - PLAN_PREVIEW_LIMIT}</div>`);
+        }
+    } catch (err) {
+        showToast(window.t('failed_load_deletions') + ' ' + err, 'error');
+    }
+}
+
+async function settleDeletions(command) {
+    deletionsModalOverlay.classList.remove('open');
+    try {
+        await invoke(command, { pairId: deletionsPairId });
+    } catch (err) {
+        showToast(window.t('failed_sync') + ' ' + err, 'error');
+    }
+}
+
+document.getElementById('btn-deletions-later').addEventListener('click', () => deletionsModalOverlay.classList.remove('open'));
+document.getElementById('btn-deletions-restore').addEventListener('click', () => settleDeletions('restore_deletions'));
+document.getElementById('btn-deletions-confirm').addEventListener('click', () => settleDeletions('confirm_deletions'));
+
+// ---- Sync plan preview ----
+const planModalOverlay = document.getElementById('plan-modal-overlay');
+const PLAN_PREVIEW_LIMIT = 500;
+let planPairId = null;
+
+const planLabels = {
+    upload: 'plan_upload',
+    download: 'plan_download',
+    delete_local: 'plan_delete_local',
+    delete_remote: 'plan_delete_remote',
+    mkdir_local: 'plan_mkdir_local',
+    mkdir_remote: 'plan_mkdir_remote',
+    conflict: 'plan_conflict',
+};
+
+async function showPlanPreview(id) {
+    planPairId = id;
+    const summary = document.getElementById('plan-summary');
+    const list = document.getElementById('plan-actions');
+    summary.textContent = window.t('planning_sync');
+    list.innerHTML = '';
+    planModalOverlay.classList.add('open');
+
+    try {
+        const plan = await invoke('plan_sync', { pairId: id });
+        const counts = {};
+        plan.actions.forEach(a => counts[a.action] = (counts[a.action] || 0) + 1);
+        summary.textContent = plan.actions.length
+            ? Object.entries(counts).map(([action, n]) => `${window.t(planLabels[action])}: ${n}`).join(' · ')
+            : window.t('plan_nothing_to_do');
+
+        plan.actions.slice(0, PLAN_PREVIEW_LIMIT).forEach(a => {
+            const row = document.createElement('div');
+            row.className = 'folder-tree-row';
+            const size = a.size != null ? ` (${formatBytes(a.size)})` : '';
+            row.textContent = `${window.t(planLabels[a.action])}  ${a.path}${size}`;
+            list.appendChild(row);
+        });
+        if (plan.actions.length > PLAN_PREVIEW_LIMIT) {
+            list.insertAdjacentHTML('beforeend', `<div class="folder-tree-empty">+${plan.actions.length - PLAN_PREVIEW_LIMIT}</div>`);
+        }
+    } catch (err) {
+        summary.textContent = '';
+        showToast(window.t('failed_plan_sync') + ' ' + err, 'error');
+    }
+}
+
+document.getElementById('btn-plan-start').addEventListener('click', async () => {
+    planModalOverlay.classList.remove('open');
+    try {
+        await invoke('start_sync_pair', { id: planPairId });
+        showToast(window.t('folder_synced'), 'success');
+    } catch (err) {
+        showToast(window.t('failed_sync') + ' ' + err, 'error');
+    }
+    await loadPairs();
+});
+
+document.getElementById('btn-plan-cancel').addEventListener('click', async () => {
+    planModalOverlay.classList.remove('open');
+    try {
+        await invoke('remove_sync_pair', { id: planPairId });
+    } catch (err) {
+        showToast(window.t('failed_remove_pair') + ' ' + err, 'error');
+    }
+    await loadPairs();
+});
+
 // ---- Modal events ----
 btnAdd.addEventListener('click', () => {
     modalOverlay.classList.add('open');
@@ -571,9 +832,10 @@ btnBrowse.addEventListener('click', async () => {
 document.querySelectorAll('.provider-card').forEach(card => {
     card.addEventListener('click', () => {
         const provider = card.dataset.provider;
-        if (provider === 'icloud' || provider === 'onedrive') return;
+        if (provider === 'icloud') return;
 
         currentProvider = provider;
+        hideConnectionForm();
         document.querySelectorAll('.provider-card').forEach(c => c.classList.remove('selected'));
         card.classList.add('selected');
         card.querySelector('input').checked = true;
@@ -591,9 +853,10 @@ addForm.addEventListener('submit', async e => {
     const remoteName = _selectedRemoteName || inputRemoteSearch.value || 'Root Directory';
     const accountId = selectAccount.value;
     const provider = currentProvider;
+    const mode = selectMode.value;
 
     if (!local || !remote || !accountId) return;
-    await addPair(local, remote, remoteName, provider, accountId);
+    await addPair(local, remote, remoteName, provider, accountId, mode);
 });
 
 // ---- Sidebar ----
@@ -617,6 +880,7 @@ async function openFolderDetail(id) {
     currentViewPath = pair.local_path;
     document.getElementById('detail-folder-name').textContent = pair.local_path.split(/[\\/]/).pop() || pair.local_path;
     document.getElementById('detail-folder-path').textContent = pair.local_path;
+    detailSyncMode.value = pair.mode;
 
     mainContent.style.display = 'none';
     detailView.style.display = 'block';
@@ -630,6 +894,7 @@ async function loadFileTable() {
 
     try {
         const files = await invoke('list_local_files', { path: currentViewPath });
+        pairConflicts = await invoke('list_conflicts', { pairId: currentPair.id });
         document.getElementById('detail-folder-path').textContent = currentViewPath;
 
         files.sort((a, b) => {
@@ -657,7 +922,23 @@ async function loadFileTable() {
     }
 }
 
+function findConflictCopy(file) {
+    const filePath = file.path.replace(/\\/g, '/');
+    const root = currentPair.local_path.replace(/\\/g, '/');
+    return pairConflicts.find(c => `${root}/${c.conflict_path}` === filePath);
+}
+
+function renderConflictActions(conflict) {
+    if (!conflict) return '';
+    return ['keep_local', 'keep_remote', 'keep_both'].map(resolution => `
+        <button class="btn-file-action conflict" onclick="resolveConflict(event, ${conflict.id}, '${resolution}')" title="${window.t(resolution)}">
+            ${resolution === 'keep_local' ? '⬆' : resolution === 'keep_remote' ? '⬇' : '⇅'}
+        </button>
+    `).join('');
+}
+
 function renderFileRow(file) {
+    const conflict = findConflictCopy(file);
     const sizeStr = file.is_dir ? '--' : formatBytes(file.size);
     const dateStr = new Date(file.modified_at * 1000).toLocaleString();
     const icon = file.is_dir
@@ -673,10 +954,11 @@ function renderFileRow(file) {
                 </div>
             </td>
             <td>${sizeStr}</td>
-            <td class="file-status-cell">${renderFileStatus('Idle')}</td>
+            <td class="file-status-cell">${renderFileStatus(conflict ? 'Conflict' : 'Idle')}</td>
             <td>${dateStr}</td>
             <td>
                 <div class="file-actions">
+                    ${renderConflictActions(conflict)}
                     <button class="btn-file-action delete" onclick="deleteFile(event, '${file.path.replace(/\\/g, '/')}')" title="Delete">
                         <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polyline points="3 6 5 6 21 6"/><path d="M19 6v14a2 2 0 0 1-2 2H7a2 2 0 0 1-2-2V6m3 0V4a2 2 0 0 1 2-2h4a2 2 0 0 1 2 2v2"/></svg>
                     </button>
@@ -692,11 +974,11 @@ function navigateToSubfolder(path) {
 }
 window.navigateToSubfolder = navigateToSubfolder;
 
-function renderFileStatus(type, message) {
+function renderFileStatus(type, message, percent) {
     if (type === 'Idle') return `<span class="file-status-idle">-</span>`;
 
-    if (type === 'Syncing' || type === 'Downloading') {
-        const label = type === 'Syncing' ? 'Syncing...' : 'Downloading...';
+    if (type === 'Syncing' || type === 'Downloading' || type === 'Progress') {
+        const label = type === 'Progress' ? `${percent}%` : type === 'Syncing' ? 'Syncing...' : 'Downloading...';
         return `
             <div class="file-status-container syncing">
                 <div class="sync-spinner-mini"></div>
@@ -716,6 +998,10 @@ function renderFileStatus(type, message) {
 
     if (type === 'Deleted') return `<span class="file-status-deleted">Deleted</span>`;
 
+    if (type === 'Moved') return `<span class="file-status-uploaded">Moved</span>`;
+
+    if (type === 'Conflict') return `<span class="file-status-conflict">${window.t('conflict')}</span>`;
+
     if (type === 'Error') {
         return `
             <div class="file-status-container error" title="${message}">
@@ -752,6 +1038,22 @@ async function deleteFile(event, path) {
 }
 window.deleteFile = deleteFile;
 
+async function resolveConflict(event, id, resolution) {
+    if (event) {
+        event.stopPropagation();
+        event.preventDefault();
+    }
+
+    try {
+        await invoke('resolve_conflict', { id, resolution });
+        showToast(window.t('conflict_resolved'), 'success');
+        loadFileTable();
+    } catch (err) {
+        showToast(window.t('failed_resolve_conflict') + ' ' + err, 'error');
+    }
+}
+window.resolveConflict = resolveConflict;
+
 btnBack.addEventListener('click', () => {
     if (currentPair && currentViewPath && currentViewPath !== currentPair.local_path) {
         const path = currentViewPath.replace(/\\/g, '/');
@@ -787,6 +1089,240 @@ btnAddFile.addEventListener('click', async () => {
     }
 });
 
+// ---- Sync mode ----
+detailSyncMode.addEventListener('change', async () => {
+    if (!currentPair) return;
+
+    try {
+        await invoke('set_sync_mode', { pairId: currentPair.id, mode: detailSyncMode.value });
+        currentPair.mode = detailSyncMode.value;
+        showToast(window.t('sync_mode_saved'), 'success');
+    } catch (err) {
+        detailSyncMode.value = currentPair.mode;
+        showToast(window.t('failed_save_sync_mode') + ' ' + err, 'error');
+    }
+});
+
+// ---- Ignore rules ----
+btnIgnoreRules.addEventListener('click', () => {
+    if (!currentPair) return;
+    inputIgnoreRules.value = currentPair.ignore_rules;
+    ignoreModalOverlay.classList.add('open');
+});
+
+function closeIgnoreModal() {
+    ignoreModalOverlay.classList.remove('open');
+}
+
+document.getElementById('ignore-modal-close').addEventListener('click', closeIgnoreModal);
+document.getElementById('btn-ignore-cancel').addEventListener('click', closeIgnoreModal);
+
+ignoreForm.addEventListener('submit', async e => {
+    e.preventDefault();
+    if (!currentPair) return;
+
+    try {
+        await invoke('set_ignore_rules', { pairId: currentPair.id, rules: inputIgnoreRules.value });
+        currentPair.ignore_rules = inputIgnoreRules.value;
+        closeIgnoreModal();
+        showToast(window.t('ignore_rules_saved'), 'success');
+    } catch (err) {
+        showToast(window.t('failed_save_ignore_rules') + ' ' + err, 'error');
+    }
+});
+
+// ---- Local trash ----
+const trashModalOverlay = document.getElementById('trash-modal-overlay');
+const trashList = document.getElementById('trash-list');
+const trashSource = document.getElementById('trash-source');
+const inputTrashRetention = document.getElementById('input-trash-retention');
+const inputPermanentDelete = document.getElementById('input-permanent-delete');
+
+async function loadTrash() {
+    trashList.innerHTML = '';
+    const remote = trashSource.value === 'remote';
+    document.getElementById('trash-hint').textContent = window.t(remote ? 'remote_trash_hint' : 'trash_hint');
+    document.getElementById('trash-retention-group').style.display = remote ? 'none' : '';
+    document.getElementById('permanent-delete-group').style.display = remote ? '' : 'none';
+    try {
+        const items = await invoke(remote ? 'list_remote_trash' : 'list_trash', { pairId: currentPair.id });
+        if (!items.length) {
+            trashList.innerHTML = `<div class="folder-tree-empty">${window.t('trash_empty')}</div>`;
+            return;
+        }
+        items.forEach(item => {
+            const row = document.createElement('div');
+            row.className = 'folder-tree-row';
+            const label = document.createElement('span');
+            label.style.flex = '1';
+            const size = item.size != null ? ` (${formatBytes(item.size)})` : '';
+            const reason = item.reason ? ` · ${window.t('trash_' + item.reason)}` : '';
+            label.textContent = `${item.path}${item.is_dir ? '/' : ''}${size}${reason} · ${new Date(item.trashed_at * 1000).toLocaleString()}`;
+            row.appendChild(label);
+            row.insertAdjacentHTML('beforeend', `
+                <button class="btn-file-action" onclick="restoreTrashItem(${item.id})" title="${window.t('restore')}">↺</button>
+            `);
+            if (!remote) {
+                row.insertAdjacentHTML('beforeend', `
+                    <button class="btn-file-action delete" onclick="deleteTrashItem(${item.id})" title="${window.t('delete_forever')}">✕</button>
+                `);
+            }
+            trashList.appendChild(row);
+        });
+    } catch (err) {
+        showToast(window.t('failed_load_trash') + ' ' + err, 'error');
+    }
+}
+
+document.getElementById('btn-trash').addEventListener('click', () => {
+    if (!currentPair) return;
+    inputTrashRetention.value = currentPair.trash_retention_days;
+    inputPermanentDelete.checked = currentPair.permanent_delete;
+    trashSource.value = 'local';
+    trashModalOverlay.classList.add('open');
+    loadTrash();
+});
+
+function closeTrashModal() {
+    trashModalOverlay.classList.remove('open');
+}
+
+document.getElementById('trash-modal-close').addEventListener('click', closeTrashModal);
+document.getElementById('btn-trash-done').addEventListener('click', closeTrashModal);
+trashSource.addEventListener('change', loadTrash);
+
+async function restoreTrashItem(id) {
+    const remote = trashSource.value === 'remote';
+    try {
+        await invoke(remote ? 'restore_remote_trash_item' : 'restore_trash_item', { id });
+        showToast(window.t('trash_item_restored'), 'success');
+        loadTrash();
+        loadFileTable();
+    } catch (err) {
+        showToast(window.t('failed_restore_trash') + ' ' + err, 'error');
+    }
+}
+window.restoreTrashItem = restoreTrashItem;
+
+async function deleteTrashItem(id) {
+    try {
+        await invoke('delete_trash_item', { id });
+        loadTrash();
+    } catch (err) {
+        showToast(window.t('failed_delete') + ' ' + err, 'error');
+    }
+}
+window.deleteTrashItem = deleteTrashItem;
+
+inputTrashRetention.addEventListener('change', async () => {
+    if (!currentPair) return;
+    const days = Math.max(0, parseInt(inputTrashRetention.value, 10) || 0);
+    try {
+        await invoke('set_trash_retention', { pairId: currentPair.id, days });
+        currentPair.trash_retention_days = days;
+        inputTrashRetention.value = days;
+        showToast(window.t('trash_retention_saved'), 'success');
+    } catch (err) {
+        inputTrashRetention.value = currentPair.trash_retention_days;
+        showToast(window.t('failed_save_trash_retention') + ' ' + err, 'error');
+    }
+});
+
+inputPermanentDelete.addEventListener('change', async () => {
+    if (!currentPair) return;
+    const permanent = inputPermanentDelete.checked;
+    try {
+        await invoke('set_permanent_delete', { pairId: currentPair.id, permanent });
+        currentPair.permanent_delete = permanent;
+    } catch (err) {
+        inputPermanentDelete.checked = currentPair.permanent_delete;
+        showToast(window.t('failed_save_permanent_delete') + ' ' + err, 'error');
+    }
+});
+
+// ---- Selective sync ----
+let selectiveSyncChanged = false;
+
+btnSelectiveSync.addEventListener('click', () => {
+    if (!currentPair) return;
+    selectiveSyncChanged = false;
+    selectiveModalOverlay.classList.add('open');
+    loadFolderChildren(folderTreeRoot, '', null, false);
+});
+
+async function closeSelectiveModal() {
+    selectiveModalOverlay.classList.remove('open');
+    // Pick up folders that were included again
+    if (selectiveSyncChanged && currentPair) {
+        await syncPairNow(null, currentPair.id);
+    }
+}
+
+document.getElementById('selective-modal-close').addEventListener('click', closeSelectiveModal);
+document.getElementById('btn-selective-done').addEventListener('click', closeSelectiveModal);
+
+async function loadFolderChildren(container, parentKey, parentId, parentExcluded) {
+    container.innerHTML = `<div class="folder-tree-empty">${window.t('loading_folders')}</div>`;
+    try {
+        const folders = await invoke('list_folder_tree', { pairId: currentPair.id, parentKey, parentId });
+        container.innerHTML = '';
+        if (!folders.length) {
+            container.innerHTML = `<div class="folder-tree-empty">${window.t('no_subfolders')}</div>`;
+            return;
+        }
+        const reload = () => loadFolderChildren(container, parentKey, parentId, parentExcluded);
+        folders.forEach(folder => container.appendChild(renderFolderNode(folder, parentExcluded, reload)));
+    } catch (err) {
+        container.innerHTML = '';
+        showToast(window.t('failed_load_folders') + ' ' + err, 'error');
+    }
+}
+
+function renderFolderNode(folder, parentExcluded, reload) {
+    const node = document.createElement('div');
+    const row = document.createElement('div');
+    row.className = 'folder-tree-row';
+
+    const toggle = document.createElement('button');
+    toggle.className = 'folder-tree-toggle';
+    toggle.textContent = '▸';
+
+    const checkbox = document.createElement('input');
+    checkbox.type = 'checkbox';
+    checkbox.checked = folder.state !== 'excluded';
+    checkbox.indeterminate = folder.state === 'partial';
+    // Folders inside an unchecked one follow their parent
+    checkbox.disabled = parentExcluded;
+
+    const label = document.createElement('span');
+    label.textContent = folder.name;
+
+    const children = document.createElement('div');
+    children.className = 'folder-tree-children';
+    children.style.display = 'none';
+
+    toggle.addEventListener('click', () => {
+        const opening = children.style.display === 'none';
+        children.style.display = opening ? 'block' : 'none';
+        toggle.textContent = opening ? '▾' : '▸';
+        if (opening) loadFolderChildren(children, folder.path, folder.id, folder.state === 'excluded');
+    });
+
+    checkbox.addEventListener('change', async () => {
+        try {
+            await invoke('set_folder_excluded', { pairId: currentPair.id, path: folder.path, excluded: !checkbox.checked });
+            selectiveSyncChanged = true;
+        } catch (err) {
+            showToast(window.t('failed_update_folder') + ' ' + err, 'error');
+        }
+        reload();
+    });
+
+    row.append(toggle, checkbox, label);
+    node.append(row, children);
+    return node;
+}
+
 function formatBytes(bytes, decimals = 2) {
     if (bytes === 0) return '0 Bytes';
     const k = 1024;
@@ -65,11 +65,93 @@ const translations = {
         failed_remove_pair: "Failed to remove sync pair:",
         session_expired: "Session expired. Please reconnect your account.",
         connected_accounts: "Connected Accounts",
-        add_another_account: "Add Google Account",
+        add_another_account: "Add Account",
         select_account: "Select Account",
         no_accounts_connected: "No accounts connected yet.",
         search_folders: "Search folders...",
-        sync_now: "Sync Now"
+        sync_now: "Sync Now",
+        conflict_detected: "Conflict detected, kept both versions:",
+        conflict: "Conflict",
+        keep_local: "Keep mine",
+        keep_remote: "Keep cloud version",
+        keep_both: "Keep both",
+        conflict_resolved: "Conflict resolved",
+        failed_resolve_conflict: "Failed to resolve conflict:",
+        connect: "Connect",
+        endpoint: "Endpoint URL",
+        region: "Region",
+        bucket: "Bucket",
+        access_key_id: "Access key ID",
+        secret_access_key: "Secret access key",
+        path_style: "Path-style addressing (MinIO, most self-hosted servers)",
+        field_required: "required",
+        webdav_url: "WebDAV URL",
+        username: "Username",
+        app_password: "Password or app password",
+        host: "Host",
+        port: "Port (22)",
+        remote_path: "Remote folder",
+        key_path: "Private key file (empty: ssh-agent)",
+        key_passphrase: "Key passphrase (optional)",
+        remote_hash: "Verify content with sha256sum on the server",
+        local_folder: "Local Folder",
+        target_folder: "Target folder (NAS mount, USB drive)",
+        ignore_rules: "Ignore Rules",
+        ignore_rules_hint: "One pattern per line, gitignore syntax. Folders can add their own .omnisyncignore file.",
+        save: "Save",
+        ignore_rules_saved: "Ignore rules saved. They apply from the next sync.",
+        failed_save_ignore_rules: "Failed to save ignore rules:",
+        choose_folders: "Choose Folders",
+        choose_folders_hint: "Unchecked folders are not downloaded and are left alone in the cloud.",
+        done: "Done",
+        no_subfolders: "No subfolders",
+        failed_load_folders: "Failed to load folders:",
+        failed_update_folder: "Failed to update folder:",
+        sync_mode: "Sync Mode",
+        mode_two_way: "Two-way",
+        mode_upload_only: "Upload only (backup)",
+        mode_download_only: "Download only",
+        mode_mirror: "Mirror to cloud",
+        sync_mode_saved: "Sync mode saved. It applies from the next sync.",
+        failed_save_sync_mode: "Failed to save sync mode:",
+        sync_preview: "Sync Preview",
+        start_sync: "Start Sync",
+        planning_sync: "Working out what will be synced...",
+        plan_nothing_to_do: "Both sides are already in sync.",
+        plan_upload: "Upload",
+        plan_download: "Download",
+        plan_delete_local: "Delete locally",
+        plan_delete_remote: "Delete in cloud",
+        plan_mkdir_local: "New local folder",
+        plan_mkdir_remote: "New cloud folder",
+        plan_conflict: "Conflict",
+        failed_plan_sync: "Failed to preview sync:",
+        failed_sync: "Sync failed:",
+        deletions_pending: "Deletions on hold",
+        deletions_pending_hint: "files were removed at once from",
+        decide_later: "Later",
+        restore_files: "Restore Files",
+        delete_in_cloud: "Delete in Cloud",
+        failed_load_deletions: "Failed to list pending deletions:",
+        trash: "Trash",
+        trash_hint: "Local files that sync deleted or replaced with a newer cloud version.",
+        trash_retention: "Keep for (days, 0 = forever)",
+        trash_empty: "The trash is empty",
+        trash_deleted: "deleted in the cloud",
+        trash_overwritten: "older version",
+        trash_replaced: "replaced by a restore",
+        restore: "Restore",
+        delete_forever: "Delete forever",
+        trash_item_restored: "Restored from the trash",
+        failed_load_trash: "Failed to load trash:",
+        failed_restore_trash: "Failed to restore:",
+        trash_retention_saved: "Trash retention saved",
+        failed_save_trash_retention: "Failed to save trash retention:",
+        trash_local: "On this computer",
+        trash_remote: "In the cloud",
+        remote_trash_hint: "Cloud entries that sync deleted, still in the provider trash. Restored entries download on the next sync.",
+        permanent_delete: "Delete on the cloud for good instead of moving to its trash",
+        failed_save_permanent_delete: "Failed to save delete setting:"
     },
     vi: {
         providers: "Các Dịch Vụ Lưu Trữ Đám Mây",
@@ -137,11 +219,93 @@ const translations = {
         failed_remove_pair: "Gỡ bỏ thất bại:",
         session_expired: "Phiên đã hết hạn. Vui lòng kết nối lại tài khoản.",
         connected_accounts: "Tài Khoản Đã Kết Nối",
-        add_another_account: "Thêm Tài Khoản Google",
+        add_another_account: "Thêm Tài Khoản",
         select_account: "Chọn Tài Khoản",
         no_accounts_connected: "Chưa có tài khoản nào được kết nối.",
         search_folders: "Tìm kiếm thư mục...",
-        sync_now: "Đồng bộ ngay"
+        sync_now: "Đồng bộ ngay",
+        conflict_detected: "Phát hiện xung đột, đã giữ cả hai phiên bản:",
+        conflict: "Xung đột",
+        keep_local: "Giữ bản của tôi",
+        keep_remote: "Giữ bản trên đám mây",
+        keep_both: "Giữ cả hai",
+        conflict_resolved: "Đã xử lý xung đột",
+        failed_resolve_conflict: "Xử lý xung đột thất bại:",
+        connect: "Kết nối",
+        endpoint: "Địa chỉ endpoint",
+        region: "Vùng",
+        bucket: "Bucket",
+        access_key_id: "Access key ID",
+        secret_access_key: "Secret access key",
+        path_style: "Địa chỉ kiểu path (MinIO, hầu hết máy chủ tự host)",
+        field_required: "bắt buộc",
+        webdav_url: "Địa chỉ WebDAV",
+        username: "Tên đăng nhập",
+        app_password: "Mật khẩu hoặc mật khẩu ứng dụng",
+        host: "Máy chủ",
+        port: "Cổng (22)",
+        remote_path: "Thư mục trên máy chủ",
+        key_path: "Tệp khóa riêng (để trống: ssh-agent)",
+        key_passphrase: "Mật khẩu khóa (không bắt buộc)",
+        remote_hash: "Kiểm tra nội dung bằng sha256sum trên máy chủ",
+        local_folder: "Thư mục cục bộ",
+        target_folder: "Thư mục đích (ổ NAS, USB)",
+        ignore_rules: "Quy tắc bỏ qua",
+        ignore_rules_hint: "Mỗi dòng một mẫu, theo cú pháp gitignore. Thư mục có thể có tệp .omnisyncignore riêng.",
+        save: "Lưu",
+        ignore_rules_saved: "Đã lưu quy tắc bỏ qua. Áp dụng từ lần đồng bộ tiếp theo.",
+        failed_save_ignore_rules: "Không thể lưu quy tắc bỏ qua:",
+        choose_folders: "Chọn thư mục",
+        choose_folders_hint: "Thư mục bỏ chọn sẽ không được tải xuống và được giữ nguyên trên đám mây.",
+        done: "Xong",
+        no_subfolders: "Không có thư mục con",
+        failed_load_folders: "Không thể tải thư mục:",
+        failed_update_folder: "Không thể cập nhật thư mục:",
+        sync_mode: "Chế độ đồng bộ",
+        mode_two_way: "Hai chiều",
+        mode_upload_only: "Chỉ tải lên (sao lưu)",
+        mode_download_only: "Chỉ tải xuống",
+        mode_mirror: "Phản chiếu lên đám mây",
+        sync_mode_saved: "Đã lưu chế độ đồng bộ. Áp dụng từ lần đồng bộ tiếp theo.",
+        failed_save_sync_mode: "Không thể lưu chế độ đồng bộ:",
+        sync_preview: "Xem trước đồng bộ",
+        start_sync: "Bắt đầu đồng bộ",
+        planning_sync: "Đang tính toán những gì sẽ được đồng bộ...",
+        plan_nothing_to_do: "Hai bên đã đồng bộ.",
+        plan_upload: "Tải lên",
+        plan_download: "Tải xuống",
+        plan_delete_local: "Xóa trên máy",
+        plan_delete_remote: "Xóa trên đám mây",
+        plan_mkdir_local: "Thư mục mới trên máy",
+        plan_mkdir_remote: "Thư mục mới trên đám mây",
+        plan_conflict: "Xung đột",
+        failed_plan_sync: "Không thể xem trước đồng bộ:",
+        failed_sync: "Đồng bộ thất bại:",
+        deletions_pending: "Lệnh xóa đang chờ",
+        deletions_pending_hint: "tệp đã bị xóa cùng lúc khỏi",
+        decide_later: "Để sau",
+        restore_files: "Khôi phục tệp",
+        delete_in_cloud: "Xóa trên đám mây",
+        failed_load_deletions: "Không thể liệt kê các lệnh xóa đang chờ:",
+        trash: "Thùng rác",
+        trash_hint: "Các tệp cục bộ bị đồng bộ xóa hoặc thay bằng phiên bản mới hơn từ đám mây.",
+        trash_retention: "Giữ trong (ngày, 0 = mãi mãi)",
+        trash_empty: "Thùng rác trống",
+        trash_deleted: "đã xóa trên đám mây",
+        trash_overwritten: "phiên bản cũ",
+        trash_replaced: "bị thay khi khôi phục",
+        restore: "Khôi phục",
+        delete_forever: "Xóa vĩnh viễn",
+        trash_item_restored: "Đã khôi phục từ thùng rác",
+        failed_load_trash: "Tải thùng rác thất bại:",
+        failed_restore_trash: "Khôi phục thất bại:",
+        trash_retention_saved: "Đã lưu thời gian giữ thùng rác",
+        failed_save_trash_retention: "Lưu thời gian giữ thùng rác thất bại:",
+        trash_local: "Trên máy tính này",
+        trash_remote: "Trên đám mây",
+        remote_trash_hint: "Các mục trên đám mây bị đồng bộ xóa, vẫn còn trong thùng rác của nhà cung cấp. Mục được khôi phục sẽ tải về ở lần đồng bộ tới.",
+        permanent_delete: "Xóa vĩnh viễn trên đám mây thay vì chuyển vào thùng rác",
+        failed_save_permanent_delete: "Lưu cài đặt xóa thất bại:"
     }
 };
 
@@ -1,3 +1,5 @@
+use omnisync_core::credential_store::{KeyringCredentialStore, KEYRING_SERVICE};
+use omnisync_core::models::SyncMode;
 use omnisync_core::SyncEngine;
 use serde::Serialize;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
@@ -75,6 +77,10 @@ struct SyncPairResponse {
     account_id: String,
     status: String,
     created_at: i64,
+    ignore_rules: String,
+    mode: String,
+    trash_retention_days: i64,
+    permanent_delete: bool,
 }
 
 #[tauri::command]
@@ -95,6 +101,10 @@ async fn get_sync_pairs(state: State<'_, AppState>) -> Result<Vec<SyncPairRespon
             account_id: p.account_id,
             status: p.status,
             created_at: p.created_at,
+            ignore_rules: p.ignore_rules,
+            mode: p.mode,
+            trash_retention_days: p.trash_retention_days,
+            permanent_delete: p.permanent_delete,
         })
         .collect())
 }
@@ -107,11 +117,26 @@ async fn add_sync_pair(
     remote_name: String,
     provider_id: String,
     account_id: String,
+    mode: Option<String>,
+    paused: Option<bool>,
 ) -> Result<i64, String> {
+    let mode: Option<SyncMode> = mode.map(|m| m.parse()).transpose().map_err(|e: anyhow::Error| e.to_string())?;
     let id = state.engine
         .add_sync_pair(&local_path, &remote_path, &remote_name, &provider_id, &account_id)
         .await
         .map_err(|e| format!("Failed to add sync pair: {}", e))?;
+    if let Some(mode) = mode {
+        state.engine
+            .set_sync_mode(id, mode)
+            .await
+            .map_err(|e| format!("Failed to set sync mode: {}", e))?;
+    }
+    if paused.unwrap_or(false) {
+        state.engine
+            .set_pair_active(id, false)
+            .await
+            .map_err(|e| format!("Failed to pause sync pair: {}", e))?;
+    }
     Ok(id)
 }
 
@@ -124,6 +149,44 @@ async fn remove_sync_pair(state: State<'_, AppState>, id: i64) -> Result<(), Str
     Ok(())
 }
 
+#[tauri::command]
+async fn set_sync_mode(state: State<'_, AppState>, pair_id: i64, mode: String) -> Result<(), String> {
+    let mode: SyncMode = mode.parse().map_err(|e: anyhow::Error| e.to_string())?;
+    state.engine
+        .set_sync_mode(pair_id, mode)
+        .await
+        .map_err(|e| format!("Failed to set sync mode: {}", e))
+}
+
+#[tauri::command]
+async fn set_ignore_rules(state: State<'_, AppState>, pair_id: i64, rules: String) -> Result<(), String> {
+    state.engine
+        .set_ignore_rules(pair_id, &rules)
+        .await
+        .map_err(|e| format!("Failed to save ignore rules: {}", e))
+}
+
+#[tauri::command]
+async fn list_folder_tree(
+    state: State<'_, AppState>,
+    pair_id: i64,
+    parent_key: Option<String>,
+    parent_id: Option<String>,
+) -> Result<Vec<omnisync_core::models::FolderNode>, String> {
+    state.engine
+        .list_folder_tree(pair_id, parent_key.as_deref().unwrap_or(""), parent_id.as_deref())
+        .await
+        .map_err(|e| format!("Failed to list folders: {}", e))
+}
+
+#[tauri::command]
+async fn set_folder_excluded(state: State<'_, AppState>, pair_id: i64, path: String, excluded: bool) -> Result<(), String> {
+    state.engine
+        .set_folder_excluded(pair_id, &path, excluded)
+        .await
+        .map_err(|e| format!("Failed to update folder: {}", e))
+}
+
 #[tauri::command]
 async fn sync_pair_now(app: tauri::AppHandle, state: State<'_, AppState>, id: i64) -> Result<(), String> {
     let pairs = state.engine.get_sync_pairs().await.map_err(|e| e.to_string())?;
@@ -144,6 +207,135 @@ async fn sync_pair_now(app: tauri::AppHandle, state: State<'_, AppState>, id: i6
     Ok(())
 }
 
+/// Status callback that forwards engine events to the window
+fn status_emitter(app: tauri::AppHandle) -> Arc<impl Fn(omnisync_core::engine::SyncStatus) + Send + Sync + 'static> {
+    Arc::new(move |status: omnisync_core::engine::SyncStatus| {
+        use tauri::Emitter;
+        let _ = app.emit("sync-status", status);
+    })
+}
+
+#[tauri::command]
+async fn pending_deletions(state: State<'_, AppState>, pair_id: i64) -> Result<Vec<String>, String> {
+    state.engine
+        .pending_deletions(pair_id)
+        .await
+        .map_err(|e| format!("Failed to list pending deletions: {}", e))
+}
+
+#[tauri::command]
+async fn confirm_deletions(app: tauri::AppHandle, state: State<'_, AppState>, pair_id: i64) -> Result<(), String> {
+    let engine = state.engine.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = engine.confirm_deletions(pair_id, status_emitter(app)).await {
+            eprintln!("Confirmed sync failed for pair {}: {:?}", pair_id, e);
+        }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+async fn restore_deletions(app: tauri::AppHandle, state: State<'_, AppState>, pair_id: i64) -> Result<(), String> {
+    let engine = state.engine.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = engine.restore_deletions(pair_id, status_emitter(app)).await {
+            eprintln!("Restoring files failed for pair {}: {:?}", pair_id, e);
+        }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+async fn plan_sync(state: State<'_, AppState>, pair_id: i64) -> Result<omnisync_core::reconcile::SyncPlan, String> {
+    state.engine
+        .plan_sync(pair_id)
+        .await
+        .map_err(|e| format!("Failed to plan sync: {}", e))
+}
+
+/// Resume a pair (e.g. one added paused for a preview) and sync it right away
+#[tauri::command]
+async fn start_sync_pair(app: tauri::AppHandle, state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    state.engine
+        .set_pair_active(id, true)
+        .await
+        .map_err(|e| format!("Failed to start sync pair: {}", e))?;
+    sync_pair_now(app, state, id).await
+}
+
+#[tauri::command]
+async fn list_conflicts(state: State<'_, AppState>, pair_id: Option<i64>) -> Result<Vec<omnisync_core::models::Conflict>, String> {
+    state.engine
+        .list_conflicts(pair_id)
+        .await
+        .map_err(|e| format!("Failed to list conflicts: {}", e))
+}
+
+#[tauri::command]
+async fn resolve_conflict(state: State<'_, AppState>, id: i64, resolution: String) -> Result<(), String> {
+    let resolution = resolution.parse().map_err(|e: anyhow::Error| e.to_string())?;
+    state.engine
+        .resolve_conflict(id, resolution)
+        .await
+        .map_err(|e| format!("Failed to resolve conflict: {}", e))
+}
+
+#[tauri::command]
+async fn list_trash(state: State<'_, AppState>, pair_id: i64) -> Result<Vec<omnisync_core::models::TrashItem>, String> {
+    state.engine
+        .list_trash(Some(pair_id))
+        .await
+        .map_err(|e| format!("Failed to list trash: {}", e))
+}
+
+#[tauri::command]
+async fn restore_trash_item(state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    state.engine
+        .restore_trash_item(id)
+        .await
+        .map_err(|e| format!("Failed to restore trash item: {}", e))
+}
+
+#[tauri::command]
+async fn delete_trash_item(state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    state.engine
+        .delete_trash_item(id)
+        .await
+        .map_err(|e| format!("Failed to delete trash item: {}", e))
+}
+
+#[tauri::command]
+async fn set_trash_retention(state: State<'_, AppState>, pair_id: i64, days: i64) -> Result<(), String> {
+    state.engine
+        .set_trash_retention(pair_id, days)
+        .await
+        .map_err(|e| format!("Failed to save trash retention: {}", e))
+}
+
+#[tauri::command]
+async fn list_remote_trash(state: State<'_, AppState>, pair_id: i64) -> Result<Vec<omnisync_core::models::RemoteTrashItem>, String> {
+    state.engine
+        .list_remote_trash(pair_id)
+        .await
+        .map_err(|e| format!("Failed to list cloud trash: {}", e))
+}
+
+#[tauri::command]
+async fn restore_remote_trash_item(state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    state.engine
+        .restore_remote_trash_item(id)
+        .await
+        .map_err(|e| format!("Failed to restore from cloud trash: {}", e))
+}
+
+#[tauri::command]
+async fn set_permanent_delete(state: State<'_, AppState>, pair_id: i64, permanent: bool) -> Result<(), String> {
+    state.engine
+        .set_permanent_delete(pair_id, permanent)
+        .await
+        .map_err(|e| format!("Failed to save delete setting: {}", e))
+}
+
 #[tauri::command]
 async fn list_remote_folders(state: State<'_, AppState>, account_id: String) -> Result<Vec<omnisync_core::provider::RemoteFolder>, String> {
     state.engine
@@ -193,36 +385,30 @@ async fn get_accounts_for_provider(state: State<'_, AppState>, provider_id: Stri
 #[tauri::command]
 async fn disconnect_account(state: State<'_, AppState>, account_id: String) -> Result<(), String> {
     state.engine
-        .disconnect_account(&account_id)
+        .sign_out(&account_id)
         .await
         .map_err(|e| format!("Failed to disconnect account: {}", e))?;
     Ok(())
 }
 
 #[tauri::command]
-async fn start_oauth(app: tauri::AppHandle, state: State<'_, AppState>, provider_id: String) -> Result<String, String> {
-    if provider_id != "gdrive" {
-        return Err("Provider not supported".to_string());
-    }
-
-    let client_id = omnisync_core::config::get_google_client_id();
-    let client_secret = omnisync_core::config::get_google_client_secret();
-
-    if client_id == "NOT_CONFIGURED" {
-        return Err("Google OAuth Client ID not configured in .env file".to_string());
-    }
+async fn connect_account(state: State<'_, AppState>, provider_id: String, config: serde_json::Value, secret: String) -> Result<String, String> {
+    state.engine
+        .connect_account(&provider_id, config, &secret)
+        .await
+        .map_err(|e| e.to_string())
+}
 
+#[tauri::command]
+async fn start_oauth(app: tauri::AppHandle, state: State<'_, AppState>, provider_id: String) -> Result<String, String> {
     let (code_verifier, code_challenge) = omnisync_core::SyncEngine::generate_pkce();
+    let engine = state.engine.clone();
 
-    let auth_url = format!(
-        "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri=http://127.0.0.1:4420&response_type=code&scope=https://www.googleapis.com/auth/drive&access_type=offline&prompt=consent&code_challenge={}&code_challenge_method=S256",
-        client_id, code_challenge
-    );
+    let auth_url = engine.oauth_authorize_url(&provider_id, &code_challenge).map_err(|e| e.to_string())?;
 
     // Start the auth listener in the background *before* opening the browser
-    let engine = state.engine.clone();
     let auth_handle = tauri::async_runtime::spawn(async move {
-        engine.authenticate_google(&client_id, &client_secret, code_verifier).await
+        engine.authenticate(&provider_id, code_verifier).await
     });
 
     // Short sleep to give the background task time to bind the TcpListener
@@ -275,6 +461,15 @@ fn main() {
             });
 
             let engine = Arc::new(SyncEngine::new(pool));
+            // Tokens go to the OS keyring; without one they stay in the database
+            match KeyringCredentialStore::open(KEYRING_SERVICE) {
+                Ok(store) => {
+                    if let Err(e) = tauri::async_runtime::block_on(engine.use_credential_store(Arc::new(store))) {
+                        eprintln!("Failed to move tokens to the OS keyring: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("OS keyring unavailable, keeping tokens in the database: {}", e),
+            }
             let engine_clone = engine.clone();
 
             app.manage(AppState {
@@ -296,15 +491,34 @@ fn main() {
             get_sync_pairs,
             add_sync_pair,
             remove_sync_pair,
+            set_sync_mode,
+            set_ignore_rules,
+            list_folder_tree,
+            set_folder_excluded,
             list_remote_folders,
             get_all_accounts,
             get_accounts_for_provider,
             disconnect_account,
             start_oauth,
+            connect_account,
             list_local_files,
             delete_local_file,
             copy_file,
-            sync_pair_now
+            sync_pair_now,
+            plan_sync,
+            pending_deletions,
+            confirm_deletions,
+            restore_deletions,
+            start_sync_pair,
+            list_conflicts,
+            resolve_conflict,
+            list_trash,
+            restore_trash_item,
+            delete_trash_item,
+            set_trash_retention,
+            list_remote_trash,
+            restore_remote_trash_item,
+            set_permanent_delete
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");